@@ -0,0 +1,94 @@
+use std::collections::HashSet;
+
+use glam::{IVec3, Mat4, Vec3};
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode};
+
+use crate::renderer::voxel::{raycast_voxel, Chunk, FaceNormal};
+
+/// Fly camera moved with WASD, space and shift, and rotated with mouse motion.
+pub struct Camera {
+    pub position: Vec3,
+    /// Radians around the Y axis, zero looks along +X.
+    pub yaw: f32,
+    /// Radians above the horizon, clamped just short of straight up or down.
+    pub pitch: f32,
+    /// Units per second.
+    pub speed: f32,
+    /// Radians per pixel of mouse motion.
+    pub sensitivity: f32,
+    pub fov_y: f32,
+    pressed: HashSet<VirtualKeyCode>,
+}
+
+impl Camera {
+    pub fn new(position: Vec3, yaw: f32, pitch: f32) -> Self {
+        Self {
+            position,
+            yaw,
+            pitch,
+            speed: 5.0,
+            sensitivity: 0.002,
+            fov_y: 70f32.to_radians(),
+            pressed: HashSet::new(),
+        }
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        Vec3::new(
+            self.yaw.cos() * self.pitch.cos(),
+            self.pitch.sin(),
+            self.yaw.sin() * self.pitch.cos(),
+        )
+    }
+
+    pub fn process_keyboard(&mut self, input: &KeyboardInput) {
+        if let Some(keycode) = input.virtual_keycode {
+            match input.state {
+                ElementState::Pressed => self.pressed.insert(keycode),
+                ElementState::Released => self.pressed.remove(&keycode),
+            };
+        }
+    }
+
+    pub fn process_mouse_motion(&mut self, delta_x: f64, delta_y: f64) {
+        let limit = std::f32::consts::FRAC_PI_2 - 0.01;
+        self.yaw += delta_x as f32 * self.sensitivity;
+        self.pitch = (self.pitch - delta_y as f32 * self.sensitivity).clamp(-limit, limit);
+    }
+
+    /// Moves along the held keys, `delta_time` in seconds keeps the speed frame rate independent.
+    pub fn update(&mut self, delta_time: f32) {
+        let forward = self.forward();
+        let right = forward.cross(Vec3::Y).normalize();
+        let mut direction = Vec3::ZERO;
+        for (key, axis) in [
+            (VirtualKeyCode::W, forward),
+            (VirtualKeyCode::S, -forward),
+            (VirtualKeyCode::D, right),
+            (VirtualKeyCode::A, -right),
+            (VirtualKeyCode::Space, Vec3::Y),
+            (VirtualKeyCode::LShift, -Vec3::Y),
+        ] {
+            if self.pressed.contains(&key) {
+                direction += axis;
+            }
+        }
+        self.position += direction.normalize_or_zero() * self.speed * delta_time;
+    }
+
+    /// Block the camera looks at within `max_dist`, see `voxel::raycast_voxel`.
+    pub fn pick(&self, chunk: &Chunk, max_dist: f32) -> Option<(IVec3, FaceNormal)> {
+        raycast_voxel(self.position, self.forward(), chunk, max_dist)
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_to_rh(self.position, self.forward(), Vec3::Y)
+    }
+
+    /// Projection with Vulkan's 0..1 depth range and Y pointing down in clip space.
+    pub fn view_projection(&self, aspect_ratio: f32) -> Mat4 {
+        let mut projection = Mat4::perspective_rh(self.fov_y, aspect_ratio, 0.1, 1000.0);
+        projection.y_axis.y *= -1.0;
+        projection * self.view()
+    }
+}
@@ -1,25 +1,56 @@
 #![allow(dead_code)]
 
+use std::time::{Duration, Instant};
 
-use renderer::Renderer;
+use camera::Camera;
+use glam::Vec3;
+use log::error;
+use renderer::{Renderer, RendererError, Vertex};
 use winit::{
-    event::{Event, WindowEvent},
+    dpi::LogicalSize,
+    event::{DeviceEvent, ElementState, Event, MouseButton, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     platform::run_return::EventLoopExtRunReturn,
-    window::WindowBuilder,
+    window::{Window, WindowBuilder},
 };
 
 #[macro_use]
 extern crate lazy_static;
 
+mod camera;
 mod renderer;
 
+/// Initial inner size of the window in logical pixels.
+const WINDOW_SIZE: LogicalSize<f64> = LogicalSize::new(1280.0, 720.0);
+
+/// How often the FPS shown in the window title is refreshed.
+const TITLE_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
 fn main() {
-    env_logger::init();
+    init_logging();
     let mut event_loop = EventLoop::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
+    let builder = Renderer::builder();
+    let title = builder.config().app_name.clone();
+    let window = WindowBuilder::new()
+        .with_title(&title)
+        .with_inner_size(WINDOW_SIZE)
+        .build(&event_loop)
+        .unwrap();
+
+    let mut renderer = builder.build(&window).unwrap();
+    let (vertices, indices) = renderer::mesh::cube();
+    renderer.set_mesh(&vertices, &indices);
+    let offsets: Vec<[f32; 3]> = (-8..8)
+        .flat_map(|x| (-8..8).map(move |z| [x as f32 * 2.0, 0.0, z as f32 * 2.0]))
+        .collect();
+    renderer.set_instances(&offsets);
+    renderer.draw_lines(&axes());
 
-    let mut renderer = Renderer::new(&window);
+    let mut camera = Camera::new(Vec3::new(0.0, 3.0, 12.0), -std::f32::consts::FRAC_PI_2, 0.0);
+    // Mouse look is only active while the right mouse button is held.
+    let mut looking = false;
+    let start = Instant::now();
+    let mut last_title_update = start;
 
     event_loop.run_return(move |event, _, control_flow| {
         *control_flow = ControlFlow::Wait;
@@ -29,10 +60,100 @@ fn main() {
                 event: WindowEvent::CloseRequested,
                 window_id,
             } if window_id == window.id() => *control_flow = ControlFlow::Exit,
+            // A minimized window reports a zero size. Rendering pauses until it's restored, so
+            // the swapchain is only rebuilt once the size is nonzero again.
+            Event::WindowEvent {
+                event: WindowEvent::Resized(_),
+                window_id,
+            } if window_id == window.id() => {
+                if let Err(err) = renderer.recreate_swapchain(&window) {
+                    error!("Failed to recreate swapchain: {}", err);
+                }
+            }
+            // Moving to a display with another scale factor changes the physical size the
+            // swapchain needs, even where the logical size stays the same.
+            Event::WindowEvent {
+                event: WindowEvent::ScaleFactorChanged { .. },
+                window_id,
+            } if window_id == window.id() => {
+                if let Err(err) = renderer.recreate_swapchain(&window) {
+                    error!("Failed to recreate swapchain: {}", err);
+                }
+            }
+            // Input consumed by the overlay doesn't reach the camera.
+            #[cfg(feature = "egui")]
+            Event::WindowEvent {
+                ref event,
+                window_id,
+            } if window_id == window.id() && renderer.handle_ui_event(event) => (),
+            Event::WindowEvent {
+                event: WindowEvent::KeyboardInput { input, .. },
+                ..
+            } => camera.process_keyboard(&input),
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Right,
+                        ..
+                    },
+                ..
+            } => looking = state == ElementState::Pressed,
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } if looking => camera.process_mouse_motion(delta.0, delta.1),
+            Event::RedrawRequested(_) if is_minimized(&window) => (),
             Event::RedrawRequested(window_id) if window_id == window.id() => {
-                renderer.draw_frame();
+                camera.update(renderer.delta_time());
+                if last_title_update.elapsed() >= TITLE_UPDATE_INTERVAL {
+                    window.set_title(&format!("{} - {:.0} fps", title, renderer.fps()));
+                    last_title_update = Instant::now();
+                }
+
+                let size = window.inner_size();
+                renderer.set_view_projection(
+                    camera.view_projection(size.width as f32 / size.height as f32),
+                );
+                renderer.set_push_data(&start.elapsed().as_secs_f32());
+                #[cfg(feature = "egui")]
+                {
+                    let fps = renderer.fps();
+                    let budget = renderer.memory_budget();
+                    renderer.run_ui(&window, |ctx| {
+                        egui::Window::new("Debug").show(ctx, |ui| {
+                            ui.label(format!("{:.0} fps", fps));
+                            for (index, heap) in budget.iter().enumerate() {
+                                ui.label(format!(
+                                    "Heap {}: {} / {} MiB",
+                                    index,
+                                    heap.used >> 20,
+                                    heap.available >> 20
+                                ));
+                            }
+                        });
+                    });
+                }
+                if let Err(err) = renderer.draw_frame() {
+                    error!("Failed to draw frame: {}", err);
+                    if matches!(err, RendererError::SurfaceLost) {
+                        if renderer.recreate_surface(&window).is_err() {
+                            *control_flow = ControlFlow::Exit;
+                        }
+                    } else if renderer.is_device_lost() && renderer.recreate_device().is_ok() {
+                        renderer.set_mesh(&vertices, &indices);
+                        renderer.set_instances(&offsets);
+                    } else {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                } else if renderer.swapchain_suboptimal() {
+                    if let Err(err) = renderer.recreate_swapchain(&window) {
+                        error!("Failed to recreate swapchain: {}", err);
+                    }
+                }
             }
-            Event::MainEventsCleared => {
+            // Don't request redraws while minimized, ControlFlow::Wait then blocks until the next window event.
+            Event::MainEventsCleared if !is_minimized(&window) => {
                 window.request_redraw();
             }
             Event::LoopDestroyed => {
@@ -42,3 +163,31 @@ fn main() {
         }
     });
 }
+
+/// X, Y and Z axes through the origin in red, green and blue.
+fn axes() -> [(Vertex, Vertex); 3] {
+    let line = |direction: [f32; 3]| {
+        let vertex = |pos| Vertex {
+            pos,
+            color: [direction[0], direction[1], direction[2], 1.0],
+            uv: [0.0; 2],
+            normal: [0.0; 3],
+        };
+        (vertex([0.0; 3]), vertex(direction.map(|x| x * 10.0)))
+    };
+    [
+        line([1.0, 0.0, 0.0]),
+        line([0.0, 1.0, 0.0]),
+        line([0.0, 0.0, 1.0]),
+    ]
+}
+
+fn is_minimized(window: &Window) -> bool {
+    let size = window.inner_size();
+    size.width == 0 || size.height == 0
+}
+
+/// Logs warnings and errors by default, `RUST_LOG` overrides the filter.
+fn init_logging() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+}
@@ -0,0 +1,118 @@
+use ash::vk::{
+    DescriptorBindingFlags, DescriptorImageInfo, DescriptorPoolSize, DescriptorSet,
+    DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutBindingFlagsCreateInfo,
+    DescriptorSetLayoutCreateFlags, DescriptorSetLayoutCreateInfo, DescriptorType, ImageLayout,
+    ShaderStageFlags, WriteDescriptorSet,
+};
+
+use super::{descriptor::DescriptorPool, device::Device, sampler::Sampler, texture::Texture};
+
+/// Upper bound of the texture array, lowered to the device's sampled image limits.
+const MAX_BINDLESS_TEXTURES: u32 = 4096;
+
+/// One partially bound array of combined image samplers at binding 0 of its own set, indexed in
+/// the fragment shader. Slots can be written while the set is bound, as long as the GPU isn't
+/// reading them.
+pub struct BindlessTextures {
+    pub descriptor_set_layout: DescriptorSetLayout,
+    pub descriptor_set: DescriptorSet,
+    pub capacity: u32,
+    /// Number of slots handed out by `push`.
+    pub len: u32,
+    _pool: DescriptorPool,
+    device: ash::Device,
+}
+
+impl BindlessTextures {
+    /// `None` without `Device::descriptor_indexing`.
+    pub fn new(device: &Device) -> Option<Self> {
+        if !device.descriptor_indexing {
+            return None;
+        }
+        let limits = &device.physical_device.properties.limits;
+        let capacity = MAX_BINDLESS_TEXTURES
+            .min(limits.max_per_stage_descriptor_sampled_images)
+            .min(limits.max_descriptor_set_sampled_images);
+
+        let bindings = [DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(capacity)
+            .stage_flags(ShaderStageFlags::FRAGMENT)
+            .build()];
+        let binding_flags =
+            [DescriptorBindingFlags::PARTIALLY_BOUND | DescriptorBindingFlags::UPDATE_AFTER_BIND];
+        let mut binding_flags_create_info =
+            DescriptorSetLayoutBindingFlagsCreateInfo::builder().binding_flags(&binding_flags);
+        let create_info = DescriptorSetLayoutCreateInfo::builder()
+            .flags(DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL)
+            .bindings(&bindings)
+            .push_next(&mut binding_flags_create_info);
+        let descriptor_set_layout = unsafe {
+            device
+                .inner
+                .create_descriptor_set_layout(&create_info, None)
+                .unwrap()
+        };
+
+        let pool = DescriptorPool::new_update_after_bind(
+            device,
+            &[DescriptorPoolSize::builder()
+                .ty(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(capacity)
+                .build()],
+            1,
+        );
+        let descriptor_set = pool.allocate(descriptor_set_layout);
+
+        Some(Self {
+            descriptor_set_layout,
+            descriptor_set,
+            capacity,
+            len: 0,
+            _pool: pool,
+            device: device.inner.clone(),
+        })
+    }
+
+    /// Writes `texture` to the next free slot and returns its index. Panics if the array is full.
+    pub fn push(&mut self, texture: &Texture, sampler: &Sampler) -> u32 {
+        assert!(
+            self.len < self.capacity,
+            "Bindless texture array is full ({} textures)",
+            self.capacity
+        );
+        let index = self.len;
+        self.write(index, texture, sampler);
+        self.len += 1;
+        index
+    }
+
+    /// Replaces the texture in slot `index`.
+    pub fn write(&self, index: u32, texture: &Texture, sampler: &Sampler) {
+        let image_infos = [DescriptorImageInfo::builder()
+            .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(texture.image.view)
+            .sampler(sampler.inner)
+            .build()];
+        let writes = [WriteDescriptorSet::builder()
+            .dst_set(self.descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(index)
+            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&image_infos)
+            .build()];
+        unsafe {
+            self.device.update_descriptor_sets(&writes, &[]);
+        }
+    }
+}
+
+impl Drop for BindlessTextures {
+    fn drop(&mut self) {
+        unsafe {
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
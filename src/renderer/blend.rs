@@ -0,0 +1,44 @@
+use ash::vk::{BlendFactor, BlendOp, ColorComponentFlags, PipelineColorBlendAttachmentState};
+
+/// How a pipeline's output is combined with the color attachment.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Overwrites the attachment.
+    #[default]
+    Opaque,
+    /// `src * src_alpha + dst * (1 - src_alpha)`, for transparent geometry and UI.
+    AlphaBlend,
+    /// `src * src_alpha + dst`, for particles and glow.
+    Additive,
+}
+
+impl BlendMode {
+    pub fn attachment_state(self) -> PipelineColorBlendAttachmentState {
+        let builder = PipelineColorBlendAttachmentState::builder().color_write_mask(
+            ColorComponentFlags::R
+                | ColorComponentFlags::G
+                | ColorComponentFlags::B
+                | ColorComponentFlags::A,
+        );
+        match self {
+            BlendMode::Opaque => builder.blend_enable(false),
+            BlendMode::AlphaBlend => builder
+                .blend_enable(true)
+                .src_color_blend_factor(BlendFactor::SRC_ALPHA)
+                .dst_color_blend_factor(BlendFactor::ONE_MINUS_SRC_ALPHA)
+                .color_blend_op(BlendOp::ADD)
+                .src_alpha_blend_factor(BlendFactor::ONE)
+                .dst_alpha_blend_factor(BlendFactor::ONE_MINUS_SRC_ALPHA)
+                .alpha_blend_op(BlendOp::ADD),
+            BlendMode::Additive => builder
+                .blend_enable(true)
+                .src_color_blend_factor(BlendFactor::SRC_ALPHA)
+                .dst_color_blend_factor(BlendFactor::ONE)
+                .color_blend_op(BlendOp::ADD)
+                .src_alpha_blend_factor(BlendFactor::ONE)
+                .dst_alpha_blend_factor(BlendFactor::ONE)
+                .alpha_blend_op(BlendOp::ADD),
+        }
+        .build()
+    }
+}
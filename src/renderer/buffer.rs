@@ -0,0 +1,162 @@
+use ash::vk::{
+    BufferCopy, BufferCreateInfo, BufferUsageFlags, DeviceSize, MemoryPropertyFlags, SharingMode,
+};
+use bytemuck::Pod;
+
+use super::{
+    device::Device,
+    memory::{Allocation, Allocator},
+};
+
+pub struct Buffer {
+    pub inner: ash::vk::Buffer,
+    pub allocation: Allocation,
+    pub size: DeviceSize,
+    device: ash::Device,
+}
+
+impl Buffer {
+    pub fn new(
+        device: &Device,
+        size: DeviceSize,
+        usage: BufferUsageFlags,
+        properties: MemoryPropertyFlags,
+    ) -> Self {
+        Buffer::with_queue_families(device, size, usage, properties, &[])
+    }
+
+    /// `HOST_VISIBLE | HOST_COHERENT` buffer sized for and filled with `data`, rewritten with
+    /// `map_write`. Panics if `data` is empty, Vulkan buffers can't be.
+    pub fn new_typed<T: Pod>(device: &Device, usage: BufferUsageFlags, data: &[T]) -> Self {
+        assert!(!data.is_empty(), "Can't create a buffer for empty data");
+        let mut buffer = Buffer::new(
+            device,
+            std::mem::size_of_val(data) as DeviceSize,
+            usage,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        );
+        buffer.map_write(data);
+        buffer
+    }
+
+    /// Buffer shared concurrently between `queue_family_indices` if there's more than one of them.
+    fn with_queue_families(
+        device: &Device,
+        size: DeviceSize,
+        usage: BufferUsageFlags,
+        properties: MemoryPropertyFlags,
+        queue_family_indices: &[u32],
+    ) -> Self {
+        let create_info = BufferCreateInfo::builder().size(size).usage(usage);
+        let create_info = if queue_family_indices.len() > 1 {
+            create_info
+                .sharing_mode(SharingMode::CONCURRENT)
+                .queue_family_indices(queue_family_indices)
+        } else {
+            create_info.sharing_mode(SharingMode::EXCLUSIVE)
+        };
+
+        let inner = unsafe { device.inner.create_buffer(&create_info, None).unwrap() };
+
+        let requirements = unsafe { device.inner.get_buffer_memory_requirements(inner) };
+        let allocation =
+            Allocator::allocate(&device.allocator, "buffer", requirements, properties, true);
+        unsafe {
+            device
+                .inner
+                .bind_buffer_memory(inner, allocation.memory(), allocation.offset())
+                .unwrap();
+        }
+
+        Self {
+            inner,
+            allocation,
+            size,
+            device: device.inner.clone(),
+        }
+    }
+
+    /// Creates a `DEVICE_LOCAL` buffer and fills it with `data` through a temporary staging buffer.
+    /// The copy runs on the transfer queue. Panics if `data` is empty.
+    pub fn new_device_local(device: &Device, usage: BufferUsageFlags, data: &[u8]) -> Self {
+        assert!(!data.is_empty(), "Can't create a buffer for empty data");
+        let size = data.len() as DeviceSize;
+        let mut staging = Buffer::new(
+            device,
+            size,
+            BufferUsageFlags::TRANSFER_SRC,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        );
+        staging.write(data);
+
+        // Shared with the graphics family so it can be used there without an ownership transfer.
+        let queue_family_indices = &device.physical_device.queue_family_indices;
+        let mut families = vec![queue_family_indices.graphics_family.unwrap()];
+        families.extend(
+            queue_family_indices
+                .transfer_family
+                .filter(|family| *family != families[0]),
+        );
+        let buffer = Buffer::with_queue_families(
+            device,
+            size,
+            usage | BufferUsageFlags::TRANSFER_DST,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+            &families,
+        );
+
+        device.one_time_submit_transfer(|command_buffer| {
+            let regions = [BufferCopy::builder().size(size).build()];
+            unsafe {
+                device
+                    .inner
+                    .cmd_copy_buffer(command_buffer, staging.inner, buffer.inner, &regions);
+            }
+        });
+
+        buffer
+    }
+
+    /// Copies `data` to the start of the buffer. The memory has to be `HOST_VISIBLE | HOST_COHERENT`.
+    pub fn write(&mut self, data: &[u8]) {
+        assert!(data.len() as DeviceSize <= self.size);
+        self.allocation.write(data);
+    }
+
+    /// Copies `data` to the start of the buffer like `write`.
+    pub fn map_write<T: Pod>(&mut self, data: &[T]) {
+        self.write(bytemuck::cast_slice(data));
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_buffer(self.inner, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::tests::headless_renderer;
+
+    #[test]
+    fn typed_buffer_round_trips_through_host_memory() {
+        let Some(renderer) = headless_renderer() else {
+            return;
+        };
+        let data = [1.0f32, -2.5, 3.25, f32::MAX];
+        let mut buffer =
+            Buffer::new_typed(&renderer.device, BufferUsageFlags::UNIFORM_BUFFER, &data);
+        assert_eq!(buffer.size, std::mem::size_of_val(&data) as DeviceSize);
+        let bytes = buffer.allocation.read();
+        assert_eq!(bytemuck::cast_slice::<u8, f32>(&bytes[..16]), data);
+
+        let data = [7.0f32, 8.0, 9.0, 10.0];
+        buffer.map_write(&data);
+        let bytes = buffer.allocation.read();
+        assert_eq!(bytemuck::cast_slice::<u8, f32>(&bytes[..16]), data);
+    }
+}
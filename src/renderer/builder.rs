@@ -0,0 +1,205 @@
+use ash::vk::{
+    AttachmentLoadOp, CompositeAlphaFlagsKHR, DebugUtilsMessageSeverityFlagsEXT, ImageLayout,
+    PhysicalDeviceFeatures, PresentModeKHR, PrimitiveTopology, SampleCountFlags, StencilOpState,
+    SurfaceFormatKHR,
+};
+use winit::window::Window;
+
+use super::{
+    blend::BlendMode, config::RendererConfig, error::RendererError, pipeline_graphics::DepthBias,
+    Renderer,
+};
+
+/// Chained setup of a `RendererConfig`, finished with `build` or `build_headless`.
+#[derive(Clone, Debug, Default)]
+pub struct RendererBuilder {
+    config: RendererConfig,
+}
+
+impl RendererBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn app_name(mut self, app_name: impl Into<String>) -> Self {
+        self.config.app_name = app_name.into();
+        self
+    }
+
+    pub fn validation(mut self, validation: bool) -> Self {
+        self.config.validation = validation;
+        self
+    }
+
+    pub fn validation_severity(mut self, severity: DebugUtilsMessageSeverityFlagsEXT) -> Self {
+        self.config.validation_severity = severity;
+        self
+    }
+
+    pub fn present_mode(mut self, present_mode: PresentModeKHR) -> Self {
+        self.config.present_mode = Some(present_mode);
+        self
+    }
+
+    pub fn desired_image_count(mut self, count: u32) -> Self {
+        self.config.desired_image_count = Some(count);
+        self
+    }
+
+    pub fn surface_formats(mut self, formats: impl Into<Vec<SurfaceFormatKHR>>) -> Self {
+        self.config.surface_formats = formats.into();
+        self
+    }
+
+    pub fn hdr(mut self, hdr: bool) -> Self {
+        self.config.hdr = hdr;
+        self
+    }
+
+    pub fn composite_alpha(mut self, composite_alpha: CompositeAlphaFlagsKHR) -> Self {
+        self.config.composite_alpha = composite_alpha;
+        self
+    }
+
+    pub fn max_fps(mut self, max_fps: u32) -> Self {
+        self.config.max_fps = Some(max_fps);
+        self
+    }
+
+    pub fn msaa(mut self, samples: SampleCountFlags) -> Self {
+        self.config.msaa_samples = samples;
+        self
+    }
+
+    pub fn clear_color(mut self, clear_color: [f32; 4]) -> Self {
+        self.config.clear_color = clear_color;
+        self
+    }
+
+    pub fn preferred_gpu(mut self, name: impl Into<String>) -> Self {
+        self.config.preferred_gpu = Some(name.into());
+        self
+    }
+
+    pub fn gpu_index(mut self, index: usize) -> Self {
+        self.config.gpu_index = Some(index);
+        self
+    }
+
+    pub fn require_geometry_shader(mut self, require: bool) -> Self {
+        self.config.require_geometry_shader = require;
+        self
+    }
+
+    pub fn features(mut self, features: PhysicalDeviceFeatures) -> Self {
+        self.config.requested_features = features;
+        self
+    }
+
+    pub fn max_anisotropy(mut self, max_anisotropy: f32) -> Self {
+        self.config.max_anisotropy = max_anisotropy;
+        self
+    }
+
+    pub fn timeline_semaphore(mut self, timeline_semaphore: bool) -> Self {
+        self.config.timeline_semaphore = timeline_semaphore;
+        self
+    }
+
+    pub fn dynamic_rendering(mut self, dynamic_rendering: bool) -> Self {
+        self.config.dynamic_rendering = dynamic_rendering;
+        self
+    }
+
+    pub fn blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.config.blend_mode = blend_mode;
+        self
+    }
+
+    pub fn topology(mut self, topology: PrimitiveTopology) -> Self {
+        self.config.topology = topology;
+        self
+    }
+
+    pub fn point_size(mut self, point_size: f32) -> Self {
+        self.config.point_size = point_size;
+        self
+    }
+
+    pub fn line_width(mut self, line_width: f32) -> Self {
+        self.config.line_width = line_width;
+        self
+    }
+
+    pub fn tessellation_level(mut self, level: f32) -> Self {
+        self.config.tessellation_level = Some(level);
+        self
+    }
+
+    pub fn color_load_op(mut self, load_op: AttachmentLoadOp) -> Self {
+        self.config.color_load_op = load_op;
+        self
+    }
+
+    pub fn color_initial_layout(mut self, layout: ImageLayout) -> Self {
+        self.config.color_initial_layout = layout;
+        self
+    }
+
+    pub fn stencil(mut self, stencil: StencilOpState) -> Self {
+        self.config.stencil = Some(stencil);
+        self
+    }
+
+    pub fn bindless(mut self, bindless: bool) -> Self {
+        self.config.bindless = bindless;
+        self
+    }
+
+    pub fn deferred(mut self, deferred: bool) -> Self {
+        self.config.deferred = deferred;
+        self
+    }
+
+    pub fn pipeline_statistics(mut self, pipeline_statistics: bool) -> Self {
+        self.config.pipeline_statistics = pipeline_statistics;
+        self
+    }
+
+    pub fn recording_threads(mut self, recording_threads: usize) -> Self {
+        self.config.recording_threads = recording_threads;
+        self
+    }
+
+    pub fn async_pipelines(mut self, async_pipelines: bool) -> Self {
+        self.config.async_pipelines = async_pipelines;
+        self
+    }
+
+    pub fn depth_clamp(mut self, depth_clamp: bool) -> Self {
+        self.config.depth_clamp = depth_clamp;
+        self
+    }
+
+    pub fn depth_bias(mut self, depth_bias: DepthBias) -> Self {
+        self.config.depth_bias = Some(depth_bias);
+        self
+    }
+
+    pub fn dynamic_depth_bias(mut self, dynamic_depth_bias: bool) -> Self {
+        self.config.dynamic_depth_bias = dynamic_depth_bias;
+        self
+    }
+
+    pub fn config(&self) -> &RendererConfig {
+        &self.config
+    }
+
+    pub fn build(self, window: &Window) -> Result<Renderer, RendererError> {
+        Renderer::new(window, self.config)
+    }
+
+    pub fn build_headless(self, width: u32, height: u32) -> Result<Renderer, RendererError> {
+        Renderer::new_headless(width, height, self.config)
+    }
+}
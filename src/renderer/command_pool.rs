@@ -1,10 +1,13 @@
 use ash::vk::{
-    CommandBuffer, CommandBufferAllocateInfo, CommandBufferLevel, CommandPoolCreateFlags,
-    CommandPoolCreateInfo,
+    CommandBuffer, CommandBufferAllocateInfo, CommandBufferBeginInfo, CommandBufferLevel,
+    CommandBufferUsageFlags, CommandPoolCreateFlags, CommandPoolCreateInfo, FenceCreateInfo, Queue,
+    SubmitInfo,
 };
 
 use super::device::Device;
 
+/// Command pools aren't thread-safe: recording into buffers of the same pool from several threads
+/// at once is undefined behavior, so every recording thread needs a pool of its own.
 pub struct CommandPool {
     pub inner: ash::vk::CommandPool,
     device: ash::Device,
@@ -12,15 +15,44 @@ pub struct CommandPool {
 
 impl CommandPool {
     pub fn new(device: &Device) -> Self {
+        let queue_family_indices = &device.physical_device.queue_family_indices;
+        CommandPool::with_family(
+            device,
+            queue_family_indices.graphics_family.unwrap(),
+            CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+        )
+    }
+
+    /// Pool for submissions to `Device::present_queue`.
+    pub fn new_present(device: &Device) -> Self {
+        let queue_family_indices = &device.physical_device.queue_family_indices;
+        CommandPool::with_family(
+            device,
+            queue_family_indices.present_family.unwrap(),
+            CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+        )
+    }
+
+    /// Pool for short-lived command buffers that are freed right after they executed, like the
+    /// ones of `submit_once`. Its buffers can't be reset individually.
+    pub fn new_transient(device: &Device, queue_family_index: u32) -> Self {
+        CommandPool::with_family(
+            device,
+            queue_family_index,
+            CommandPoolCreateFlags::TRANSIENT,
+        )
+    }
+
+    /// Pool for submissions to any queue of `queue_family_index`. The other constructors pass
+    /// `RESET_COMMAND_BUFFER` so buffers can be rerecorded every frame.
+    pub fn with_family(
+        device: &Device,
+        queue_family_index: u32,
+        flags: CommandPoolCreateFlags,
+    ) -> Self {
         let create_info = CommandPoolCreateInfo::builder()
-            .flags(CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
-            .queue_family_index(
-                device
-                    .physical_device
-                    .queue_family_indices
-                    .graphics_family
-                    .unwrap(),
-            );
+            .flags(flags)
+            .queue_family_index(queue_family_index);
 
         let inner = unsafe {
             device
@@ -35,13 +67,66 @@ impl CommandPool {
         }
     }
 
-    pub fn allocate(&mut self) -> CommandBuffer {
+    pub fn allocate(&mut self, level: CommandBufferLevel) -> CommandBuffer {
+        self.allocate_many(1, level)[0]
+    }
+
+    /// Allocates `count` buffers with a single call, e.g. one per swapchain image.
+    pub fn allocate_many(&mut self, count: u32, level: CommandBufferLevel) -> Vec<CommandBuffer> {
+        let alloc_info = CommandBufferAllocateInfo::builder()
+            .command_pool(self.inner)
+            .level(level)
+            .command_buffer_count(count);
+
+        unsafe { self.device.allocate_command_buffers(&alloc_info).unwrap() }
+    }
+
+    /// Returns buffers allocated from this pool, they must not be pending execution. Buffers that
+    /// live as long as the pool don't need this, destroying the pool frees them.
+    pub fn free(&mut self, command_buffers: &[CommandBuffer]) {
+        unsafe {
+            self.device
+                .free_command_buffers(self.inner, command_buffers);
+        }
+    }
+
+    /// Records a command buffer with `record`, submits it to `queue` and blocks on a fence until it
+    /// has executed. Other work on `queue` keeps running. Usually called through
+    /// `Device::one_time_submit`.
+    pub fn submit_once<F: FnOnce(CommandBuffer)>(&self, queue: Queue, record: F) {
         let alloc_info = CommandBufferAllocateInfo::builder()
             .command_pool(self.inner)
             .level(CommandBufferLevel::PRIMARY)
             .command_buffer_count(1);
+        let command_buffers = unsafe { self.device.allocate_command_buffers(&alloc_info).unwrap() };
 
-        unsafe { self.device.allocate_command_buffers(&alloc_info).unwrap()[0] }
+        let begin_info =
+            CommandBufferBeginInfo::builder().flags(CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        unsafe {
+            self.device
+                .begin_command_buffer(command_buffers[0], &begin_info)
+                .unwrap();
+        }
+
+        record(command_buffers[0]);
+
+        let submit_info = SubmitInfo::builder().command_buffers(&command_buffers);
+        unsafe {
+            self.device.end_command_buffer(command_buffers[0]).unwrap();
+            let fence = self
+                .device
+                .create_fence(&FenceCreateInfo::default(), None)
+                .unwrap();
+            self.device
+                .queue_submit(queue, &[submit_info.build()], fence)
+                .unwrap();
+            self.device
+                .wait_for_fences(&[fence], true, u64::MAX)
+                .unwrap();
+            self.device.destroy_fence(fence, None);
+            self.device
+                .free_command_buffers(self.inner, &command_buffers);
+        }
     }
 }
 
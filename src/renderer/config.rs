@@ -0,0 +1,165 @@
+use ash::vk::{
+    AttachmentLoadOp, ColorSpaceKHR, CompositeAlphaFlagsKHR, DebugUtilsMessageSeverityFlagsEXT,
+    Format, ImageLayout, PhysicalDeviceFeatures, PresentModeKHR, PrimitiveTopology,
+    SampleCountFlags, StencilOpState, SurfaceFormatKHR,
+};
+
+use log::warn;
+
+use super::{
+    blend::BlendMode,
+    constants::{GPU_ENV_VAR, VALIDATION_ENV_VAR},
+    pipeline_graphics::DepthBias,
+};
+
+#[derive(Clone, Debug)]
+pub struct RendererConfig {
+    /// Application name reported to the driver.
+    pub app_name: String,
+    /// Enable the validation layers and debug messenger if available. Defaults to
+    /// `OPENCUBES_VALIDATION` if it's set to 1 or 0, else to on in debug builds.
+    pub validation: bool,
+    /// Reject physical devices without geometry shader support and enable the feature on the device.
+    pub require_geometry_shader: bool,
+    /// Optional features to enable on the device. Unsupported ones are logged and skipped.
+    pub requested_features: PhysicalDeviceFeatures,
+    /// MSAA sample count. Clamped to the highest count the device supports.
+    pub msaa_samples: SampleCountFlags,
+    /// Severities the debug messenger reports when validation is enabled.
+    pub validation_severity: DebugUtilsMessageSeverityFlagsEXT,
+    /// Present mode used if the surface supports it, otherwise MAILBOX or FIFO.
+    pub present_mode: Option<PresentModeKHR>,
+    /// Number of swapchain images, e.g. 3 for triple buffering. Defaults to one more than the minimum.
+    pub desired_image_count: Option<u32>,
+    /// Swapchain formats and color spaces tried in order, falling back to the first one the
+    /// surface reports. Put a UNORM format first to do gamma correction in the shaders.
+    pub surface_formats: Vec<SurfaceFormatKHR>,
+    /// Prefer an HDR10 or extended sRGB swapchain, falling back to `surface_formats` if the
+    /// surface doesn't support one. HDR10 expects PQ encoded shader output, check
+    /// `Renderer::surface_format` for what was picked. See `Renderer::set_hdr_metadata`.
+    pub hdr: bool,
+    /// How the window's alpha is composited, e.g. `PRE_MULTIPLIED` for a transparent background
+    /// together with `WindowBuilder::with_transparent`. Falls back to the first supported mode.
+    pub composite_alpha: CompositeAlphaFlagsKHR,
+    /// Caps the frame rate by sleeping at the end of `draw_frame`, regardless of the present mode.
+    pub max_fps: Option<u32>,
+    /// RGBA color the frame is cleared to.
+    pub clear_color: [f32; 4],
+    /// Pick a suitable GPU whose name contains this over the highest rated one. Defaults to
+    /// `OPENCUBES_GPU`.
+    pub preferred_gpu: Option<String>,
+    /// Index into `Renderer::enumerate_gpus` of the GPU to use, takes precedence over `preferred_gpu`.
+    pub gpu_index: Option<usize>,
+    /// Sampler anisotropy, clamped to the device limit. 1.0 or an unsupported device disables it.
+    pub max_anisotropy: f32,
+    /// Track frame completion with a timeline semaphore instead of a fence if the device supports it.
+    pub timeline_semaphore: bool,
+    /// Render the main pass with `VK_KHR_dynamic_rendering` instead of a render pass if supported.
+    /// Not used with MSAA, post-processing or the egui overlay, which still need a render pass.
+    pub dynamic_rendering: bool,
+    /// Blending of the main pipeline, e.g. `AlphaBlend` for transparent geometry.
+    pub blend_mode: BlendMode,
+    /// Primitive topology the mesh's indices are drawn with.
+    pub topology: PrimitiveTopology,
+    /// Diameter in pixels of points set with `Renderer::set_points`, clamped to the device's
+    /// point size range. Sizes above 1.0 need the `large_points` feature.
+    pub point_size: f32,
+    /// Width of lines drawn with `Renderer::draw_lines`. Widths above 1.0 enable the `wide_lines`
+    /// feature if supported and are clamped to the device's line width range.
+    pub line_width: f32,
+    /// Split every triangle of the mesh into this many segments per edge with tessellation
+    /// shaders, clamped to the device limit. Needs the `tessellation_shader` feature, without it
+    /// the mesh is drawn as is. Replaces `topology`.
+    pub tessellation_level: Option<f32>,
+    /// What the main pass does with the color image's previous contents. `LOAD` keeps the last
+    /// frame drawn into the image, e.g. to accumulate effects, and isn't supported with MSAA.
+    pub color_load_op: AttachmentLoadOp,
+    /// Layout the main pass expects the color image in. Has to be the final layout, e.g.
+    /// `PRESENT_SRC_KHR`, with `LOAD` and is replaced by it if left `UNDEFINED`.
+    pub color_initial_layout: ImageLayout,
+    /// Stencil test of the main pipeline, see the `stencil` module for presets.
+    pub stencil: Option<StencilOpState>,
+    /// Sample the textures registered with `Renderer::register_texture` from one descriptor
+    /// array, if the device supports descriptor indexing.
+    pub bindless: bool,
+    /// Shade the scene in a lighting subpass reading a G-buffer through input attachments, see
+    /// `DeferredLighting`. Not supported with MSAA, dynamic rendering, bindless textures,
+    /// post-processing, lines and points or when headless.
+    pub deferred: bool,
+    /// Collect `Renderer::pipeline_stats` every frame. Needs the `pipeline_statistics_query`
+    /// feature, and `inherited_queries` together with `recording_threads`.
+    pub pipeline_statistics: bool,
+    /// Worker threads recording the scene's draws into secondary command buffers, 0 records
+    /// everything on the calling thread. Only used for the main render pass.
+    pub recording_threads: usize,
+    /// Compile the main pipeline on a worker thread. Frames only clear until it's ready, see
+    /// `Renderer::pipelines_ready`.
+    pub async_pipelines: bool,
+    /// Clamp the main pipeline's depths instead of clipping at the near and far planes, if the
+    /// device supports the `depth_clamp` feature.
+    pub depth_clamp: bool,
+    /// Depth bias baked into the main pipeline.
+    pub depth_bias: Option<DepthBias>,
+    /// Make the main pipeline's depth bias dynamic, set with `Renderer::set_depth_bias`.
+    /// `depth_bias` is then only the initial value.
+    pub dynamic_depth_bias: bool,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            app_name: String::from("OpenCubes"),
+            validation: env_flag(VALIDATION_ENV_VAR).unwrap_or(cfg!(debug_assertions)),
+            require_geometry_shader: false,
+            requested_features: PhysicalDeviceFeatures::default(),
+            msaa_samples: SampleCountFlags::TYPE_1,
+            validation_severity: DebugUtilsMessageSeverityFlagsEXT::WARNING
+                | DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            present_mode: None,
+            desired_image_count: None,
+            surface_formats: vec![SurfaceFormatKHR {
+                format: Format::B8G8R8A8_SRGB,
+                color_space: ColorSpaceKHR::SRGB_NONLINEAR,
+            }],
+            hdr: false,
+            composite_alpha: CompositeAlphaFlagsKHR::OPAQUE,
+            max_fps: None,
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            preferred_gpu: std::env::var(GPU_ENV_VAR)
+                .ok()
+                .filter(|name| !name.is_empty()),
+            gpu_index: None,
+            max_anisotropy: 16.0,
+            timeline_semaphore: true,
+            dynamic_rendering: false,
+            blend_mode: BlendMode::Opaque,
+            topology: PrimitiveTopology::TRIANGLE_LIST,
+            point_size: 1.0,
+            line_width: 1.0,
+            tessellation_level: None,
+            color_load_op: AttachmentLoadOp::CLEAR,
+            color_initial_layout: ImageLayout::UNDEFINED,
+            stencil: None,
+            bindless: false,
+            deferred: false,
+            pipeline_statistics: false,
+            recording_threads: 0,
+            async_pipelines: false,
+            depth_clamp: false,
+            depth_bias: None,
+            dynamic_depth_bias: false,
+        }
+    }
+}
+
+/// `Some` if the variable is set to 1 or 0, unset or other values fall back to the default.
+fn env_flag(name: &str) -> Option<bool> {
+    match std::env::var(name).ok()?.as_str() {
+        "1" => Some(true),
+        "0" => Some(false),
+        value => {
+            warn!("Ignoring {}={}, expected 1 or 0", name, value);
+            None
+        }
+    }
+}
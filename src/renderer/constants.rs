@@ -1,4 +1,7 @@
-use ash::extensions::ext::DebugUtils;
+use ash::{
+    extensions::ext::DebugUtils,
+    vk::{ClearDepthStencilValue, ClearValue, ColorSpaceKHR, Format, SurfaceFormatKHR},
+};
 
 use crate::renderer::utils::apiversion::ApiVersion;
 use std::ffi::CString;
@@ -15,11 +18,71 @@ lazy_static! {
     pub static ref INSTANCE_REQUIRED_LAYER_NAMES: Vec<CString> = vec![];
     pub static ref INSTANCE_DEBUG_EXTENSION_NAMES: Vec<CString> = vec![CString::from(DebugUtils::name())];
     pub static ref INSTANCE_REQUIRED_EXTENSION_NAMES: Vec<CString> = vec![];
-    pub static ref INSTANCE_OPTIONAL_EXTENSION_NAMES: Vec<CString> = vec![];
+    pub static ref INSTANCE_OPTIONAL_EXTENSION_NAMES: Vec<CString> = vec![CString::new("VK_EXT_swapchain_colorspace").unwrap()];
 
     // PHYSICAL DEVICE
     pub static ref PHYSICAL_DEVICE_REQUIRED_EXTENSION_NAMES: Vec<CString> = vec![CString::new("VK_KHR_swapchain").unwrap()];
-    pub static ref PHYSICAL_DEVICE_OPTIONAL_EXTENSION_NAMES: Vec<CString> = vec![];
+    pub static ref PHYSICAL_DEVICE_OPTIONAL_EXTENSION_NAMES: Vec<CString> = vec![
+        CString::new("VK_EXT_memory_budget").unwrap(),
+        CString::new("VK_KHR_dynamic_rendering").unwrap(),
+        CString::new("VK_EXT_hdr_metadata").unwrap(),
+        CString::new("VK_EXT_extended_dynamic_state").unwrap(),
+    ];
     pub static ref PHYSICAL_DEVICE_REQUIRED_LAYER_NAMES: Vec<CString> = vec![];
     pub static ref PHYSICAL_DEVICE_OPTIONAL_LAYER_NAMES: Vec<CString> = vec![];
 }
+
+/// Size of the push constant range shared by all pipeline stages. 128 bytes is the minimum `maxPushConstantsSize` guaranteed by the spec.
+pub const PUSH_CONSTANT_SIZE: u32 = 128;
+
+/// Depth is cleared to the far plane and stencil to 0.
+pub const DEPTH_STENCIL_CLEAR_VALUE: ClearValue = ClearValue {
+    depth_stencil: ClearDepthStencilValue {
+        depth: 1.0,
+        stencil: 0,
+    },
+};
+
+/// Swapchain formats tried before `RendererConfig::surface_formats` when HDR output is requested,
+/// HDR10 first and then linear extended sRGB. HDR color spaces are only reported with
+/// `VK_EXT_swapchain_colorspace`.
+pub const HDR_SURFACE_FORMATS: [SurfaceFormatKHR; 2] = [
+    SurfaceFormatKHR {
+        format: Format::A2B10G10R10_UNORM_PACK32,
+        color_space: ColorSpaceKHR::HDR10_ST2084_EXT,
+    },
+    SurfaceFormatKHR {
+        format: Format::R16G16B16A16_SFLOAT,
+        color_space: ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+    },
+];
+
+/// Transforms the dynamic uniform buffer at binding 2 holds, see `Renderer::draw_object`.
+pub const MAX_OBJECTS: usize = 1024;
+
+/// Size of the vertex shader's std140 `Object` block: the model matrix and the bindless texture
+/// index, padded to 16 bytes.
+pub const OBJECT_DATA_SIZE: u64 = 80;
+
+/// Color format of the offscreen image rendered into when running headless.
+pub const HEADLESS_COLOR_FORMAT: Format = Format::R8G8B8A8_SRGB;
+
+/// Frames that can be executing on the GPU while the next one is recorded, each waited on with
+/// `Renderer::in_flight_fence`.
+pub const MAX_FRAMES_IN_FLIGHT: u64 = 1;
+
+/// Nanoseconds to wait for a frame's fence before giving up on a hung GPU.
+pub const FENCE_TIMEOUT: u64 = 5_000_000_000;
+
+/// G-buffer written by the scene subpass of a deferred main pass, see `DeferredLighting`.
+pub const GBUFFER_ALBEDO_FORMAT: Format = Format::R8G8B8A8_UNORM;
+pub const GBUFFER_NORMAL_FORMAT: Format = Format::R16G16B16A16_SFLOAT;
+
+/// Grid lines drawn by `Renderer::set_show_gizmos` on each side of the origin, one unit apart.
+pub const GIZMO_GRID_HALF_EXTENT: u32 = 10;
+
+/// Environment variables read by `RendererConfig::default`, values set on the config win.
+/// Substring of the GPU name to prefer, like `RendererConfig::preferred_gpu`.
+pub const GPU_ENV_VAR: &str = "OPENCUBES_GPU";
+/// `1` or `0` to force validation on or off, like `RendererConfig::validation`.
+pub const VALIDATION_ENV_VAR: &str = "OPENCUBES_VALIDATION";
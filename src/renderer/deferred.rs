@@ -0,0 +1,204 @@
+use std::ffi::CString;
+
+use ash::vk::{
+    CommandBuffer, CullModeFlags, DescriptorImageInfo, DescriptorPoolSize, DescriptorSet,
+    DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType,
+    DynamicState, Extent2D, GraphicsPipelineCreateInfo, ImageLayout, ImageView, PipelineBindPoint,
+    PipelineCache, PipelineColorBlendStateCreateInfo, PipelineDynamicStateCreateInfo,
+    PipelineInputAssemblyStateCreateInfo, PipelineLayout, PipelineLayoutCreateInfo,
+    PipelineMultisampleStateCreateInfo, PipelineRasterizationStateCreateInfo,
+    PipelineShaderStageCreateInfo, PipelineVertexInputStateCreateInfo,
+    PipelineViewportStateCreateInfo, PolygonMode, PrimitiveTopology, Rect2D, SampleCountFlags,
+    ShaderStageFlags, Viewport, WriteDescriptorSet,
+};
+
+use super::{
+    blend::BlendMode, descriptor::DescriptorPool, device::Device,
+    pipeline_graphics::GraphicsPipeline, shader_module::ShaderModule,
+};
+
+/// Subpass of a deferred main render pass that shades the G-buffer into the color attachment.
+pub const LIGHTING_SUBPASS: u32 = 1;
+
+/// Fullscreen lighting subpass of a main pipeline created with `PipelineOptions::deferred`. Reads
+/// the albedo and normal the scene subpass wrote as input attachments, so only the current
+/// pixel is loaded and the G-buffer can stay in tile memory.
+pub struct DeferredLighting {
+    pub pipeline: ash::vk::Pipeline,
+    pub pipeline_layout: PipelineLayout,
+    pub descriptor_set_layout: DescriptorSetLayout,
+    /// Points at the G-buffer images, rewritten with `write_gbuffer` when they're recreated.
+    pub descriptor_set: DescriptorSet,
+    _descriptor_pool: DescriptorPool,
+    device: ash::Device,
+}
+
+impl DeferredLighting {
+    /// The render pass of `main` has to outlive the lighting pipeline, which is created for it.
+    pub fn new(device: &Device, main: &GraphicsPipeline) -> Self {
+        let bindings = [0, 1].map(|binding| {
+            DescriptorSetLayoutBinding::builder()
+                .binding(binding)
+                .descriptor_type(DescriptorType::INPUT_ATTACHMENT)
+                .descriptor_count(1)
+                .stage_flags(ShaderStageFlags::FRAGMENT)
+                .build()
+        });
+        let descriptor_set_layout_create_info =
+            DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+        let descriptor_set_layout = unsafe {
+            device
+                .inner
+                .create_descriptor_set_layout(&descriptor_set_layout_create_info, None)
+                .unwrap()
+        };
+        let descriptor_pool = DescriptorPool::new(
+            device,
+            &[DescriptorPoolSize::builder()
+                .ty(DescriptorType::INPUT_ATTACHMENT)
+                .descriptor_count(bindings.len() as u32)
+                .build()],
+            1,
+        );
+        let descriptor_set = descriptor_pool.allocate(descriptor_set_layout);
+
+        let set_layouts = [descriptor_set_layout];
+        let pipeline_layout_create_info =
+            PipelineLayoutCreateInfo::builder().set_layouts(&set_layouts);
+        let pipeline_layout = unsafe {
+            device
+                .inner
+                .create_pipeline_layout(&pipeline_layout_create_info, None)
+                .unwrap()
+        };
+
+        let vert_shader_module =
+            ShaderModule::new(device, include_bytes!("shaders/fullscreen_vert.spv"));
+        let frag_shader_module =
+            ShaderModule::new(device, include_bytes!("shaders/deferred_lighting_frag.spv"));
+        let p_name = CString::new("main").unwrap();
+        let shader_stage_create_infos = [
+            PipelineShaderStageCreateInfo::builder()
+                .stage(ShaderStageFlags::VERTEX)
+                .module(vert_shader_module.inner)
+                .name(&p_name)
+                .build(),
+            PipelineShaderStageCreateInfo::builder()
+                .stage(ShaderStageFlags::FRAGMENT)
+                .module(frag_shader_module.inner)
+                .name(&p_name)
+                .build(),
+        ];
+
+        let vertex_input_create_info = PipelineVertexInputStateCreateInfo::builder();
+        let input_assembly_create_info = PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(PrimitiveTopology::TRIANGLE_LIST)
+            .primitive_restart_enable(false);
+        let viewport_create_info = PipelineViewportStateCreateInfo::builder()
+            .viewport_count(1)
+            .scissor_count(1);
+        let dynamic_states = [DynamicState::VIEWPORT, DynamicState::SCISSOR];
+        let dynamic_state_create_info =
+            PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
+        let rasterizer_create_info = PipelineRasterizationStateCreateInfo::builder()
+            .polygon_mode(PolygonMode::FILL)
+            .line_width(1.0)
+            .cull_mode(CullModeFlags::NONE);
+        let multisample_create_info = PipelineMultisampleStateCreateInfo::builder()
+            .rasterization_samples(SampleCountFlags::TYPE_1);
+        let color_blend_attachments = [BlendMode::Opaque.attachment_state()];
+        let color_blend_create_info =
+            PipelineColorBlendStateCreateInfo::builder().attachments(&color_blend_attachments);
+
+        let create_infos = [GraphicsPipelineCreateInfo::builder()
+            .stages(&shader_stage_create_infos)
+            .vertex_input_state(&vertex_input_create_info)
+            .input_assembly_state(&input_assembly_create_info)
+            .viewport_state(&viewport_create_info)
+            .dynamic_state(&dynamic_state_create_info)
+            .rasterization_state(&rasterizer_create_info)
+            .multisample_state(&multisample_create_info)
+            .color_blend_state(&color_blend_create_info)
+            .layout(pipeline_layout)
+            .render_pass(main.render_pass)
+            .subpass(LIGHTING_SUBPASS)
+            .build()];
+        let pipeline = unsafe {
+            device
+                .inner
+                .create_graphics_pipelines(PipelineCache::null(), &create_infos, None)
+                .unwrap()[0]
+        };
+
+        Self {
+            pipeline,
+            pipeline_layout,
+            descriptor_set_layout,
+            descriptor_set,
+            _descriptor_pool: descriptor_pool,
+            device: device.inner.clone(),
+        }
+    }
+
+    /// Points the input attachments at the G-buffer. The set can't be in use by a pending frame.
+    pub fn write_gbuffer(&self, albedo: ImageView, normal: ImageView) {
+        let image_infos = [albedo, normal].map(|view| {
+            [DescriptorImageInfo::builder()
+                .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .image_view(view)
+                .build()]
+        });
+        let writes: Vec<_> = image_infos
+            .iter()
+            .enumerate()
+            .map(|(binding, image_info)| {
+                WriteDescriptorSet::builder()
+                    .dst_set(self.descriptor_set)
+                    .dst_binding(binding as u32)
+                    .dst_array_element(0)
+                    .descriptor_type(DescriptorType::INPUT_ATTACHMENT)
+                    .image_info(image_info)
+                    .build()
+            })
+            .collect();
+        unsafe {
+            self.device.update_descriptor_sets(&writes, &[]);
+        }
+    }
+
+    /// Draws a fullscreen triangle inside `LIGHTING_SUBPASS` of the begun render pass.
+    pub fn record(&self, device: &ash::Device, command_buffer: CommandBuffer, extent: Extent2D) {
+        let viewport = Viewport::builder()
+            .width(extent.width as f32)
+            .height(extent.height as f32)
+            .max_depth(1.0)
+            .build();
+        let scissor = Rect2D::builder().extent(extent).build();
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::GRAPHICS, self.pipeline);
+            device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+            device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+    }
+}
+
+impl Drop for DeferredLighting {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
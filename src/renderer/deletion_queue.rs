@@ -0,0 +1,37 @@
+use std::{any::Any, collections::VecDeque};
+
+use super::constants::MAX_FRAMES_IN_FLIGHT;
+
+/// Resources replaced while frames using them may still be executing, dropped once those frames
+/// have finished instead of waiting for the device to go idle.
+#[derive(Default)]
+pub struct DeletionQueue {
+    /// Resources with the number of frames started before they were pushed, oldest first.
+    pending: VecDeque<(u64, Box<dyn Any>)>,
+    frame: u64,
+}
+
+impl DeletionQueue {
+    /// Keeps `resource` alive until the frames recorded so far have finished.
+    pub fn push<T: 'static>(&mut self, resource: T) {
+        self.pending.push_back((self.frame, Box::new(resource)));
+    }
+
+    /// Called at the start of every frame, once the fence of the frame `MAX_FRAMES_IN_FLIGHT`
+    /// frames ago has signaled. Drops everything no unfinished frame can use anymore.
+    pub fn flush(&mut self) {
+        while self
+            .pending
+            .front()
+            .is_some_and(|(frame, _)| frame + MAX_FRAMES_IN_FLIGHT <= self.frame + 1)
+        {
+            self.pending.pop_front();
+        }
+        self.frame += 1;
+    }
+
+    /// Drops everything right away, the device has to be idle.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
@@ -0,0 +1,173 @@
+use ash::{
+    prelude::VkResult,
+    vk::{
+        DescriptorPoolCreateFlags, DescriptorPoolCreateInfo, DescriptorPoolResetFlags,
+        DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo, DescriptorSetLayout,
+        DescriptorType,
+    },
+};
+
+use super::device::Device;
+
+pub struct DescriptorPool {
+    pub inner: ash::vk::DescriptorPool,
+    device: ash::Device,
+}
+
+impl DescriptorPool {
+    pub fn new(device: &Device, pool_sizes: &[DescriptorPoolSize], max_sets: u32) -> Self {
+        Self::create(
+            &device.inner,
+            pool_sizes,
+            max_sets,
+            DescriptorPoolCreateFlags::empty(),
+        )
+    }
+
+    /// For sets whose layout has `UPDATE_AFTER_BIND_POOL`.
+    pub fn new_update_after_bind(
+        device: &Device,
+        pool_sizes: &[DescriptorPoolSize],
+        max_sets: u32,
+    ) -> Self {
+        Self::create(
+            &device.inner,
+            pool_sizes,
+            max_sets,
+            DescriptorPoolCreateFlags::UPDATE_AFTER_BIND,
+        )
+    }
+
+    fn create(
+        device: &ash::Device,
+        pool_sizes: &[DescriptorPoolSize],
+        max_sets: u32,
+        flags: DescriptorPoolCreateFlags,
+    ) -> Self {
+        let create_info = DescriptorPoolCreateInfo::builder()
+            .flags(flags)
+            .pool_sizes(pool_sizes)
+            .max_sets(max_sets);
+
+        let inner = unsafe { device.create_descriptor_pool(&create_info, None).unwrap() };
+
+        Self {
+            inner,
+            device: device.clone(),
+        }
+    }
+
+    pub fn allocate(&self, layout: DescriptorSetLayout) -> DescriptorSet {
+        self.try_allocate(layout).unwrap()
+    }
+
+    /// Fails with `ERROR_OUT_OF_POOL_MEMORY` or `ERROR_FRAGMENTED_POOL` when the pool is full.
+    pub fn try_allocate(&self, layout: DescriptorSetLayout) -> VkResult<DescriptorSet> {
+        let layouts = [layout];
+        let allocate_info = DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(self.inner)
+            .set_layouts(&layouts);
+
+        unsafe {
+            self.device
+                .allocate_descriptor_sets(&allocate_info)
+                .map(|descriptor_sets| descriptor_sets[0])
+        }
+    }
+
+    /// Frees all sets allocated from the pool.
+    pub fn reset(&self) {
+        unsafe {
+            self.device
+                .reset_descriptor_pool(self.inner, DescriptorPoolResetFlags::empty())
+                .unwrap();
+        }
+    }
+}
+
+impl Drop for DescriptorPool {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_descriptor_pool(self.inner, None);
+        }
+    }
+}
+
+/// Hands out descriptor sets from a list of pools, adding a pool whenever the current one runs
+/// out. `reset` recycles all pools at once, for sets that only live for a frame.
+pub struct DescriptorAllocator {
+    /// Descriptors of each type per set, scaled by `sets_per_pool` for every new pool.
+    pub pool_ratios: Vec<(DescriptorType, f32)>,
+    pub sets_per_pool: u32,
+    /// The last one is allocated from.
+    used_pools: Vec<DescriptorPool>,
+    free_pools: Vec<DescriptorPool>,
+    device: ash::Device,
+}
+
+impl DescriptorAllocator {
+    /// Sizes suitable for typical material and per-object sets.
+    pub const DEFAULT_POOL_RATIOS: [(DescriptorType, f32); 8] = [
+        (DescriptorType::SAMPLER, 0.5),
+        (DescriptorType::COMBINED_IMAGE_SAMPLER, 4.0),
+        (DescriptorType::SAMPLED_IMAGE, 4.0),
+        (DescriptorType::STORAGE_IMAGE, 1.0),
+        (DescriptorType::UNIFORM_BUFFER, 2.0),
+        (DescriptorType::STORAGE_BUFFER, 2.0),
+        (DescriptorType::UNIFORM_BUFFER_DYNAMIC, 1.0),
+        (DescriptorType::INPUT_ATTACHMENT, 0.5),
+    ];
+    pub const DEFAULT_SETS_PER_POOL: u32 = 256;
+
+    pub fn new(device: &Device) -> Self {
+        Self {
+            pool_ratios: Self::DEFAULT_POOL_RATIOS.to_vec(),
+            sets_per_pool: Self::DEFAULT_SETS_PER_POOL,
+            used_pools: Vec::new(),
+            free_pools: Vec::new(),
+            device: device.inner.clone(),
+        }
+    }
+
+    pub fn allocate(&mut self, layout: DescriptorSetLayout) -> DescriptorSet {
+        if let Some(pool) = self.used_pools.last() {
+            match pool.try_allocate(layout) {
+                Ok(descriptor_set) => return descriptor_set,
+                Err(ash::vk::Result::ERROR_OUT_OF_POOL_MEMORY)
+                | Err(ash::vk::Result::ERROR_FRAGMENTED_POOL) => {}
+                Err(result) => panic!("Failed to allocate descriptor set: {}", result),
+            }
+        }
+        let pool = self.free_pools.pop().unwrap_or_else(|| self.create_pool());
+        let descriptor_set = pool.allocate(layout);
+        self.used_pools.push(pool);
+        descriptor_set
+    }
+
+    /// Frees every set allocated so far, they must not be in use by the GPU anymore.
+    pub fn reset(&mut self) {
+        for pool in &self.used_pools {
+            pool.reset();
+        }
+        self.free_pools.append(&mut self.used_pools);
+    }
+
+    fn create_pool(&self) -> DescriptorPool {
+        let pool_sizes: Vec<DescriptorPoolSize> = self
+            .pool_ratios
+            .iter()
+            .map(|&(ty, ratio)| {
+                DescriptorPoolSize::builder()
+                    .ty(ty)
+                    .descriptor_count(((ratio * self.sets_per_pool as f32) as u32).max(1))
+                    .build()
+            })
+            .collect();
+        DescriptorPool::create(
+            &self.device,
+            &pool_sizes,
+            self.sets_per_pool,
+            DescriptorPoolCreateFlags::empty(),
+        )
+    }
+}
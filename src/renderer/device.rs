@@ -1,14 +1,34 @@
+use std::{
+    ffi::CStr,
+    mem::ManuallyDrop,
+    sync::{Arc, Mutex},
+};
+
 use ash::{
-    vk::{DeviceCreateInfo, DeviceQueueCreateInfo, PhysicalDeviceFeatures, Queue},
+    extensions::{ext::ExtendedDynamicState, khr::DynamicRendering},
+    vk::{
+        CommandBuffer, DeviceCreateInfo, DeviceQueueCreateInfo, ExtHdrMetadataFn,
+        PhysicalDeviceDynamicRenderingFeatures, PhysicalDeviceExtendedDynamicStateFeaturesEXT,
+        PhysicalDeviceFeatures, PhysicalDeviceFeatures2, PhysicalDeviceVulkan12Features, Queue,
+        QueueFamilyProperties,
+    },
     Instance,
 };
+use log::{info, warn};
 
 use super::{
+    command_pool::CommandPool,
     constants::{
         PHYSICAL_DEVICE_OPTIONAL_EXTENSION_NAMES, PHYSICAL_DEVICE_REQUIRED_EXTENSION_NAMES,
     },
+    error::RendererError,
+    memory::Allocator,
     physical_device::PhysicalDevice,
-    utils::extension::Extension,
+    utils::{
+        cstringstuff::CStrPtrs,
+        extension::Extension,
+        features::{self, DeviceFeatureSet},
+    },
 };
 
 pub struct Device {
@@ -16,12 +36,33 @@ pub struct Device {
     pub physical_device: PhysicalDevice,
     pub enabled_extensions: Vec<Extension>,
     pub enabled_features: PhysicalDeviceFeatures,
+    /// Requested core features the device doesn't support, skipped when it was created.
+    pub unsupported_features: Vec<&'static str>,
+    /// Timeline semaphores (core in Vulkan 1.2) are enabled whenever the device supports them.
+    pub timeline_semaphore: bool,
+    /// The descriptor indexing features bindless textures need (core in Vulkan 1.2): partially
+    /// bound and update-after-bind runtime arrays of sampled images with non-uniform indexing.
+    pub descriptor_indexing: bool,
+    /// Loaded when `VK_KHR_dynamic_rendering` is supported.
+    pub dynamic_rendering: Option<DynamicRendering>,
+    /// Loaded when `VK_EXT_extended_dynamic_state` is supported.
+    pub extended_dynamic_state: Option<ExtendedDynamicState>,
+    /// Loaded when `VK_EXT_hdr_metadata` is supported.
+    pub hdr_metadata: Option<ExtHdrMetadataFn>,
     pub graphics_queue: Queue,
+    /// Same as `graphics_queue` when rendering headless.
     pub present_queue: Queue,
+    /// Queue of a dedicated transfer family, same as `graphics_queue` if there's none.
+    pub transfer_queue: Queue,
+    pub allocator: ManuallyDrop<Arc<Mutex<Allocator>>>,
 }
 
 impl Device {
-    pub fn new(instance: &Instance, physical_device: PhysicalDevice) -> Self {
+    pub fn new(
+        instance: &Instance,
+        physical_device: PhysicalDevice,
+        requested_features: &PhysicalDeviceFeatures,
+    ) -> Result<Self, RendererError> {
         let mut queue_create_infos: Vec<DeviceQueueCreateInfo> = Vec::new();
         let unique_queue_families = physical_device.queue_family_indices.get_unique_indices();
         let queue_priorities = [1.0];
@@ -42,21 +83,102 @@ impl Device {
             .cloned()
             .collect();
 
-        let enabled_extensions_names_raw: Vec<*const i8> =
-            enabled_extensions.iter().map(|x| x.name.as_ptr()).collect();
+        // Borrows `enabled_extensions` until the device is created.
+        let enabled_extensions_names_raw =
+            CStrPtrs::new(enabled_extensions.iter().map(|x| x.name.as_c_str()));
+
+        let (enabled_features, unsupported_features) =
+            features::filter_supported(requested_features, &physical_device.features);
+        for feature in &unsupported_features {
+            warn!(
+                "Requested device feature {} is not supported, skipping it",
+                feature
+            );
+        }
 
-        let enabled_features = PhysicalDeviceFeatures::builder().build();
+        let has_dynamic_rendering_extension = enabled_extensions
+            .iter()
+            .any(|x| x.name.as_c_str() == DynamicRendering::name());
+        let has_extended_dynamic_state_extension = enabled_extensions
+            .iter()
+            .any(|x| x.name.as_c_str() == ExtendedDynamicState::name());
+        let mut supported_vulkan12_features = PhysicalDeviceVulkan12Features::default();
+        let mut supported_dynamic_rendering_features =
+            PhysicalDeviceDynamicRenderingFeatures::default();
+        let mut supported_features2 =
+            PhysicalDeviceFeatures2::builder().push_next(&mut supported_vulkan12_features);
+        if has_dynamic_rendering_extension {
+            supported_features2 =
+                supported_features2.push_next(&mut supported_dynamic_rendering_features);
+        }
+        let mut supported_extended_dynamic_state_features =
+            PhysicalDeviceExtendedDynamicStateFeaturesEXT::default();
+        if has_extended_dynamic_state_extension {
+            supported_features2 =
+                supported_features2.push_next(&mut supported_extended_dynamic_state_features);
+        }
+        unsafe {
+            instance.get_physical_device_features2(physical_device.inner, &mut supported_features2);
+        }
+        let timeline_semaphore = supported_vulkan12_features.timeline_semaphore != 0;
+        let descriptor_indexing = supported_vulkan12_features.descriptor_binding_partially_bound
+            != 0
+            && supported_vulkan12_features.runtime_descriptor_array != 0
+            && supported_vulkan12_features.shader_sampled_image_array_non_uniform_indexing != 0
+            && supported_vulkan12_features.descriptor_binding_sampled_image_update_after_bind != 0;
+        let dynamic_rendering = supported_dynamic_rendering_features.dynamic_rendering != 0;
+        let extended_dynamic_state =
+            supported_extended_dynamic_state_features.extended_dynamic_state != 0;
+        let mut vulkan12_features = PhysicalDeviceVulkan12Features::builder()
+            .timeline_semaphore(timeline_semaphore)
+            .descriptor_binding_partially_bound(descriptor_indexing)
+            .runtime_descriptor_array(descriptor_indexing)
+            .shader_sampled_image_array_non_uniform_indexing(descriptor_indexing)
+            .descriptor_binding_sampled_image_update_after_bind(descriptor_indexing);
+        let mut dynamic_rendering_features =
+            PhysicalDeviceDynamicRenderingFeatures::builder().dynamic_rendering(dynamic_rendering);
+        let mut extended_dynamic_state_features =
+            PhysicalDeviceExtendedDynamicStateFeaturesEXT::builder()
+                .extended_dynamic_state(extended_dynamic_state);
 
-        let device_create_info = DeviceCreateInfo::builder()
+        let mut device_create_info = DeviceCreateInfo::builder()
             .queue_create_infos(&queue_create_infos)
-            .enabled_extension_names(&enabled_extensions_names_raw)
-            .enabled_features(&enabled_features);
+            .enabled_extension_names(enabled_extensions_names_raw.as_slice())
+            .enabled_features(&enabled_features)
+            .push_next(&mut vulkan12_features);
+        if has_dynamic_rendering_extension {
+            device_create_info = device_create_info.push_next(&mut dynamic_rendering_features);
+        }
+        if has_extended_dynamic_state_extension {
+            device_create_info = device_create_info.push_next(&mut extended_dynamic_state_features);
+        }
 
-        let inner = unsafe {
-            instance
-                .create_device(physical_device.inner, &device_create_info, None)
-                .unwrap()
-        };
+        let inner =
+            unsafe { instance.create_device(physical_device.inner, &device_create_info, None)? };
+        let dynamic_rendering = dynamic_rendering.then(|| DynamicRendering::new(instance, &inner));
+        let extended_dynamic_state =
+            extended_dynamic_state.then(|| ExtendedDynamicState::new(instance, &inner));
+        let hdr_metadata = enabled_extensions
+            .iter()
+            .any(|x| x.name.as_c_str() == ExtHdrMetadataFn::name())
+            .then(|| {
+                ExtHdrMetadataFn::load(|name| unsafe {
+                    std::mem::transmute(
+                        instance.get_device_proc_addr(inner.handle(), name.as_ptr()),
+                    )
+                })
+            });
+        for extension in &enabled_extensions {
+            info!("Enabled device extension {}", extension);
+        }
+        for name in PHYSICAL_DEVICE_OPTIONAL_EXTENSION_NAMES.iter() {
+            if !enabled_extensions.iter().any(|x| x.name == *name) {
+                info!(
+                    "Optional device extension {} is not supported",
+                    name.to_string_lossy()
+                );
+            }
+        }
 
         let graphics_queue = unsafe {
             inner.get_device_queue(
@@ -68,26 +190,111 @@ impl Device {
             )
         };
 
-        let present_queue = unsafe {
-            inner.get_device_queue(
-                physical_device.queue_family_indices.present_family.unwrap(),
-                0,
-            )
+        let present_queue = match physical_device.queue_family_indices.present_family {
+            Some(present_family) => unsafe { inner.get_device_queue(present_family, 0) },
+            None => graphics_queue,
+        };
+
+        let transfer_queue = match physical_device.queue_family_indices.transfer_family {
+            Some(transfer_family) => unsafe { inner.get_device_queue(transfer_family, 0) },
+            None => graphics_queue,
         };
 
-        Self {
+        let allocator = Arc::new(Mutex::new(Allocator::new(
+            instance,
+            physical_device.inner,
+            &inner,
+        )));
+
+        Ok(Self {
             inner,
+            allocator: ManuallyDrop::new(allocator),
             physical_device,
             enabled_features,
+            unsupported_features,
+            timeline_semaphore,
+            descriptor_indexing,
+            dynamic_rendering,
+            extended_dynamic_state,
+            hdr_metadata,
             enabled_extensions,
             graphics_queue,
             present_queue,
+            transfer_queue,
+        })
+    }
+
+    /// Family of `graphics_queue`.
+    pub fn graphics_family_index(&self) -> u32 {
+        self.physical_device
+            .queue_family_indices
+            .graphics_family
+            .unwrap()
+    }
+
+    /// Family of `present_queue`, `None` when rendering headless.
+    pub fn present_family_index(&self) -> Option<u32> {
+        self.physical_device.queue_family_indices.present_family
+    }
+
+    /// Family of `transfer_queue`, the graphics family if there's no dedicated one.
+    pub fn transfer_family_index(&self) -> u32 {
+        self.physical_device
+            .queue_family_indices
+            .transfer_family
+            .unwrap_or_else(|| self.graphics_family_index())
+    }
+
+    /// Capabilities and queue count of one of the families above.
+    pub fn queue_family_properties(&self, family_index: u32) -> &QueueFamilyProperties {
+        &self.physical_device.queue_family_properties[family_index as usize]
+    }
+
+    /// Records `record` into a short-lived command buffer, submits it to `graphics_queue` and
+    /// blocks until it has executed. For layout transitions and copies outside of a frame.
+    pub fn one_time_submit<F: FnOnce(CommandBuffer)>(&self, record: F) {
+        CommandPool::new_transient(self, self.graphics_family_index())
+            .submit_once(self.graphics_queue, record);
+    }
+
+    /// Like `one_time_submit`, on `transfer_queue`. Resources used on the graphics queue
+    /// afterwards need a queue family ownership transfer if the families differ.
+    pub fn one_time_submit_transfer<F: FnOnce(CommandBuffer)>(&self, record: F) {
+        CommandPool::new_transient(self, self.transfer_family_index())
+            .submit_once(self.transfer_queue, record);
+    }
+
+    pub fn feature_set(&self) -> DeviceFeatureSet {
+        DeviceFeatureSet {
+            enabled: self.enabled_features,
+            unsupported: self.unsupported_features.clone(),
+            timeline_semaphore: self.timeline_semaphore,
+            descriptor_indexing: self.descriptor_indexing,
+            dynamic_rendering: self.dynamic_rendering.is_some(),
+            extended_dynamic_state: self.extended_dynamic_state.is_some(),
         }
     }
+
+    pub fn enabled_extension_names(&self) -> Vec<&CStr> {
+        self.enabled_extensions
+            .iter()
+            .map(|x| x.name.as_c_str())
+            .collect()
+    }
+
+    pub fn has_extension(&self, name: &CStr) -> bool {
+        self.enabled_extensions
+            .iter()
+            .any(|x| (x.name).as_c_str() == name)
+    }
 }
 
 impl Drop for Device {
     fn drop(&mut self) {
-        unsafe { self.inner.destroy_device(None) };
+        // The allocator still owns memory blocks of this device, so it has to go first.
+        unsafe {
+            ManuallyDrop::drop(&mut self.allocator);
+            self.inner.destroy_device(None);
+        }
     }
 }
@@ -0,0 +1,110 @@
+use std::{ffi::CString, fmt};
+
+use super::utils::apiversion::ApiVersion;
+
+#[derive(Debug)]
+pub enum RendererError {
+    /// The Vulkan loader or driver is older than the API version the renderer targets.
+    UnsupportedApiVersion {
+        actual: ApiVersion,
+        required: ApiVersion,
+    },
+    MissingLayer(CString),
+    MissingExtension(CString),
+    /// An instance extension the window system needs to create a surface, e.g. `VK_KHR_wayland_surface`.
+    MissingSurfaceExtension(CString),
+    /// The Vulkan loader found no working driver (ICD), or the driver exposes no GPU.
+    NoVulkanDriver,
+    NoSuitablePhysicalDevice,
+    /// The renderer is headless or its GPU can't present to the window added with `Renderer::add_window`.
+    UnsupportedSurface,
+    /// `Renderer::draw_window` was given a window that wasn't added with `add_window` or has been
+    /// removed.
+    UnknownWindow,
+    /// Waiting for a frame's fence exceeded `FENCE_TIMEOUT`.
+    FenceTimeout,
+    /// The GPU was reset or crashed. See `Renderer::recreate_device`.
+    DeviceLost,
+    /// The window's surface went away, e.g. on display hotplug or a driver update. Raised by
+    /// `Renderer::draw_frame` when acquiring or presenting, and by `recreate_swapchain` when the
+    /// swapchain can't be rebuilt. See `Renderer::recreate_surface`.
+    SurfaceLost,
+    /// `Renderer::read_pixels` was called before a frame was drawn into the offscreen image.
+    NoFrameDrawn,
+    /// The surface doesn't support `TRANSFER_SRC` usage for swapchain images, which
+    /// `Renderer::capture_frame` copies from.
+    UnsupportedCapture,
+    /// `Renderer::capture_frame` only reads back 8 bit RGBA and BGRA swapchains, not e.g. HDR ones.
+    UnsupportedCaptureFormat(ash::vk::Format),
+    /// Encoding or writing a captured frame failed.
+    Image(::image::ImageError),
+    Vulkan(ash::vk::Result),
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RendererError::UnsupportedApiVersion { actual, required } => write!(
+                f,
+                "Vulkan API version is too low: {}, required: {}",
+                actual, required
+            ),
+            RendererError::MissingLayer(name) => {
+                write!(f, "Required layer {:?} is not supported", name)
+            }
+            RendererError::MissingExtension(name) => {
+                write!(f, "Required extension {:?} is not supported", name)
+            }
+            RendererError::MissingSurfaceExtension(name) => write!(
+                f,
+                "Surface extension {:?} required by the window system is not supported",
+                name
+            ),
+            RendererError::NoVulkanDriver => write!(
+                f,
+                "No working Vulkan driver found. Install or update the GPU driver, or a Vulkan \
+                 runtime such as Mesa's lavapipe for software rendering"
+            ),
+            RendererError::NoSuitablePhysicalDevice => write!(f, "No suitable GPU found"),
+            RendererError::UnsupportedSurface => {
+                write!(f, "Presenting to the window is not supported")
+            }
+            RendererError::UnknownWindow => write!(f, "The window was not added to the renderer"),
+            RendererError::DeviceLost => write!(f, "Device lost"),
+            RendererError::SurfaceLost => write!(f, "Surface lost"),
+            RendererError::NoFrameDrawn => write!(f, "No frame has been drawn yet"),
+            RendererError::UnsupportedCapture => {
+                write!(f, "Copying from swapchain images is not supported")
+            }
+            RendererError::UnsupportedCaptureFormat(format) => {
+                write!(
+                    f,
+                    "Capturing {:?} swapchain images is not supported",
+                    format
+                )
+            }
+            RendererError::Image(err) => write!(f, "Failed to save image: {}", err),
+            RendererError::FenceTimeout => {
+                write!(f, "Timed out waiting for the GPU to finish a frame")
+            }
+            RendererError::Vulkan(result) => write!(f, "Vulkan call failed: {}", result),
+        }
+    }
+}
+
+impl std::error::Error for RendererError {}
+
+impl From<ash::vk::Result> for RendererError {
+    fn from(result: ash::vk::Result) -> Self {
+        match result {
+            ash::vk::Result::ERROR_SURFACE_LOST_KHR => RendererError::SurfaceLost,
+            result => RendererError::Vulkan(result),
+        }
+    }
+}
+
+impl From<::image::ImageError> for RendererError {
+    fn from(err: ::image::ImageError) -> Self {
+        RendererError::Image(err)
+    }
+}
@@ -0,0 +1,470 @@
+use ash::vk::{
+    AccessFlags, BufferImageCopy, CommandBuffer, DependencyFlags, Extent2D, Extent3D, Filter,
+    Format, FormatFeatureFlags, ImageAspectFlags, ImageBlit, ImageCreateInfo, ImageLayout,
+    ImageMemoryBarrier, ImageSubresourceLayers, ImageSubresourceRange, ImageTiling, ImageType,
+    ImageUsageFlags, ImageView, ImageViewCreateInfo, ImageViewType, MemoryPropertyFlags, Offset3D,
+    PipelineStageFlags, SampleCountFlags, SharingMode, QUEUE_FAMILY_IGNORED,
+};
+
+use super::{
+    device::Device,
+    memory::{Allocation, Allocator},
+};
+
+pub struct Image {
+    pub inner: ash::vk::Image,
+    pub view: ImageView,
+    pub allocation: Allocation,
+    pub format: Format,
+    pub extent: Extent2D,
+    pub mip_levels: u32,
+    device: ash::Device,
+}
+
+impl Image {
+    pub fn new(
+        device: &Device,
+        extent: Extent2D,
+        mip_levels: u32,
+        samples: SampleCountFlags,
+        format: Format,
+        usage: ImageUsageFlags,
+        properties: MemoryPropertyFlags,
+    ) -> Self {
+        let create_info = ImageCreateInfo::builder()
+            .image_type(ImageType::TYPE_2D)
+            .extent(Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            })
+            .mip_levels(mip_levels)
+            .array_layers(1)
+            .format(format)
+            .tiling(ImageTiling::OPTIMAL)
+            .initial_layout(ImageLayout::UNDEFINED)
+            .usage(usage)
+            .sharing_mode(SharingMode::EXCLUSIVE)
+            .samples(samples);
+
+        let inner = unsafe { device.inner.create_image(&create_info, None).unwrap() };
+
+        let requirements = unsafe { device.inner.get_image_memory_requirements(inner) };
+        let allocation =
+            Allocator::allocate(&device.allocator, "image", requirements, properties, false);
+        unsafe {
+            device
+                .inner
+                .bind_image_memory(inner, allocation.memory(), allocation.offset())
+                .unwrap();
+        }
+
+        let view_create_info = ImageViewCreateInfo::builder()
+            .image(inner)
+            .view_type(ImageViewType::TYPE_2D)
+            .format(format)
+            .subresource_range(
+                ImageSubresourceRange::builder()
+                    .aspect_mask(aspect_flags(format))
+                    .base_mip_level(0)
+                    .level_count(mip_levels)
+                    .base_array_layer(0)
+                    .layer_count(1)
+                    .build(),
+            );
+        let view = unsafe {
+            device
+                .inner
+                .create_image_view(&view_create_info, None)
+                .unwrap()
+        };
+
+        Self {
+            inner,
+            view,
+            allocation,
+            format,
+            extent,
+            mip_levels,
+            device: device.inner.clone(),
+        }
+    }
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_image_view(self.view, None);
+            self.device.destroy_image(self.inner, None);
+        }
+    }
+}
+
+/// Aspects an image view of `format` has to cover.
+pub fn aspect_flags(format: Format) -> ImageAspectFlags {
+    match format {
+        Format::D16_UNORM | Format::D32_SFLOAT | Format::X8_D24_UNORM_PACK32 => {
+            ImageAspectFlags::DEPTH
+        }
+        Format::D16_UNORM_S8_UINT | Format::D24_UNORM_S8_UINT | Format::D32_SFLOAT_S8_UINT => {
+            ImageAspectFlags::DEPTH | ImageAspectFlags::STENCIL
+        }
+        Format::S8_UINT => ImageAspectFlags::STENCIL,
+        _ => ImageAspectFlags::COLOR,
+    }
+}
+/// First depth format usable as an optimally tiled depth attachment, preferring 32-bit float.
+pub fn find_depth_format(instance: &ash::Instance, device: &Device) -> Format {
+    [
+        Format::D32_SFLOAT,
+        Format::D32_SFLOAT_S8_UINT,
+        Format::D24_UNORM_S8_UINT,
+    ]
+    .into_iter()
+    .find(|format| {
+        let properties = unsafe {
+            instance.get_physical_device_format_properties(device.physical_device.inner, *format)
+        };
+        properties
+            .optimal_tiling_features
+            .contains(FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+    })
+    .unwrap_or(Format::D16_UNORM)
+}
+
+/// First format usable as an optimally tiled depth-stencil attachment, preferring `D24_UNORM_S8_UINT`.
+pub fn find_depth_stencil_format(instance: &ash::Instance, device: &Device) -> Format {
+    [
+        Format::D24_UNORM_S8_UINT,
+        Format::D32_SFLOAT_S8_UINT,
+        Format::D16_UNORM_S8_UINT,
+    ]
+    .into_iter()
+    .find(|format| {
+        let properties = unsafe {
+            instance.get_physical_device_format_properties(device.physical_device.inner, *format)
+        };
+        properties
+            .optimal_tiling_features
+            .contains(FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+    })
+    .expect("No depth-stencil format is supported")
+}
+
+/// Records a pipeline barrier moving all mip levels of an image from `old_layout` to `new_layout`.
+/// Transitions to `DEPTH_STENCIL_ATTACHMENT_OPTIMAL` cover the depth and stencil aspects, all
+/// others the color aspect.
+pub fn transition_image_layout(
+    device: &ash::Device,
+    command_buffer: CommandBuffer,
+    image: ash::vk::Image,
+    mip_levels: u32,
+    old_layout: ImageLayout,
+    new_layout: ImageLayout,
+) {
+    let (src_access_mask, dst_access_mask, src_stage, dst_stage) = match (old_layout, new_layout) {
+        (ImageLayout::UNDEFINED, ImageLayout::TRANSFER_DST_OPTIMAL) => (
+            AccessFlags::empty(),
+            AccessFlags::TRANSFER_WRITE,
+            PipelineStageFlags::TOP_OF_PIPE,
+            PipelineStageFlags::TRANSFER,
+        ),
+        (ImageLayout::TRANSFER_DST_OPTIMAL, ImageLayout::SHADER_READ_ONLY_OPTIMAL) => (
+            AccessFlags::TRANSFER_WRITE,
+            AccessFlags::SHADER_READ,
+            PipelineStageFlags::TRANSFER,
+            PipelineStageFlags::FRAGMENT_SHADER,
+        ),
+        (ImageLayout::UNDEFINED, ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL) => (
+            AccessFlags::empty(),
+            AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                | AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            PipelineStageFlags::EARLY_FRAGMENT_TESTS | PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            PipelineStageFlags::EARLY_FRAGMENT_TESTS | PipelineStageFlags::LATE_FRAGMENT_TESTS,
+        ),
+        (ImageLayout::UNDEFINED, ImageLayout::COLOR_ATTACHMENT_OPTIMAL) => (
+            AccessFlags::empty(),
+            AccessFlags::COLOR_ATTACHMENT_WRITE,
+            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        ),
+        (ImageLayout::COLOR_ATTACHMENT_OPTIMAL, ImageLayout::PRESENT_SRC_KHR) => (
+            AccessFlags::COLOR_ATTACHMENT_WRITE,
+            AccessFlags::empty(),
+            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            PipelineStageFlags::BOTTOM_OF_PIPE,
+        ),
+        (ImageLayout::PRESENT_SRC_KHR, ImageLayout::COLOR_ATTACHMENT_OPTIMAL) => (
+            AccessFlags::empty(),
+            AccessFlags::COLOR_ATTACHMENT_READ | AccessFlags::COLOR_ATTACHMENT_WRITE,
+            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        ),
+        // Gives a new image the layout a pass loading it expects.
+        (ImageLayout::UNDEFINED, ImageLayout::PRESENT_SRC_KHR)
+        | (ImageLayout::UNDEFINED, ImageLayout::TRANSFER_SRC_OPTIMAL) => (
+            AccessFlags::empty(),
+            AccessFlags::empty(),
+            PipelineStageFlags::TOP_OF_PIPE,
+            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        ),
+        // Reads back a swapchain image in the frame that rendered it.
+        (ImageLayout::PRESENT_SRC_KHR, ImageLayout::TRANSFER_SRC_OPTIMAL) => (
+            AccessFlags::COLOR_ATTACHMENT_WRITE,
+            AccessFlags::TRANSFER_READ,
+            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            PipelineStageFlags::TRANSFER,
+        ),
+        // Followed by the release to the present queue family, which waits on `TRANSFER`.
+        (ImageLayout::TRANSFER_SRC_OPTIMAL, ImageLayout::PRESENT_SRC_KHR) => (
+            AccessFlags::TRANSFER_READ,
+            AccessFlags::empty(),
+            PipelineStageFlags::TRANSFER,
+            PipelineStageFlags::TRANSFER,
+        ),
+        _ => panic!(
+            "Unsupported layout transition: {:?} -> {:?}",
+            old_layout, new_layout
+        ),
+    };
+
+    let aspect_mask = if new_layout == ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL {
+        ImageAspectFlags::DEPTH | ImageAspectFlags::STENCIL
+    } else {
+        ImageAspectFlags::COLOR
+    };
+    let barrier = ImageMemoryBarrier::builder()
+        .old_layout(old_layout)
+        .new_layout(new_layout)
+        .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(
+            ImageSubresourceRange::builder()
+                .aspect_mask(aspect_mask)
+                .base_mip_level(0)
+                .level_count(mip_levels)
+                .base_array_layer(0)
+                .layer_count(1)
+                .build(),
+        )
+        .src_access_mask(src_access_mask)
+        .dst_access_mask(dst_access_mask);
+
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            src_stage,
+            dst_stage,
+            DependencyFlags::empty(),
+            &[],
+            &[],
+            &[barrier.build()],
+        );
+    }
+}
+
+/// Records a copy of tightly packed pixels from `buffer` into the first mip level of `image`,
+/// which has to be in `TRANSFER_DST_OPTIMAL` layout.
+pub fn copy_buffer_to_image(
+    device: &ash::Device,
+    command_buffer: CommandBuffer,
+    buffer: ash::vk::Buffer,
+    image: ash::vk::Image,
+    extent: Extent2D,
+) {
+    let region = BufferImageCopy::builder()
+        .buffer_offset(0)
+        .buffer_row_length(0)
+        .buffer_image_height(0)
+        .image_subresource(
+            ImageSubresourceLayers::builder()
+                .aspect_mask(ImageAspectFlags::COLOR)
+                .mip_level(0)
+                .base_array_layer(0)
+                .layer_count(1)
+                .build(),
+        )
+        .image_offset(Offset3D { x: 0, y: 0, z: 0 })
+        .image_extent(Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        });
+
+    unsafe {
+        device.cmd_copy_buffer_to_image(
+            command_buffer,
+            buffer,
+            image,
+            ImageLayout::TRANSFER_DST_OPTIMAL,
+            &[region.build()],
+        );
+    }
+}
+/// Copies the first mip level of an image in `TRANSFER_SRC_OPTIMAL` layout into a tightly packed buffer.
+pub fn copy_image_to_buffer(
+    device: &ash::Device,
+    command_buffer: CommandBuffer,
+    image: ash::vk::Image,
+    buffer: ash::vk::Buffer,
+    extent: Extent2D,
+) {
+    let region = BufferImageCopy::builder()
+        .buffer_offset(0)
+        .buffer_row_length(0)
+        .buffer_image_height(0)
+        .image_subresource(
+            ImageSubresourceLayers::builder()
+                .aspect_mask(ImageAspectFlags::COLOR)
+                .mip_level(0)
+                .base_array_layer(0)
+                .layer_count(1)
+                .build(),
+        )
+        .image_offset(Offset3D { x: 0, y: 0, z: 0 })
+        .image_extent(Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        });
+
+    unsafe {
+        device.cmd_copy_image_to_buffer(
+            command_buffer,
+            image,
+            ImageLayout::TRANSFER_SRC_OPTIMAL,
+            buffer,
+            &[region.build()],
+        );
+    }
+}
+
+/// Fills mip levels `1..mip_levels` by repeatedly blitting the previous level at half size.
+/// Expects every level in `TRANSFER_DST_OPTIMAL` and leaves all of them in `SHADER_READ_ONLY_OPTIMAL`.
+pub fn generate_mipmaps(
+    device: &ash::Device,
+    command_buffer: CommandBuffer,
+    image: ash::vk::Image,
+    extent: Extent2D,
+    mip_levels: u32,
+) {
+    let mut barrier = ImageMemoryBarrier::builder()
+        .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(
+            ImageSubresourceRange::builder()
+                .aspect_mask(ImageAspectFlags::COLOR)
+                .level_count(1)
+                .base_array_layer(0)
+                .layer_count(1)
+                .build(),
+        )
+        .build();
+
+    let mut mip_width = extent.width as i32;
+    let mut mip_height = extent.height as i32;
+
+    for level in 1..mip_levels {
+        barrier.subresource_range.base_mip_level = level - 1;
+        barrier.old_layout = ImageLayout::TRANSFER_DST_OPTIMAL;
+        barrier.new_layout = ImageLayout::TRANSFER_SRC_OPTIMAL;
+        barrier.src_access_mask = AccessFlags::TRANSFER_WRITE;
+        barrier.dst_access_mask = AccessFlags::TRANSFER_READ;
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                PipelineStageFlags::TRANSFER,
+                PipelineStageFlags::TRANSFER,
+                DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+        }
+
+        let next_width = (mip_width / 2).max(1);
+        let next_height = (mip_height / 2).max(1);
+        let blit = ImageBlit::builder()
+            .src_offsets([
+                Offset3D { x: 0, y: 0, z: 0 },
+                Offset3D {
+                    x: mip_width,
+                    y: mip_height,
+                    z: 1,
+                },
+            ])
+            .src_subresource(
+                ImageSubresourceLayers::builder()
+                    .aspect_mask(ImageAspectFlags::COLOR)
+                    .mip_level(level - 1)
+                    .base_array_layer(0)
+                    .layer_count(1)
+                    .build(),
+            )
+            .dst_offsets([
+                Offset3D { x: 0, y: 0, z: 0 },
+                Offset3D {
+                    x: next_width,
+                    y: next_height,
+                    z: 1,
+                },
+            ])
+            .dst_subresource(
+                ImageSubresourceLayers::builder()
+                    .aspect_mask(ImageAspectFlags::COLOR)
+                    .mip_level(level)
+                    .base_array_layer(0)
+                    .layer_count(1)
+                    .build(),
+            );
+        unsafe {
+            device.cmd_blit_image(
+                command_buffer,
+                image,
+                ImageLayout::TRANSFER_SRC_OPTIMAL,
+                image,
+                ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[blit.build()],
+                Filter::LINEAR,
+            );
+        }
+
+        barrier.old_layout = ImageLayout::TRANSFER_SRC_OPTIMAL;
+        barrier.new_layout = ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        barrier.src_access_mask = AccessFlags::TRANSFER_READ;
+        barrier.dst_access_mask = AccessFlags::SHADER_READ;
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                PipelineStageFlags::TRANSFER,
+                PipelineStageFlags::FRAGMENT_SHADER,
+                DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+        }
+
+        mip_width = next_width;
+        mip_height = next_height;
+    }
+
+    barrier.subresource_range.base_mip_level = mip_levels - 1;
+    barrier.old_layout = ImageLayout::TRANSFER_DST_OPTIMAL;
+    barrier.new_layout = ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+    barrier.src_access_mask = AccessFlags::TRANSFER_WRITE;
+    barrier.dst_access_mask = AccessFlags::SHADER_READ;
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            PipelineStageFlags::TRANSFER,
+            PipelineStageFlags::FRAGMENT_SHADER,
+            DependencyFlags::empty(),
+            &[],
+            &[],
+            &[barrier],
+        );
+    }
+}
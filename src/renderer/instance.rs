@@ -1,10 +1,11 @@
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 
 use ash::{
     extensions::ext::DebugUtils,
     vk::{ApplicationInfo, InstanceCreateInfo},
     Entry,
 };
+use log::info;
 use winit::window::Window;
 
 use crate::renderer::{
@@ -17,11 +18,13 @@ use crate::renderer::{
 };
 
 use super::{
+    config::RendererConfig,
     constants::{
         INSTANCE_API_VERSION, INSTANCE_APPLICATION_NAME, INSTANCE_APPLICATION_VERSION,
         INSTANCE_ENGINE_NAME, INSTANCE_ENGINE_VERSION,
     },
-    utils::{apiversion::ApiVersion, debug::DebugMessenger, layer::Layer},
+    error::RendererError,
+    utils::{apiversion::ApiVersion, cstringstuff::CStrPtrs, debug::DebugMessenger, layer::Layer},
 };
 
 pub struct Instance {
@@ -32,58 +35,64 @@ pub struct Instance {
 }
 
 impl Instance {
-    pub fn new(entry: &Entry, window: &Window) -> Self {
-        let version = match entry.try_enumerate_instance_version().unwrap() {
+    /// Without a window no surface extensions are enabled and only headless rendering is possible.
+    pub fn new(
+        entry: &Entry,
+        window: Option<&Window>,
+        config: &RendererConfig,
+    ) -> Result<Self, RendererError> {
+        let version = match entry.try_enumerate_instance_version()? {
             Some(version) => ApiVersion::from(version),
             None => ApiVersion::new(0, 1, 0, 0),
         };
 
         if version < *INSTANCE_API_VERSION {
-            panic!(
-                "Vulkan API Version is too low! Actual: {:?}, Required: {:?}",
-                version, *INSTANCE_API_VERSION
-            );
+            return Err(RendererError::UnsupportedApiVersion {
+                actual: version,
+                required: *INSTANCE_API_VERSION,
+            });
         }
 
         let mut layers: Vec<Layer> =
-            Layer::convert_vec(&entry.enumerate_instance_layer_properties().unwrap());
-        layers = layers
-            .into_iter()
-            .filter(|l| {
-                let mut is_debug = false;
-                if cfg!(debug_assertions) {
-                    is_debug = INSTANCE_DEBUG_LAYER_NAMES.contains(&l.name);
-                }
-                is_debug || INSTANCE_REQUIRED_LAYER_NAMES.contains(&l.name)
-            })
-            .collect();
+            Layer::convert_vec(&entry.enumerate_instance_layer_properties()?);
+        layers.retain(|l| {
+            let mut is_debug = false;
+            if config.validation {
+                is_debug = INSTANCE_DEBUG_LAYER_NAMES.contains(&l.name);
+            }
+            is_debug || INSTANCE_REQUIRED_LAYER_NAMES.contains(&l.name)
+        });
 
         let mut extensions: Vec<Extension> =
-            Extension::convert_vec(&entry.enumerate_instance_extension_properties(None).unwrap());
+            Extension::convert_vec(&entry.enumerate_instance_extension_properties(None)?);
         for layer in &layers {
             extensions.extend(Extension::convert_vec(
-                &entry
-                    .enumerate_instance_extension_properties(Some(layer.name.as_c_str()))
-                    .unwrap(),
+                &entry.enumerate_instance_extension_properties(Some(layer.name.as_c_str()))?,
             ));
         }
 
-        extensions = extensions
-            .into_iter()
-            .filter(|e| {
-                let mut is_debug = false;
-                if cfg!(debug_assertions) {
-                    is_debug = INSTANCE_DEBUG_EXTENSION_NAMES.contains(&e.name);
-                }
-                INSTANCE_REQUIRED_EXTENSION_NAMES.contains(&e.name)
-                    || INSTANCE_OPTIONAL_EXTENSION_NAMES.contains(&e.name)
-                    || is_debug
-            })
-            .collect();
+        // Surface extensions the window system needs, e.g. VK_KHR_surface and VK_KHR_xlib_surface.
+        let surface_extension_names: Vec<CString> = match window {
+            Some(window) => ash_window::enumerate_required_extensions(window)?
+                .iter()
+                .map(|name| unsafe { CStr::from_ptr(*name) }.to_owned())
+                .collect(),
+            None => Vec::new(),
+        };
+
+        extensions.retain(|e| {
+            let mut is_debug = false;
+            if config.validation {
+                is_debug = INSTANCE_DEBUG_EXTENSION_NAMES.contains(&e.name);
+            }
+            INSTANCE_REQUIRED_EXTENSION_NAMES.contains(&e.name)
+                || INSTANCE_OPTIONAL_EXTENSION_NAMES.contains(&e.name)
+                || surface_extension_names.contains(&e.name)
+                || is_debug
+        });
 
         for required in &*INSTANCE_REQUIRED_LAYER_NAMES {
             let mut is_supported = false;
-            let last_checked_name = required.clone();
             for layer in &layers {
                 if *required == layer.name {
                     is_supported = true;
@@ -91,13 +100,12 @@ impl Instance {
                 }
             }
             if !is_supported {
-                panic!("REQUIRED LAYER NOT SUPPORTED: {:?}", last_checked_name);
+                return Err(RendererError::MissingLayer(required.clone()));
             }
         }
 
         for required in &*INSTANCE_REQUIRED_EXTENSION_NAMES {
             let mut is_supported = false;
-            let last_checked_name = required.clone();
             for extension in &extensions {
                 if *required == extension.name {
                     is_supported = true;
@@ -105,49 +113,75 @@ impl Instance {
                 }
             }
             if !is_supported {
-                panic!("REQUIRED EXTENSION NOT SUPPORTED: {:?}", last_checked_name);
+                return Err(RendererError::MissingExtension(required.clone()));
             }
         }
 
+        for required in surface_extension_names {
+            if !extensions
+                .iter()
+                .any(|extension| extension.name == required)
+            {
+                return Err(RendererError::MissingSurfaceExtension(required));
+            }
+        }
+
+        let application_name = CString::new(config.app_name.as_str())
+            .unwrap_or_else(|_| INSTANCE_APPLICATION_NAME.clone());
         let application_info = ApplicationInfo::builder()
-            .application_name(&INSTANCE_APPLICATION_NAME)
+            .application_name(&application_name)
             .application_version(INSTANCE_APPLICATION_VERSION.u32())
             .engine_name(&INSTANCE_ENGINE_NAME)
             .engine_version(INSTANCE_ENGINE_VERSION.u32())
             .api_version(INSTANCE_API_VERSION.u32_patchless());
 
-        let layer_names_raw: Vec<*const i8> =
-            layers.iter().map(|l| l.name.as_c_str().as_ptr()).collect();
-
-        let mut extension_names_raw: Vec<*const i8> = extensions
-            .iter()
-            .map(|l| l.name.as_c_str().as_ptr())
-            .collect();
-        extension_names_raw.extend(ash_window::enumerate_required_extensions(window).unwrap());
+        // Borrow `layers` and `extensions`, which can't be modified until the instance is created.
+        let layer_names_raw = CStrPtrs::new(layers.iter().map(|l| l.name.as_c_str()));
+        let extension_names_raw = CStrPtrs::new(extensions.iter().map(|e| e.name.as_c_str()));
 
         let mut create_info = InstanceCreateInfo::builder()
             .application_info(&application_info)
-            .enabled_layer_names(&layer_names_raw)
-            .enabled_extension_names(&extension_names_raw);
+            .enabled_layer_names(layer_names_raw.as_slice())
+            .enabled_extension_names(extension_names_raw.as_slice());
 
         let mut instance_debug_create_info;
-        if cfg!(debug_assertions)
+        if config.validation
             && extensions
                 .iter()
                 .any(|x| (x.name).as_c_str() == DebugUtils::name())
         {
-            instance_debug_create_info = DebugMessenger::get_create_info();
+            instance_debug_create_info =
+                DebugMessenger::get_create_info(config.validation_severity);
             create_info = create_info.push_next(&mut instance_debug_create_info);
         }
 
-        let inner = unsafe { entry.create_instance(&create_info, None).unwrap() };
+        // The loader reports a missing or broken driver as an incompatible one.
+        let inner = unsafe {
+            entry
+                .create_instance(&create_info, None)
+                .map_err(|result| match result {
+                    ash::vk::Result::ERROR_INCOMPATIBLE_DRIVER
+                    | ash::vk::Result::ERROR_INITIALIZATION_FAILED => RendererError::NoVulkanDriver,
+                    result => result.into(),
+                })?
+        };
+        for layer in &layers {
+            info!("Enabled instance layer {}", layer);
+        }
+        for extension in &extensions {
+            info!("Enabled instance extension {}", extension);
+        }
 
-        Instance {
+        Ok(Instance {
             inner,
             version,
             layers,
             extensions,
-        }
+        })
+    }
+
+    pub fn enabled_extension_names(&self) -> Vec<&CStr> {
+        self.extensions.iter().map(|x| x.name.as_c_str()).collect()
     }
 
     pub fn has_extension_debug_utils(&self) -> bool {
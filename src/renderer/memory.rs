@@ -0,0 +1,279 @@
+use std::sync::{Arc, Mutex};
+
+use ash::vk::{
+    DeviceMemory, DeviceSize, MemoryHeapFlags, MemoryPropertyFlags, MemoryRequirements,
+    PhysicalDeviceMemoryBudgetPropertiesEXT, PhysicalDeviceMemoryProperties2,
+};
+#[cfg(not(feature = "gpu-allocator"))]
+use ash::vk::{MemoryAllocateInfo, MemoryMapFlags};
+#[cfg(feature = "gpu-allocator")]
+use gpu_allocator::{
+    vulkan::{AllocationCreateDesc, AllocationScheme, AllocatorCreateDesc},
+    AllocatorDebugSettings, MemoryLocation,
+};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AllocationStats {
+    pub allocation_count: usize,
+    pub allocated_bytes: DeviceSize,
+}
+
+/// Memory usage of a single heap, in bytes.
+#[derive(Clone, Copy, Debug)]
+pub struct HeapBudget {
+    pub flags: MemoryHeapFlags,
+    /// Used by this process. Without `VK_EXT_memory_budget` only what this allocator handed out.
+    pub used: DeviceSize,
+    /// How much this process can use before allocations may fail or degrade performance.
+    /// Without `VK_EXT_memory_budget` the heap size.
+    pub available: DeviceSize,
+}
+
+/// Hands out device memory. With the `gpu-allocator` feature allocations are sub-allocated from
+/// larger blocks, otherwise every allocation is a dedicated `allocate_memory` call.
+pub struct Allocator {
+    #[cfg(feature = "gpu-allocator")]
+    inner: gpu_allocator::vulkan::Allocator,
+    instance: ash::Instance,
+    physical_device: ash::vk::PhysicalDevice,
+    device: ash::Device,
+    stats: AllocationStats,
+}
+
+impl Allocator {
+    pub fn new(
+        instance: &ash::Instance,
+        physical_device: ash::vk::PhysicalDevice,
+        device: &ash::Device,
+    ) -> Self {
+        #[cfg(feature = "gpu-allocator")]
+        let inner = gpu_allocator::vulkan::Allocator::new(&AllocatorCreateDesc {
+            instance: instance.clone(),
+            device: device.clone(),
+            physical_device,
+            debug_settings: AllocatorDebugSettings::default(),
+            buffer_device_address: false,
+        })
+        .unwrap();
+
+        Self {
+            #[cfg(feature = "gpu-allocator")]
+            inner,
+            instance: instance.clone(),
+            physical_device,
+            device: device.clone(),
+            stats: AllocationStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> AllocationStats {
+        self.stats
+    }
+
+    /// Per-heap budget, from `VK_EXT_memory_budget` if `has_budget_extension`, otherwise the heap
+    /// sizes with the allocator's total counted against the first `DEVICE_LOCAL` heap.
+    pub fn budget(&self, has_budget_extension: bool) -> Vec<HeapBudget> {
+        let mut budget_properties = PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        let mut properties = PhysicalDeviceMemoryProperties2::builder();
+        if has_budget_extension {
+            properties = properties.push_next(&mut budget_properties);
+        }
+        let mut properties = properties.build();
+        unsafe {
+            self.instance
+                .get_physical_device_memory_properties2(self.physical_device, &mut properties);
+        }
+        let heaps = &properties.memory_properties.memory_heaps
+            [..properties.memory_properties.memory_heap_count as usize];
+
+        if has_budget_extension {
+            return heaps
+                .iter()
+                .enumerate()
+                .map(|(index, heap)| HeapBudget {
+                    flags: heap.flags,
+                    used: budget_properties.heap_usage[index],
+                    available: budget_properties.heap_budget[index],
+                })
+                .collect();
+        }
+        let device_local = heaps
+            .iter()
+            .position(|heap| heap.flags.contains(MemoryHeapFlags::DEVICE_LOCAL));
+        heaps
+            .iter()
+            .enumerate()
+            .map(|(index, heap)| HeapBudget {
+                flags: heap.flags,
+                used: if Some(index) == device_local {
+                    self.stats.allocated_bytes
+                } else {
+                    0
+                },
+                available: heap.size,
+            })
+            .collect()
+    }
+
+    pub fn allocate(
+        allocator: &Arc<Mutex<Allocator>>,
+        name: &str,
+        requirements: MemoryRequirements,
+        properties: MemoryPropertyFlags,
+        linear: bool,
+    ) -> Allocation {
+        let mut this = allocator.lock().unwrap();
+
+        #[cfg(feature = "gpu-allocator")]
+        let inner = {
+            let location = if !properties.contains(MemoryPropertyFlags::HOST_VISIBLE) {
+                MemoryLocation::GpuOnly
+            } else if properties.contains(MemoryPropertyFlags::HOST_CACHED) {
+                MemoryLocation::GpuToCpu
+            } else {
+                MemoryLocation::CpuToGpu
+            };
+            this.inner
+                .allocate(&AllocationCreateDesc {
+                    name,
+                    requirements,
+                    location,
+                    linear,
+                    allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+                })
+                .unwrap()
+        };
+
+        #[cfg(not(feature = "gpu-allocator"))]
+        let memory = {
+            let _ = (name, linear);
+            let memory_type_index = find_memory_type(
+                &this.instance,
+                this.physical_device,
+                requirements.memory_type_bits,
+                properties,
+            );
+            let allocate_info = MemoryAllocateInfo::builder()
+                .allocation_size(requirements.size)
+                .memory_type_index(memory_type_index);
+            unsafe { this.device.allocate_memory(&allocate_info, None).unwrap() }
+        };
+
+        this.stats.allocation_count += 1;
+        this.stats.allocated_bytes += requirements.size;
+
+        Allocation {
+            #[cfg(feature = "gpu-allocator")]
+            inner,
+            #[cfg(not(feature = "gpu-allocator"))]
+            memory,
+            size: requirements.size,
+            allocator: allocator.clone(),
+        }
+    }
+}
+
+/// A block of device memory that is returned to its `Allocator` on drop.
+pub struct Allocation {
+    #[cfg(feature = "gpu-allocator")]
+    inner: gpu_allocator::vulkan::Allocation,
+    #[cfg(not(feature = "gpu-allocator"))]
+    memory: DeviceMemory,
+    size: DeviceSize,
+    allocator: Arc<Mutex<Allocator>>,
+}
+
+impl Allocation {
+    pub fn memory(&self) -> DeviceMemory {
+        #[cfg(feature = "gpu-allocator")]
+        return unsafe { self.inner.memory() };
+        #[cfg(not(feature = "gpu-allocator"))]
+        return self.memory;
+    }
+
+    pub fn offset(&self) -> DeviceSize {
+        #[cfg(feature = "gpu-allocator")]
+        return self.inner.offset();
+        #[cfg(not(feature = "gpu-allocator"))]
+        return 0;
+    }
+
+    pub fn size(&self) -> DeviceSize {
+        self.size
+    }
+
+    /// Copies `data` to the start of the allocation. The memory has to be `HOST_VISIBLE | HOST_COHERENT`.
+    pub fn write(&mut self, data: &[u8]) {
+        assert!(data.len() as DeviceSize <= self.size);
+
+        #[cfg(feature = "gpu-allocator")]
+        self.inner.mapped_slice_mut().unwrap()[..data.len()].copy_from_slice(data);
+
+        #[cfg(not(feature = "gpu-allocator"))]
+        unsafe {
+            let device = &self.allocator.lock().unwrap().device;
+            let mapped = device
+                .map_memory(self.memory, 0, self.size, MemoryMapFlags::empty())
+                .unwrap();
+            std::ptr::copy_nonoverlapping(data.as_ptr(), mapped as *mut u8, data.len());
+            device.unmap_memory(self.memory);
+        }
+    }
+
+    /// Copies the whole allocation into a new vector. The memory has to be `HOST_VISIBLE | HOST_COHERENT`.
+    pub fn read(&self) -> Vec<u8> {
+        #[cfg(feature = "gpu-allocator")]
+        return self.inner.mapped_slice().unwrap()[..self.size as usize].to_vec();
+
+        #[cfg(not(feature = "gpu-allocator"))]
+        unsafe {
+            let device = &self.allocator.lock().unwrap().device;
+            let mapped = device
+                .map_memory(self.memory, 0, self.size, MemoryMapFlags::empty())
+                .unwrap();
+            let data = std::slice::from_raw_parts(mapped as *const u8, self.size as usize).to_vec();
+            device.unmap_memory(self.memory);
+            data
+        }
+    }
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        let mut allocator = self.allocator.lock().unwrap();
+        allocator.stats.allocation_count -= 1;
+        allocator.stats.allocated_bytes -= self.size;
+
+        #[cfg(feature = "gpu-allocator")]
+        allocator
+            .inner
+            .free(std::mem::take(&mut self.inner))
+            .unwrap();
+
+        #[cfg(not(feature = "gpu-allocator"))]
+        unsafe {
+            allocator.device.free_memory(self.memory, None);
+        }
+    }
+}
+
+pub fn find_memory_type(
+    instance: &ash::Instance,
+    physical_device: ash::vk::PhysicalDevice,
+    type_filter: u32,
+    properties: MemoryPropertyFlags,
+) -> u32 {
+    let memory_properties =
+        unsafe { instance.get_physical_device_memory_properties(physical_device) };
+
+    for i in 0..memory_properties.memory_type_count {
+        if type_filter & (1 << i) != 0
+            && memory_properties.memory_types[i as usize]
+                .property_flags
+                .contains(properties)
+        {
+            return i;
+        }
+    }
+    panic!("No suitable memory type found for {:?}!", properties);
+}
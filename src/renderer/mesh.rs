@@ -0,0 +1,135 @@
+use ash::vk::BufferUsageFlags;
+
+use super::{buffer::Buffer, device::Device, vertex::Vertex};
+
+/// Indexed geometry living in device local memory.
+pub struct Mesh {
+    pub vertex_buffer: Buffer,
+    pub index_buffer: Buffer,
+    pub index_count: u32,
+}
+
+impl Mesh {
+    /// Panics if `vertices` or `indices` is empty.
+    pub fn new(device: &Device, vertices: &[Vertex], indices: &[u32]) -> Self {
+        assert!(
+            !vertices.is_empty() && !indices.is_empty(),
+            "A mesh needs at least one vertex and index"
+        );
+        let vertex_buffer = Buffer::new_device_local(
+            device,
+            BufferUsageFlags::VERTEX_BUFFER,
+            bytemuck::cast_slice(vertices),
+        );
+        let index_buffer = Buffer::new_device_local(
+            device,
+            BufferUsageFlags::INDEX_BUFFER,
+            bytemuck::cast_slice(indices),
+        );
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            index_count: indices.len() as u32,
+        }
+    }
+}
+
+/// Outward normal and the two in-plane axes of every cube face, with `u x v = normal` so the
+/// corners below wind counter-clockwise seen from outside.
+const CUBE_FACES: [([f32; 3], [f32; 3], [f32; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+    ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+    ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+];
+
+/// Unit cube centered at the origin: 24 vertices with per-face normals and 36 indices.
+pub fn cube() -> (Vec<Vertex>, Vec<u32>) {
+    cube_with_colors([[1.0; 4]; 6])
+}
+
+/// Like `cube`, with one color per face in the order +X, -X, +Y, -Y, +Z, -Z.
+pub fn cube_with_colors(face_colors: [[f32; 4]; 6]) -> (Vec<Vertex>, Vec<u32>) {
+    let corners = [
+        (-0.5, -0.5, [0.0, 1.0]),
+        (0.5, -0.5, [1.0, 1.0]),
+        (0.5, 0.5, [1.0, 0.0]),
+        (-0.5, 0.5, [0.0, 0.0]),
+    ];
+    let mut vertices = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+
+    for ((normal, u, v), color) in CUBE_FACES.iter().zip(face_colors) {
+        let base = vertices.len() as u32;
+        for (su, sv, uv) in corners {
+            let pos = [0, 1, 2].map(|i| normal[i] * 0.5 + u[i] * su + v[i] * sv);
+            vertices.push(Vertex {
+                pos,
+                color,
+                uv,
+                normal: *normal,
+            });
+        }
+        indices.extend([0, 1, 2, 2, 3, 0].map(|i| base + i));
+    }
+
+    (vertices, indices)
+}
+
+/// Line list of a ground grid on the XZ plane spanning `-half_extent..=half_extent` with one line
+/// per unit, and the axes through the origin in red (X), green (Y) and blue (Z).
+pub fn gizmo_lines(half_extent: u32) -> Vec<Vertex> {
+    let extent = half_extent as f32;
+    let line = |start: [f32; 3], end: [f32; 3], color: [f32; 4]| {
+        [start, end].map(|pos| Vertex {
+            pos,
+            color,
+            ..Vertex::default()
+        })
+    };
+    let mut vertices = Vec::new();
+    let grid_color = [0.4, 0.4, 0.4, 1.0];
+    for i in -(half_extent as i32)..=half_extent as i32 {
+        // The axes take the place of the grid lines through the origin.
+        if i == 0 {
+            continue;
+        }
+        let i = i as f32;
+        vertices.extend(line([i, 0.0, -extent], [i, 0.0, extent], grid_color));
+        vertices.extend(line([-extent, 0.0, i], [extent, 0.0, i], grid_color));
+    }
+    vertices.extend(line(
+        [-extent, 0.0, 0.0],
+        [extent, 0.0, 0.0],
+        [1.0, 0.0, 0.0, 1.0],
+    ));
+    vertices.extend(line(
+        [0.0, 0.0, 0.0],
+        [0.0, extent, 0.0],
+        [0.0, 1.0, 0.0, 1.0],
+    ));
+    vertices.extend(line(
+        [0.0, 0.0, -extent],
+        [0.0, 0.0, extent],
+        [0.0, 0.0, 1.0, 1.0],
+    ));
+    vertices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cube_has_four_vertices_and_two_triangles_per_face() {
+        let (vertices, indices) = cube();
+        assert_eq!(vertices.len(), 24);
+        assert_eq!(indices.len(), 36);
+        assert!(indices
+            .iter()
+            .all(|&index| (index as usize) < vertices.len()));
+    }
+}
@@ -1,154 +1,1910 @@
+use std::{
+    mem::size_of,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
 use ash::{
+    extensions::ext::ExtendedDynamicState,
+    prelude::VkResult,
     vk::{
-        ClearValue, CommandBuffer, CommandBufferBeginInfo, CommandBufferResetFlags, Fence,
-        FenceCreateFlags, FenceCreateInfo, PipelineBindPoint, PipelineStageFlags, PresentInfoKHR,
-        RenderPassBeginInfo, Semaphore, SemaphoreCreateInfo, SubmitInfo, SubpassContents,
+        AccessFlags, AttachmentLoadOp, AttachmentStoreOp, BufferUsageFlags, ClearColorValue,
+        ClearValue, ColorSpaceKHR, CommandBuffer, CommandBufferBeginInfo,
+        CommandBufferInheritanceInfo, CommandBufferLevel, CommandBufferResetFlags,
+        CommandBufferUsageFlags, DependencyFlags, DescriptorBufferInfo, DescriptorImageInfo,
+        DescriptorPoolSize, DescriptorSet, DescriptorSetLayout, DescriptorType, ExtMemoryBudgetFn,
+        Extent2D, Fence, FenceCreateFlags, FenceCreateInfo, Format, Framebuffer, HdrMetadataEXT,
+        ImageLayout, IndexType, MemoryBarrier, MemoryPropertyFlags, PhysicalDeviceLimits, Pipeline,
+        PipelineBindPoint, PipelineLayout, PipelineStageFlags, PresentInfoKHR, PresentModeKHR,
+        PrimitiveTopology, QueryPipelineStatisticFlags, QueueFamilyProperties, Rect2D,
+        RenderPassBeginInfo, RenderingAttachmentInfo, RenderingInfo, SampleCountFlags, Semaphore,
+        SemaphoreCreateInfo, ShaderStageFlags, SubmitInfo, SubpassContents, SurfaceFormatKHR,
+        SurfaceTransformFlagsKHR, Viewport, WriteDescriptorSet, WHOLE_SIZE,
     },
     Entry,
 };
-use winit::window::Window;
+use bytemuck::Pod;
+use glam::Mat4;
+use log::{error, warn};
+#[cfg(feature = "egui")]
+use winit::event::WindowEvent;
+use winit::window::{Window, WindowId};
+
+pub use self::{
+    builder::RendererBuilder,
+    config::RendererConfig,
+    error::RendererError,
+    memory::{AllocationStats, HeapBudget},
+    pipeline_graphics::{DepthBias, FaceCulling},
+    sampler::SamplerBuilder,
+    timing::{BenchReport, FrameTimeStats},
+    utils::{debug::ValidationMessage, features::DeviceFeatureSet},
+    vertex::Vertex,
+};
 
 use self::{
-    command_pool::CommandPool, device::Device, instance::Instance, physical_device::PhysicalDevice,
-    pipeline_graphics::GraphicsPipeline, surface::Surface, swapchain::SwapChain,
+    bindless::BindlessTextures,
+    buffer::Buffer,
+    command_pool::CommandPool,
+    constants::{
+        DEPTH_STENCIL_CLEAR_VALUE, FENCE_TIMEOUT, GIZMO_GRID_HALF_EXTENT, HEADLESS_COLOR_FORMAT,
+        MAX_OBJECTS, OBJECT_DATA_SIZE, PUSH_CONSTANT_SIZE,
+    },
+    deferred::{DeferredLighting, LIGHTING_SUBPASS},
+    deletion_queue::DeletionQueue,
+    descriptor::{DescriptorAllocator, DescriptorPool},
+    device::Device,
+    instance::Instance,
+    mesh::Mesh,
+    offscreen::OffscreenTarget,
+    physical_device::{GpuCandidate, PhysicalDevice},
+    pipeline_compute::ComputePass,
+    pipeline_graphics::{GraphicsPipeline, PipelineOptions, Tessellation},
+    pipeline_primitive::{PrimitiveBatch, PrimitiveDraw, PrimitivePipeline},
+    post_process::PostProcess,
+    query::{OcclusionQuery, PipelineStats, PipelineStatsQuery},
+    render_pass::RenderPass,
+    sampler::Sampler,
+    submit::{Submission, SubmitBatch},
+    surface::Surface,
+    swapchain::SwapChain,
+    texture::Texture,
+    timeline::TimelineSemaphore,
+    timing::{FrameLimiter, FrameTimer, GpuTimer},
     utils::debug::DebugMessenger,
+    vertex::InstanceData,
+    window_target::WindowTarget,
 };
 
+#[cfg(feature = "egui")]
+use self::ui::UiRenderer;
+
+mod bindless;
+pub mod blend;
+mod buffer;
+mod builder;
 mod command_pool;
+mod config;
 mod constants;
+mod deferred;
+mod deletion_queue;
+mod descriptor;
 mod device;
+mod error;
+mod image;
 mod instance;
+mod memory;
+pub mod mesh;
+mod offscreen;
 mod physical_device;
+mod pipeline_compute;
 mod pipeline_graphics;
+mod pipeline_primitive;
+pub mod post_process;
+mod query;
+pub mod reflect;
+mod render_pass;
+mod render_target;
+mod sampler;
+pub mod scene;
 mod shader_module;
+pub mod stencil;
+pub mod submit;
 mod surface;
 mod swapchain;
+mod texture;
+mod timeline;
+mod timing;
+#[cfg(feature = "egui")]
+mod ui;
 mod utils;
+mod vertex;
+pub mod voxel;
+mod window_target;
+
+const TRIANGLE_VERTICES: [Vertex; 3] = [
+    Vertex {
+        pos: [0.0, -0.5, 0.0],
+        color: [1.0, 0.0, 0.0, 1.0],
+        uv: [0.5, 0.0],
+        normal: [0.0, 0.0, -1.0],
+    },
+    Vertex {
+        pos: [0.5, 0.5, 0.0],
+        color: [0.0, 1.0, 0.0, 1.0],
+        uv: [1.0, 1.0],
+        normal: [0.0, 0.0, -1.0],
+    },
+    Vertex {
+        pos: [-0.5, 0.5, 0.0],
+        color: [0.0, 0.0, 1.0, 1.0],
+        uv: [0.0, 1.0],
+        normal: [0.0, 0.0, -1.0],
+    },
+];
+const TRIANGLE_INDICES: [u32; 3] = [0, 1, 2];
 
+/// Logs through the `log` facade only and never installs a logger, that's up to the application.
+/// Without one the messages are dropped.
 pub struct Renderer {
     // SYNC
     image_available_smph: Semaphore,
     render_finished_smph: Semaphore,
     in_flight_fence: Fence,
+    /// Replaces `in_flight_fence` for frame completion when enabled and supported.
+    frame_timeline: Option<TimelineSemaphore>,
+    compute_pass: Option<ComputePass>,
+    post_process: Option<PostProcess>,
+    /// Only created for windowed renderers without MSAA.
+    #[cfg(feature = "egui")]
+    ui: Option<UiRenderer>,
+    /// Group counts of compute dispatches recorded at the start of the next frame.
+    pending_dispatches: Vec<[u32; 3]>,
+    mesh: Mesh,
+    instance_buffer: Buffer,
+    instance_count: u32,
+    texture: Texture,
+    sampler: Sampler,
+    /// Set with `set_texture_sampler`, `sampler` is used for the texture otherwise.
+    texture_sampler: Option<Sampler>,
+    descriptor_set: DescriptorSet,
+    descriptor_pool: DescriptorPool,
+    /// Reset at the start of every frame, see `allocate_transient_descriptor_set`.
+    transient_descriptors: DescriptorAllocator,
+    /// Slot 0 holds `texture`, followed by `bindless_textures`.
+    bindless: Option<BindlessTextures>,
+    bindless_textures: Vec<Texture>,
+    /// Holds `view_projection`, rewritten at the start of every frame.
+    uniform_buffer: Buffer,
+    /// Holds `object_transforms`, `object_stride` bytes apart.
+    object_buffer: Buffer,
+    object_stride: u32,
+    object_transforms: Vec<Mat4>,
+    /// Bindless texture index of each object, 0 for objects without one.
+    object_textures: Vec<u32>,
+    /// Cull mode and front face of each object, applied when the pipeline has them dynamic.
+    object_face_culling: Vec<FaceCulling>,
+    /// Set with `set_depth_bias`, applied when the pipeline has it dynamic.
+    depth_bias: DepthBias,
+    /// Transform indices queued with `draw_object` for the next frame.
+    object_draws: Vec<u32>,
+    view_projection: Mat4,
+    /// Set with `set_hdr_metadata`, reapplied to new swapchains.
+    hdr_metadata: Option<HdrMetadataEXT>,
+    clear_color: [f32; 4],
+    push_data: [u8; PUSH_CONSTANT_SIZE as usize],
+    gpu_timer: Option<GpuTimer>,
+    pipeline_stats: Option<PipelineStatsQuery>,
+    frame_timer: FrameTimer,
+    frame_limiter: Option<FrameLimiter>,
     command_buffer: CommandBuffer,
+    /// Set by `submit_with_next_frame`, batched into the frame's `queue_submit`.
+    submissions: Vec<Submission>,
+    /// Swapchain image the last frame was rendered into, 0 for the offscreen image. `None` until a
+    /// frame has been drawn into the current target.
+    last_image_index: Option<u32>,
+    /// Set by `capture_frame`, saved once the next frame finished.
+    capture_path: Option<PathBuf>,
+    /// Staging buffer the frame being drawn copies its swapchain image into for `capture_path`.
+    capture_buffer: Option<Buffer>,
     command_pool: CommandPool,
+    /// One pool and secondary command buffer per recording thread.
+    recording_pools: Vec<(CommandPool, CommandBuffer)>,
+    /// `None` with dynamic rendering or deferred shading, which the debug primitives don't support.
+    line_pipeline: Option<PrimitivePipeline>,
+    point_pipeline: Option<PrimitivePipeline>,
+    /// Lighting subpass of the main render pass with `RendererConfig::deferred`.
+    deferred_lighting: Option<DeferredLighting>,
+    /// Segment endpoints set by `draw_lines`.
+    lines: PrimitiveBatch,
+    /// Set by `set_points`.
+    points: PrimitiveBatch,
+    /// Grid and axes while `set_show_gizmos` is on, empty otherwise.
+    gizmos: PrimitiveBatch,
     graphics_pipeline: GraphicsPipeline,
-    swap_chain: SwapChain,
+    /// Main render pass, shared by the pipelines drawing the scene and the framebuffers.
+    render_pass: RenderPass,
+    swap_chain: Option<SwapChain>,
+    offscreen: Option<OffscreenTarget>,
+    /// Windows added with `add_window`.
+    windows: Vec<WindowTarget>,
+    /// Replaced meshes and buffers waiting for the frames using them to finish.
+    deletion_queue: DeletionQueue,
     device: Device,
-    surface: Surface,
+    /// Set once a Vulkan call reported `ERROR_DEVICE_LOST`, cleared by `recreate_device`.
+    device_lost: bool,
+    /// Acquire or present reported the swapchain as suboptimal or out of date, see
+    /// `swapchain_suboptimal`.
+    swapchain_suboptimal: bool,
+    config: RendererConfig,
+    surface: Option<Surface>,
     debug_messenger: Option<DebugMessenger>,
     instance: Instance,
     entry: Entry,
 }
 
 impl Renderer {
-    pub fn new(window: &Window) -> Self {
+    pub fn builder() -> RendererBuilder {
+        RendererBuilder::new()
+    }
+
+    pub fn new(window: &Window, config: RendererConfig) -> Result<Self, RendererError> {
+        let size = window.inner_size();
+        Renderer::create(
+            Some(window),
+            Extent2D::builder()
+                .width(size.width)
+                .height(size.height)
+                .build(),
+            config,
+        )
+    }
+
+    /// Lists the available GPUs as rated for `config`, for example to let the user pick one and pass
+    /// its index as `RendererConfig::gpu_index`. Without a window presentation support isn't checked.
+    pub fn enumerate_gpus(
+        window: Option<&Window>,
+        config: &RendererConfig,
+    ) -> Result<Vec<GpuCandidate>, RendererError> {
+        let entry = Entry::linked();
+        let instance = Instance::new(&entry, window, config)?;
+        let surface = window
+            .map(|window| Surface::new(&entry, &instance, window))
+            .transpose()?;
+        PhysicalDevice::enumerate(&instance, surface.as_ref(), config)
+    }
+
+    /// Renders into an offscreen image of the given size instead of a window. Read the result with `read_pixels`.
+    pub fn new_headless(
+        width: u32,
+        height: u32,
+        config: RendererConfig,
+    ) -> Result<Self, RendererError> {
+        Renderer::create(
+            None,
+            Extent2D::builder().width(width).height(height).build(),
+            config,
+        )
+    }
+
+    /// `extent` is only used for the offscreen image when there's no window.
+    fn create(
+        window: Option<&Window>,
+        extent: Extent2D,
+        config: RendererConfig,
+    ) -> Result<Self, RendererError> {
         let entry = Entry::linked();
-        let instance = Instance::new(&entry, window);
+        let instance = Instance::new(&entry, window, &config)?;
 
         let mut debug_messenger = None;
-        if cfg!(debug_assertions) && instance.has_extension_debug_utils() {
-            debug_messenger = Some(DebugMessenger::new(&entry, &instance.inner));
+        if config.validation && instance.has_extension_debug_utils() {
+            debug_messenger = Some(DebugMessenger::new(
+                &entry,
+                &instance.inner,
+                config.validation_severity,
+            ));
         }
 
-        let surface = Surface::new(&entry, &instance, window);
-        let physical_device = PhysicalDevice::pick(&instance, &surface);
-        let device = Device::new(&instance.inner, physical_device);
-        let mut swap_chain = SwapChain::new(&instance, window, &surface, &device);
-        let graphics_pipeline = GraphicsPipeline::new(&device, &swap_chain);
-        swap_chain.create_framebuffers(&device, &graphics_pipeline);
-        let mut command_pool = CommandPool::new(&device);
-        let command_buffer = command_pool.allocate();
-
-        let smph_info = SemaphoreCreateInfo::builder();
-        let fence_info = FenceCreateInfo::builder().flags(FenceCreateFlags::SIGNALED);
+        let surface = window
+            .map(|window| Surface::new(&entry, &instance, window))
+            .transpose()?;
+        let resources = DeviceResources::new(&instance, surface.as_ref(), extent, &config)?;
 
-        let (image_available_smph, render_finished_smph, in_flight_fence) = unsafe {
-            (
-                device.inner.create_semaphore(&smph_info, None).unwrap(),
-                device.inner.create_semaphore(&smph_info, None).unwrap(),
-                device.inner.create_fence(&fence_info, None).unwrap(),
-            )
+        #[cfg(feature = "egui")]
+        let ui = match (window, &resources.swap_chain) {
+            (Some(_), Some(_))
+                if resources.graphics_pipeline.samples != SampleCountFlags::TYPE_1
+                    || resources.graphics_pipeline.uses_dynamic_rendering() =>
+            {
+                warn!(
+                    "The egui overlay is not supported with MSAA or dynamic rendering, skipping it"
+                );
+                None
+            }
+            (Some(window), Some(swap_chain)) => Some(UiRenderer::new(
+                &resources.device,
+                window,
+                swap_chain.surface_format.format,
+                resources.graphics_pipeline.depth_format,
+                ImageLayout::PRESENT_SRC_KHR,
+            )),
+            _ => None,
         };
 
-        Renderer {
+        let depth_bias = resources
+            .graphics_pipeline
+            .options
+            .depth_bias
+            .unwrap_or_default();
+        let renderer = Renderer {
             entry,
             instance,
             debug_messenger,
             surface,
-            device,
-            swap_chain,
-            graphics_pipeline,
-            command_pool,
-            command_buffer,
-            image_available_smph,
-            render_finished_smph,
-            in_flight_fence,
+            device: resources.device,
+            swap_chain: resources.swap_chain,
+            offscreen: resources.offscreen,
+            windows: Vec::new(),
+            deletion_queue: DeletionQueue::default(),
+            line_pipeline: resources.line_pipeline,
+            point_pipeline: resources.point_pipeline,
+            deferred_lighting: resources.deferred_lighting,
+            lines: PrimitiveBatch::default(),
+            gizmos: PrimitiveBatch::default(),
+            points: PrimitiveBatch::default(),
+            graphics_pipeline: resources.graphics_pipeline,
+            render_pass: resources.render_pass,
+            command_pool: resources.command_pool,
+            command_buffer: resources.command_buffer,
+            recording_pools: resources.recording_pools,
+            submissions: Vec::new(),
+            last_image_index: None,
+            capture_path: None,
+            capture_buffer: None,
+            compute_pass: None,
+            post_process: None,
+            #[cfg(feature = "egui")]
+            ui,
+            pending_dispatches: Vec::new(),
+            mesh: resources.mesh,
+            instance_buffer: resources.instance_buffer,
+            instance_count: 1,
+            texture: resources.texture,
+            sampler: resources.sampler,
+            texture_sampler: None,
+            descriptor_set: resources.descriptor_set,
+            descriptor_pool: resources.descriptor_pool,
+            transient_descriptors: resources.transient_descriptors,
+            bindless: resources.bindless,
+            bindless_textures: Vec::new(),
+            uniform_buffer: resources.uniform_buffer,
+            object_buffer: resources.object_buffer,
+            object_stride: resources.object_stride,
+            object_transforms: vec![Mat4::IDENTITY],
+            object_textures: Vec::new(),
+            object_face_culling: Vec::new(),
+            depth_bias,
+            object_draws: Vec::new(),
+            view_projection: Mat4::IDENTITY,
+            hdr_metadata: None,
+            clear_color: config.clear_color,
+            push_data: [0; PUSH_CONSTANT_SIZE as usize],
+            gpu_timer: resources.gpu_timer,
+            pipeline_stats: resources.pipeline_stats,
+            frame_timer: FrameTimer::new(),
+            frame_limiter: config.max_fps.map(FrameLimiter::new),
+            image_available_smph: resources.image_available_smph,
+            render_finished_smph: resources.render_finished_smph,
+            in_flight_fence: resources.in_flight_fence,
+            frame_timeline: resources.frame_timeline,
+            device_lost: false,
+            swapchain_suboptimal: false,
+            config,
+        };
+        renderer.set_debug_names();
+        Ok(renderer)
+    }
+
+    fn set_debug_names(&self) {
+        let debug_messenger = match &self.debug_messenger {
+            Some(debug_messenger) => debug_messenger,
+            None => return,
+        };
+        let device = &self.device.inner;
+
+        if let Some(swap_chain) = &self.swap_chain {
+            for (i, image) in swap_chain.images.iter().enumerate() {
+                debug_messenger.set_object_name(device, *image, &format!("Swapchain Image {}", i));
+            }
+        }
+        if let Some(offscreen) = &self.offscreen {
+            debug_messenger.set_object_name(device, offscreen.image.inner, "Offscreen Image");
         }
+        if self.scene_pipeline_ready() {
+            debug_messenger.set_object_name(
+                device,
+                self.graphics_pipeline.inner,
+                "Graphics Pipeline",
+            );
+        }
+        debug_messenger.set_object_name(device, self.render_pass.inner, "Main Render Pass");
+        debug_messenger.set_object_name(device, self.command_buffer, "Frame Command Buffer");
+    }
+
+    /// Fails with `RendererError::FenceTimeout` if the previous frame didn't finish within
+    /// `FENCE_TIMEOUT`, which usually means the GPU hung.
+    pub fn draw_frame(&mut self) -> Result<(), RendererError> {
+        let result = self.render_frame();
+        self.object_draws.clear();
+        if let Some(frame_limiter) = &mut self.frame_limiter {
+            frame_limiter.wait();
+        }
+        result
     }
 
-    pub fn draw_frame(&mut self) {
+    fn render_frame(&mut self) -> Result<(), RendererError> {
+        self.frame_timer.tick();
+        self.graphics_pipeline.poll();
         unsafe {
-            self.device
-                .inner
-                .wait_for_fences(&[self.in_flight_fence], true, u64::MAX)
-                .unwrap();
-            self.device
-                .inner
-                .reset_fences(&[self.in_flight_fence])
-                .unwrap();
-            let index = self
+            match self.wait_for_previous_frame(FENCE_TIMEOUT) {
+                Ok(()) => {}
+                Err(ash::vk::Result::TIMEOUT) => {
+                    error!(
+                        "Timed out after {}ms waiting for the previous frame",
+                        FENCE_TIMEOUT / 1_000_000
+                    );
+                    return Err(RendererError::FenceTimeout);
+                }
+                Err(result) => return Err(self.device_error(result)),
+            }
+            // The uniform buffer may still be read by the other windows' frames.
+            for window in &self.windows {
+                match self.device.inner.wait_for_fences(
+                    &[window.in_flight_fence],
+                    true,
+                    FENCE_TIMEOUT,
+                ) {
+                    Ok(()) => {}
+                    Err(ash::vk::Result::TIMEOUT) => return Err(RendererError::FenceTimeout),
+                    Err(result) => return Err(self.device_error(result)),
+                }
+            }
+            self.deletion_queue.flush();
+            if let Some(gpu_timer) = &mut self.gpu_timer {
+                gpu_timer.collect();
+            }
+            if let Some(pipeline_stats) = &mut self.pipeline_stats {
+                pipeline_stats.collect();
+            }
+            let pre_transform = self
                 .swap_chain
-                .loader
-                .acquire_next_image(
-                    self.swap_chain.inner,
-                    u64::MAX,
-                    self.image_available_smph,
-                    Fence::null(),
-                )
-                .unwrap()
-                .0;
-            self.device
+                .as_ref()
+                .map_or(Mat4::IDENTITY, SwapChain::pre_transform_matrix);
+            self.uniform_buffer
+                .map_write(&[pre_transform * self.view_projection]);
+            self.upload_object_transforms();
+            self.transient_descriptors.reset();
+            self.lines.upload(&self.device);
+            self.points.upload(&self.device);
+            self.gizmos.upload(&self.device);
+            let swap_chain = match &self.swap_chain {
+                Some(swap_chain) => swap_chain,
+                None if self.offscreen.is_some() => {
+                    self.draw_headless()?;
+                    return self.finish_capture();
+                }
+                // A failed `recreate_surface` left the window without one.
+                None => return Err(RendererError::SurfaceLost),
+            };
+            let index = match swap_chain.loader.acquire_next_image(
+                swap_chain.inner,
+                u64::MAX,
+                self.image_available_smph,
+                Fence::null(),
+            ) {
+                Ok((index, suboptimal)) => {
+                    self.swapchain_suboptimal |= suboptimal;
+                    index
+                }
+                // Usually a resize the window event hasn't reported yet, the frame is skipped.
+                Err(ash::vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                    self.swapchain_suboptimal = true;
+                    return Ok(());
+                }
+                Err(result) => return Err(self.device_error(result)),
+            };
+            if self.capture_path.is_some() && self.capture_bgra().is_ok() {
+                let extent = swap_chain.extent;
+                self.capture_buffer = Some(Buffer::new(
+                    &self.device,
+                    extent.width as u64 * extent.height as u64 * 4,
+                    BufferUsageFlags::TRANSFER_DST,
+                    MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+                ));
+            }
+            if let Err(result) = self
+                .device
                 .inner
                 .reset_command_buffer(self.command_buffer, CommandBufferResetFlags::empty())
-                .unwrap();
-            self.record_commandbuffer(index as usize);
+            {
+                return Err(self.device_error(result));
+            }
+            self.record_commandbuffer(index as usize)?;
 
             let wait_semaphores = [self.image_available_smph];
             let wait_stages = [PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-            let command_buffers = [self.command_buffer];
             let signal_semaphores = [self.render_finished_smph];
-            let submit_info = SubmitInfo::builder()
+            if let Err(result) =
+                self.submit_frame(&wait_semaphores, &wait_stages, &signal_semaphores)
+            {
+                return Err(self.device_error(result));
+            }
+
+            let swap_chain = self.swap_chain.as_ref().unwrap();
+            let present_wait_semaphores = match swap_chain.acquire_for_present(
+                &self.device,
+                index as usize,
+                self.render_finished_smph,
+            ) {
+                Ok(semaphore) => [semaphore],
+                Err(result) => return Err(self.device_error(result)),
+            };
+            let indices = [index];
+            let swapchains = [swap_chain.inner];
+            let present_info = PresentInfoKHR::builder()
+                .wait_semaphores(&present_wait_semaphores)
+                .swapchains(&swapchains)
+                .image_indices(&indices);
+
+            match swap_chain
+                .loader
+                .queue_present(self.device.present_queue, &present_info)
+            {
+                Ok(suboptimal) => self.swapchain_suboptimal |= suboptimal,
+                Err(ash::vk::Result::ERROR_OUT_OF_DATE_KHR) => self.swapchain_suboptimal = true,
+                Err(result) => return Err(self.device_error(result)),
+            }
+            self.last_image_index = Some(index);
+        }
+        self.finish_capture()
+    }
+
+    /// Adds another window sharing this renderer's device, drawn with `draw_window`. Resizes are
+    /// handled by `recreate_swapchain` like for the main window.
+    ///
+    /// Other windows show the scene with the same camera, but without lines, points, gizmos,
+    /// post-processing or the egui overlay. Fails if the renderer is headless.
+    pub fn add_window(&mut self, window: &Window) -> Result<(), RendererError> {
+        if self.surface.is_none() {
+            return Err(RendererError::UnsupportedSurface);
+        }
+        let target = WindowTarget::new(
+            &self.entry,
+            &self.instance,
+            &self.device,
+            window,
+            &self.graphics_pipeline,
+            &self.config,
+        )?;
+        self.windows.push(target);
+        Ok(())
+    }
+
+    /// Has to be called before the window is destroyed.
+    pub fn remove_window(&mut self, window_id: WindowId) {
+        unsafe {
+            self.device.inner.device_wait_idle().unwrap();
+        }
+        self.windows.retain(|target| target.window_id != window_id);
+    }
+
+    /// Draws a frame into a window added with `add_window`. `draw_frame` draws the main window.
+    /// Fails with `UnknownWindow` for other ids, e.g. of a removed window. Check
+    /// `window_swapchain_suboptimal` afterwards like `swapchain_suboptimal` for the main window.
+    pub fn draw_window(&mut self, window_id: WindowId) -> Result<(), RendererError> {
+        let window_index = self
+            .windows
+            .iter()
+            .position(|target| target.window_id == window_id)
+            .ok_or(RendererError::UnknownWindow)?;
+        let target = &self.windows[window_index];
+        // A failed `recreate_swapchain` left the window without one.
+        let swap_chain = target
+            .swap_chain
+            .as_ref()
+            .ok_or(RendererError::SurfaceLost)?;
+        unsafe {
+            match self
+                .device
+                .inner
+                .wait_for_fences(&[target.in_flight_fence], true, FENCE_TIMEOUT)
+            {
+                Ok(()) => {}
+                Err(ash::vk::Result::TIMEOUT) => return Err(RendererError::FenceTimeout),
+                Err(result) => return Err(self.device_error(result)),
+            }
+            let index = match swap_chain.loader.acquire_next_image(
+                swap_chain.inner,
+                u64::MAX,
+                target.image_available_smph,
+                Fence::null(),
+            ) {
+                Ok((index, suboptimal)) => {
+                    self.windows[window_index].swapchain_suboptimal |= suboptimal;
+                    index
+                }
+                Err(ash::vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                    self.windows[window_index].swapchain_suboptimal = true;
+                    return Ok(());
+                }
+                Err(result) => return Err(self.device_error(result)),
+            };
+            let target = &self.windows[window_index];
+            let swap_chain = target.swap_chain.as_ref().unwrap();
+            self.device
+                .inner
+                .reset_fences(&[target.in_flight_fence])
+                .unwrap();
+            if let Err(result) = self
+                .device
+                .inner
+                .reset_command_buffer(target.command_buffer, CommandBufferResetFlags::empty())
+            {
+                return Err(self.device_error(result));
+            }
+            self.record_window(target, index as usize);
+
+            let wait_semaphores = [target.image_available_smph];
+            let wait_stages = [PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+            let signal_semaphores = [target.render_finished_smph];
+            let command_buffers = [target.command_buffer];
+            let submit_infos = [SubmitInfo::builder()
                 .wait_semaphores(&wait_semaphores)
                 .wait_dst_stage_mask(&wait_stages)
                 .command_buffers(&command_buffers)
-                .signal_semaphores(&signal_semaphores);
-
-            let submit_infos = [submit_info.build()];
-            self.device
+                .signal_semaphores(&signal_semaphores)
+                .build()];
+            let indices = [index];
+            let swapchains = [swap_chain.inner];
+            let result = self
+                .device
                 .inner
                 .queue_submit(
                     self.device.graphics_queue,
                     &submit_infos,
-                    self.in_flight_fence,
+                    target.in_flight_fence,
                 )
-                .unwrap();
+                .and_then(|()| {
+                    swap_chain.acquire_for_present(
+                        &self.device,
+                        index as usize,
+                        target.render_finished_smph,
+                    )
+                })
+                .and_then(|semaphore| {
+                    let present_wait_semaphores = [semaphore];
+                    let present_info = PresentInfoKHR::builder()
+                        .wait_semaphores(&present_wait_semaphores)
+                        .swapchains(&swapchains)
+                        .image_indices(&indices);
+                    match swap_chain
+                        .loader
+                        .queue_present(self.device.present_queue, &present_info)
+                    {
+                        Err(ash::vk::Result::ERROR_OUT_OF_DATE_KHR) => Ok(true),
+                        result => result,
+                    }
+                });
+            match result {
+                Ok(suboptimal) => self.windows[window_index].swapchain_suboptimal |= suboptimal,
+                Err(result) => return Err(self.device_error(result)),
+            }
+        }
+        Ok(())
+    }
 
-            let indices = [index];
-            let swapchains = [self.swap_chain.inner];
-            let present_info = PresentInfoKHR::builder()
-                .wait_semaphores(&signal_semaphores)
-                .swapchains(&swapchains)
-                .image_indices(&indices);
+    /// Like `swapchain_suboptimal` for a window added with `add_window`, false for unknown ids.
+    pub fn window_swapchain_suboptimal(&self, window_id: WindowId) -> bool {
+        self.windows
+            .iter()
+            .any(|target| target.window_id == window_id && target.swapchain_suboptimal)
+    }
 
-            self.swap_chain
-                .loader
-                .queue_present(self.device.present_queue, &present_info)
+    /// Records the scene into `target`'s command buffer with its own render pass.
+    fn record_window(&self, target: &WindowTarget, image_index: usize) {
+        let device = &self.device.inner;
+        let pipeline = &target.graphics_pipeline;
+        let swap_chain = target.swap_chain.as_ref().unwrap();
+        let clear_color = ClearValue {
+            color: ClearColorValue {
+                float32: self.clear_color,
+            },
+        };
+        let mut clear_values = vec![clear_color];
+        if pipeline.samples != SampleCountFlags::TYPE_1 {
+            clear_values.push(clear_color);
+        }
+        clear_values.push(DEPTH_STENCIL_CLEAR_VALUE);
+        let render_pass_begin_info = RenderPassBeginInfo::builder()
+            .render_pass(pipeline.render_pass)
+            .framebuffer(swap_chain.framebuffers[image_index])
+            .render_area(Rect2D::builder().extent(swap_chain.extent).build())
+            .clear_values(&clear_values);
+        let scene_draw = SceneDraw {
+            pipeline: pipeline.inner,
+            pipeline_layout: pipeline.pipeline_layout,
+            bindless_set: None,
+            ..self.scene_draw()
+        };
+        unsafe {
+            device
+                .begin_command_buffer(target.command_buffer, &CommandBufferBeginInfo::builder())
                 .unwrap();
+            swap_chain.record_initial_layout(
+                device,
+                target.command_buffer,
+                image_index,
+                pipeline.options.color_initial_layout,
+            );
+            device.cmd_begin_render_pass(
+                target.command_buffer,
+                &render_pass_begin_info,
+                SubpassContents::INLINE,
+            );
+            scene_draw.record(
+                device,
+                target.command_buffer,
+                swap_chain.extent,
+                self.scene_objects(),
+                0..self.instance_count,
+            );
+            device.cmd_end_render_pass(target.command_buffer);
+            swap_chain.record_release(device, target.command_buffer, image_index);
+            device.end_command_buffer(target.command_buffer).unwrap();
+        }
+    }
+
+    /// Converts a failed Vulkan call and remembers if it lost the device.
+    fn device_error(&mut self, result: ash::vk::Result) -> RendererError {
+        if result == ash::vk::Result::ERROR_DEVICE_LOST {
+            error!("Device lost");
+            self.device_lost = true;
+            return RendererError::DeviceLost;
         }
+        if result == ash::vk::Result::ERROR_SURFACE_LOST_KHR {
+            warn!("Surface lost");
+            return RendererError::SurfaceLost;
+        }
+        result.into()
+    }
+
+    /// Submits `submission` to the graphics queue together with the next frame, ahead of its
+    /// command buffer, e.g. a prepass recorded by the caller. Its command buffers have to stay
+    /// valid until that frame has finished.
+    pub fn submit_with_next_frame(&mut self, submission: Submission) {
+        self.submissions.push(submission);
+    }
+
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost
+    }
+
+    /// The swapchain no longer matches the surface, e.g. after a DPI change or a resize that
+    /// made it out of date. The frame is finished normally or skipped, `recreate_swapchain`
+    /// should be called before the next one.
+    pub fn swapchain_suboptimal(&self) -> bool {
+        self.swapchain_suboptimal
+    }
+
+    /// False while `RendererConfig::async_pipelines` compiles the main pipeline. `draw_frame`
+    /// only clears until then.
+    pub fn pipelines_ready(&self) -> bool {
+        self.graphics_pipeline.is_ready()
+    }
+
+    /// The main pipeline can be bound, taken over by `draw_frame` once it's ready.
+    fn scene_pipeline_ready(&self) -> bool {
+        self.graphics_pipeline.inner != ash::vk::Pipeline::null()
+    }
+
+    /// Rebuilds the device and everything created from it, keeping the instance and surface.
+    ///
+    /// Recovery is best effort: the mesh, instances, texture, compute shader and post-processing are
+    /// reset to their defaults and have to be set again by the caller. Windows added with
+    /// `add_window` are removed.
+    pub fn recreate_device(&mut self) -> Result<(), RendererError> {
+        unsafe {
+            // Fails with ERROR_DEVICE_LOST, which is why we're here in the first place.
+            let _ = self.device.inner.device_wait_idle();
+        }
+        self.windows.clear();
+        self.deletion_queue.clear();
+        let (_, extent) = self.render_target(0)?;
+        // The surface can only have one swapchain, and the passes rendering into the old one
+        // are rebuilt below.
+        self.post_process = None;
+        self.capture_buffer = None;
+        #[cfg(feature = "egui")]
+        let ui = self.ui.take();
+        drop(self.swap_chain.take());
+        let resources =
+            DeviceResources::new(&self.instance, self.surface.as_ref(), extent, &self.config)?;
+
+        unsafe {
+            self.device
+                .inner
+                .destroy_semaphore(self.image_available_smph, None);
+            self.device
+                .inner
+                .destroy_semaphore(self.render_finished_smph, None);
+            self.device.inner.destroy_fence(self.in_flight_fence, None);
+        }
+        // Assigned in field order, so everything created from the old device is dropped before it.
+        self.image_available_smph = resources.image_available_smph;
+        self.render_finished_smph = resources.render_finished_smph;
+        self.in_flight_fence = resources.in_flight_fence;
+        self.frame_timeline = resources.frame_timeline;
+        self.compute_pass = None;
+        #[cfg(feature = "egui")]
+        if let (Some(ui), Some(swap_chain)) = (ui, &resources.swap_chain) {
+            self.ui = Some(ui.rebuild(
+                &resources.device,
+                swap_chain.surface_format.format,
+                resources.graphics_pipeline.depth_format,
+                ImageLayout::PRESENT_SRC_KHR,
+            ));
+        }
+        self.pending_dispatches.clear();
+        self.mesh = resources.mesh;
+        self.instance_buffer = resources.instance_buffer;
+        self.instance_count = 1;
+        self.texture = resources.texture;
+        self.sampler = resources.sampler;
+        self.texture_sampler = None;
+        self.descriptor_set = resources.descriptor_set;
+        self.descriptor_pool = resources.descriptor_pool;
+        self.transient_descriptors = resources.transient_descriptors;
+        self.bindless_textures.clear();
+        self.bindless = resources.bindless;
+        self.uniform_buffer = resources.uniform_buffer;
+        self.object_buffer = resources.object_buffer;
+        self.object_stride = resources.object_stride;
+        self.gpu_timer = resources.gpu_timer;
+        self.pipeline_stats = resources.pipeline_stats;
+        self.command_buffer = resources.command_buffer;
+        self.last_image_index = None;
+        self.command_pool = resources.command_pool;
+        self.recording_pools = resources.recording_pools;
+        self.line_pipeline = resources.line_pipeline;
+        self.point_pipeline = resources.point_pipeline;
+        self.deferred_lighting = resources.deferred_lighting;
+        self.lines.buffer = None;
+        self.points.buffer = None;
+        self.gizmos.buffer = None;
+        self.submissions.clear();
+        self.graphics_pipeline = resources.graphics_pipeline;
+        self.render_pass = resources.render_pass;
+        self.swap_chain = resources.swap_chain;
+        self.offscreen = resources.offscreen;
+        self.device = resources.device;
+        self.device_lost = false;
+        self.swapchain_suboptimal = false;
+
+        self.set_debug_names();
+        self.apply_hdr_metadata();
+        Ok(())
+    }
+
+    /// Renders a frame into the offscreen image. No acquire or present, so no semaphores are needed.
+    fn draw_headless(&mut self) -> Result<(), RendererError> {
+        unsafe {
+            if let Err(result) = self
+                .device
+                .inner
+                .reset_command_buffer(self.command_buffer, CommandBufferResetFlags::empty())
+            {
+                return Err(self.device_error(result));
+            }
+            self.record_commandbuffer(0)?;
+            if let Err(result) = self.submit_frame(&[], &[], &[]) {
+                return Err(self.device_error(result));
+            }
+        }
+        self.last_image_index = Some(0);
+        Ok(())
+    }
+
+    /// Waits until the last submitted frame has finished on the GPU.
+    fn wait_for_previous_frame(&self, timeout: u64) -> VkResult<()> {
+        match &self.frame_timeline {
+            Some(frame_timeline) => frame_timeline.wait(timeout),
+            None => unsafe {
+                self.device
+                    .inner
+                    .wait_for_fences(&[self.in_flight_fence], true, timeout)
+            },
+        }
+    }
+
+    /// Submits `command_buffer` after the submissions queued with `submit_with_next_frame` and
+    /// signals the next frame timeline value, or `in_flight_fence`.
+    unsafe fn submit_frame(
+        &mut self,
+        wait_semaphores: &[Semaphore],
+        wait_stages: &[PipelineStageFlags],
+        signal_semaphores: &[Semaphore],
+    ) -> VkResult<()> {
+        let mut frame = Submission {
+            command_buffers: vec![self.command_buffer],
+            wait_semaphores: wait_semaphores.to_vec(),
+            wait_stages: wait_stages.to_vec(),
+            signal_semaphores: signal_semaphores.to_vec(),
+            ..Submission::default()
+        };
+        let fence = match &self.frame_timeline {
+            Some(frame_timeline) => {
+                frame.signal_values = vec![0; frame.signal_semaphores.len()];
+                frame.signal_semaphores.push(frame_timeline.inner);
+                frame.signal_values.push(frame_timeline.next_value());
+                Fence::null()
+            }
+            // Reset only now, a frame that fails to acquire an image leaves the fence signaled.
+            None => {
+                self.device.inner.reset_fences(&[self.in_flight_fence])?;
+                self.in_flight_fence
+            }
+        };
+        let batch = self
+            .submissions
+            .drain(..)
+            .fold(SubmitBatch::new(), SubmitBatch::submission)
+            .submission(frame);
+        batch.submit(&self.device.inner, self.device.graphics_queue, fence)?;
+        if let Some(frame_timeline) = &mut self.frame_timeline {
+            frame_timeline.value = frame_timeline.next_value();
+        }
+        Ok(())
+    }
+
+    /// Copies the offscreen image of the last drawn frame into tightly packed RGBA8 pixels.
+    /// Panics if the renderer isn't headless. Fails with `NoFrameDrawn` before the first frame,
+    /// when the image has no contents yet, and with `FenceTimeout` if that frame doesn't finish.
+    pub fn read_pixels(&mut self) -> Result<Vec<u8>, RendererError> {
+        assert!(
+            self.offscreen.is_some(),
+            "read_pixels requires a headless renderer"
+        );
+        if self.last_image_index.is_none() {
+            return Err(RendererError::NoFrameDrawn);
+        }
+        match self.wait_for_previous_frame(FENCE_TIMEOUT) {
+            Ok(()) => {}
+            Err(ash::vk::Result::TIMEOUT) => return Err(RendererError::FenceTimeout),
+            Err(result) => return Err(self.device_error(result)),
+        }
+        let offscreen = self.offscreen.as_ref().unwrap();
+        let extent = offscreen.extent;
+        let size = extent.width as usize * extent.height as usize * 4;
+        let buffer = Buffer::new(
+            &self.device,
+            size as u64,
+            BufferUsageFlags::TRANSFER_DST,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        );
+        self.device.one_time_submit(|command_buffer| {
+            image::copy_image_to_buffer(
+                &self.device.inner,
+                command_buffer,
+                offscreen.image.inner,
+                buffer.inner,
+                extent,
+            );
+        });
+
+        let mut pixels = buffer.allocation.read();
+        pixels.truncate(size);
+        Ok(pixels)
+    }
+
+    /// Saves the next frame drawn by `draw_frame` as a PNG, once the GPU finished it. Fails right
+    /// away with `UnsupportedCapture` if the surface doesn't allow copying from swapchain images,
+    /// and with `UnsupportedCaptureFormat` for swapchain formats other than 8 bit RGBA or BGRA.
+    /// Errors saving the frame are returned by that `draw_frame`.
+    pub fn capture_frame(&mut self, path: &Path) -> Result<(), RendererError> {
+        self.capture_bgra()?;
+        self.capture_path = Some(path.to_owned());
+        Ok(())
+    }
+
+    /// Whether captured pixels have to be swizzled from BGRA. The offscreen image is RGBA.
+    fn capture_bgra(&self) -> Result<bool, RendererError> {
+        let swap_chain = match &self.swap_chain {
+            Some(swap_chain) => swap_chain,
+            None => return Ok(false),
+        };
+        if !swap_chain.transfer_src {
+            return Err(RendererError::UnsupportedCapture);
+        }
+        match swap_chain.surface_format.format {
+            Format::B8G8R8A8_SRGB | Format::B8G8R8A8_UNORM => Ok(true),
+            Format::R8G8B8A8_SRGB | Format::R8G8B8A8_UNORM => Ok(false),
+            // The readback assumes 4 bytes per pixel, e.g. HDR formats aren't.
+            format => Err(RendererError::UnsupportedCaptureFormat(format)),
+        }
+    }
+
+    /// Saves the frame just drawn if `capture_frame` asked for it. The swapchain image was copied
+    /// by the frame's command buffer, see `SwapChain::record_capture`.
+    fn finish_capture(&mut self) -> Result<(), RendererError> {
+        let path = match self.capture_path.take() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let bgra = self.capture_bgra()?;
+        let (mut pixels, extent) = match (&self.swap_chain, self.capture_buffer.take()) {
+            (Some(swap_chain), Some(buffer)) => {
+                let extent = swap_chain.extent;
+                match self.wait_for_previous_frame(FENCE_TIMEOUT) {
+                    Ok(()) => {}
+                    Err(ash::vk::Result::TIMEOUT) => return Err(RendererError::FenceTimeout),
+                    Err(result) => return Err(self.device_error(result)),
+                }
+                let mut pixels = buffer.allocation.read();
+                pixels.truncate(extent.width as usize * extent.height as usize * 4);
+                (pixels, extent)
+            }
+            // `capture_bgra` succeeded, so the frame recorded the copy.
+            (Some(_), None) => unreachable!(),
+            (None, _) => {
+                let extent = self.offscreen.as_ref().unwrap().extent;
+                (self.read_pixels()?, extent)
+            }
+        };
+        if bgra {
+            utils::pixels::bgra_to_rgba(&mut pixels);
+        }
+        ::image::save_buffer(
+            &path,
+            &pixels,
+            extent.width,
+            extent.height,
+            ::image::ColorType::Rgba8,
+        )?;
+        Ok(())
+    }
+
+    /// Framebuffer and extent to render into for the given swapchain image. Fails with
+    /// `SurfaceLost` while a failed `recreate_surface` left the window without a swapchain.
+    fn render_target(&self, image_index: usize) -> Result<(Framebuffer, Extent2D), RendererError> {
+        match (&self.swap_chain, &self.offscreen) {
+            // There are no swapchain framebuffers with dynamic rendering.
+            (Some(swap_chain), _) => Ok((
+                swap_chain
+                    .framebuffers
+                    .get(image_index)
+                    .copied()
+                    .unwrap_or_else(Framebuffer::null),
+                swap_chain.extent,
+            )),
+            (None, Some(offscreen)) => Ok((offscreen.framebuffer, offscreen.extent)),
+            (None, None) => Err(RendererError::SurfaceLost),
+        }
+    }
+
+    /// Replaces the main window's surface and swapchain after `draw_frame` failed with
+    /// `RendererError::SurfaceLost`. Does nothing while the window is minimized, the next frame
+    /// fails again until it has a size. Fails with `UnsupportedSurface` if the GPU can't present
+    /// to the new surface, which needs `recreate_device` or a new renderer.
+    pub fn recreate_surface(&mut self, window: &Window) -> Result<(), RendererError> {
+        if self.offscreen.is_some() {
+            return Err(RendererError::UnsupportedSurface);
+        }
+        let size = window.inner_size();
+        if size.width == 0 || size.height == 0 {
+            return Ok(());
+        }
+        if let Err(result) = unsafe { self.device.inner.device_wait_idle() } {
+            return Err(self.device_error(result));
+        }
+        // The swapchain has to be destroyed before its surface.
+        self.swap_chain = None;
+        self.surface = None;
+        let surface = Surface::new(&self.entry, &self.instance, window)?;
+        if !surface.is_supported(&self.device.physical_device)? {
+            return Err(RendererError::UnsupportedSurface);
+        }
+        self.surface = Some(surface);
+        self.recreate_swapchain(window)
+    }
+
+    /// Rebuilds the swapchain after the window was resized.
+    ///
+    /// A minimized window has a zero extent, which can't back a swapchain. In that case nothing is
+    /// rebuilt and the caller must skip `draw_frame` until the window is restored and this is called
+    /// again with a nonzero size.
+    ///
+    /// Fails with `SurfaceLost` if the surface went away, or another error if the swapchain
+    /// couldn't be rebuilt. The window is left without a swapchain then, see `recreate_surface`.
+    pub fn recreate_swapchain(&mut self, window: &Window) -> Result<(), RendererError> {
+        if let Some(index) = self
+            .windows
+            .iter()
+            .position(|target| target.window_id == window.id())
+        {
+            if let Err(result) = unsafe { self.device.inner.device_wait_idle() } {
+                return Err(self.device_error(result));
+            }
+            let result = self.windows[index].recreate_swapchain(
+                &self.instance,
+                &self.device,
+                window,
+                &self.config,
+            );
+            return result.map_err(|result| self.device_error(result));
+        }
+        let size = window.inner_size();
+        if size.width == 0 || size.height == 0 {
+            return Ok(());
+        }
+        let window_size = Extent2D::builder()
+            .width(size.width)
+            .height(size.height)
+            .build();
+        let present_mode = self
+            .swap_chain
+            .as_ref()
+            .map(|swap_chain| swap_chain.present_mode);
+        self.rebuild_swapchain(window_size, present_mode)
+    }
+
+    /// Replaces the main window's swapchain, keeping the surface. `present_mode` is used if the
+    /// surface supports it. Leaves the window without a swapchain if that fails.
+    fn rebuild_swapchain(
+        &mut self,
+        window_size: Extent2D,
+        present_mode: Option<PresentModeKHR>,
+    ) -> Result<(), RendererError> {
+        if self.surface.is_none() {
+            return Ok(());
+        }
+        if let Err(result) = unsafe { self.device.inner.device_wait_idle() } {
+            return Err(self.device_error(result));
+        }
+        // The old swapchain has to be destroyed before a new one is created for the same window.
+        drop(self.swap_chain.take());
+        let mut swap_chain = match SwapChain::new(
+            &self.instance,
+            window_size,
+            self.surface.as_ref().unwrap(),
+            &self.device,
+            present_mode,
+            &self.config,
+        ) {
+            Ok(swap_chain) => swap_chain,
+            Err(result) => return Err(self.device_error(result)),
+        };
+        swap_chain.create_framebuffers(&self.device, &self.render_pass);
+        if let Some(deferred_lighting) = &self.deferred_lighting {
+            deferred_lighting.write_gbuffer(swap_chain.gbuffer[0].view, swap_chain.gbuffer[1].view);
+        }
+        self.swap_chain = Some(swap_chain);
+        self.last_image_index = None;
+        self.swapchain_suboptimal = false;
+        self.apply_hdr_metadata();
+
+        // The scene target has to match the new swapchain extent.
+        if let Some(post_process) = self.post_process.take() {
+            self.set_post_process(Some(&post_process.fragment_code));
+        }
+        Ok(())
+    }
+
+    /// Sets the compute shader run by `dispatch`. Its storage buffer at binding 0 is created with
+    /// `storage_buffer_size` bytes and stays alive until the next call.
+    pub fn set_compute_shader(&mut self, code: &[u8], storage_buffer_size: u64) {
+        unsafe {
+            self.device.inner.device_wait_idle().unwrap();
+        }
+        self.pending_dispatches.clear();
+        self.compute_pass = Some(ComputePass::new(&self.device, code, storage_buffer_size));
+    }
+
+    /// Queues a compute dispatch. It runs on the graphics queue before the next frame's render pass,
+    /// which sees its writes. The push data is shared with the graphics shaders.
+    pub fn dispatch(&mut self, x: u32, y: u32, z: u32) {
+        assert!(
+            self.compute_pass.is_some(),
+            "dispatch called without a compute shader"
+        );
+        self.pending_dispatches.push([x, y, z]);
+    }
+
+    fn record_dispatches(&mut self) {
+        let compute_pass = match &self.compute_pass {
+            Some(compute_pass) if !self.pending_dispatches.is_empty() => compute_pass,
+            _ => return,
+        };
+        let device = &self.device.inner;
+        unsafe {
+            device.cmd_bind_pipeline(
+                self.command_buffer,
+                PipelineBindPoint::COMPUTE,
+                compute_pass.pipeline.inner,
+            );
+            device.cmd_bind_descriptor_sets(
+                self.command_buffer,
+                PipelineBindPoint::COMPUTE,
+                compute_pass.pipeline.pipeline_layout,
+                0,
+                &[compute_pass.descriptor_set],
+                &[],
+            );
+            device.cmd_push_constants(
+                self.command_buffer,
+                compute_pass.pipeline.pipeline_layout,
+                ShaderStageFlags::COMPUTE,
+                0,
+                &self.push_data,
+            );
+            for [x, y, z] in self.pending_dispatches.drain(..) {
+                device.cmd_dispatch(self.command_buffer, x, y, z);
+            }
+
+            // Make the compute writes visible to the vertex input and shaders of the render pass.
+            let barrier = MemoryBarrier::builder()
+                .src_access_mask(AccessFlags::SHADER_WRITE)
+                .dst_access_mask(AccessFlags::VERTEX_ATTRIBUTE_READ | AccessFlags::SHADER_READ);
+            device.cmd_pipeline_barrier(
+                self.command_buffer,
+                PipelineStageFlags::COMPUTE_SHADER,
+                PipelineStageFlags::VERTEX_INPUT
+                    | PipelineStageFlags::VERTEX_SHADER
+                    | PipelineStageFlags::FRAGMENT_SHADER,
+                DependencyFlags::empty(),
+                &[barrier.build()],
+                &[],
+                &[],
+            );
+        }
+    }
+
+    /// Renders the scene into an offscreen target and draws it to the output with a fullscreen pass
+    /// running `fragment_code`, which samples the scene at set 0, binding 0. `None` renders directly
+    /// to the output again. Not supported with MSAA.
+    pub fn set_post_process(&mut self, fragment_code: Option<&[u8]>) {
+        unsafe {
+            self.device.inner.device_wait_idle().unwrap();
+        }
+        self.post_process = None;
+        let fragment_code = match fragment_code {
+            Some(fragment_code) => fragment_code,
+            None => return,
+        };
+        if self.deferred_lighting.is_some() {
+            warn!("Post-processing is not supported with deferred shading, skipping it");
+            return;
+        }
+        if self.graphics_pipeline.samples != SampleCountFlags::TYPE_1 {
+            warn!("Post-processing is not supported with MSAA, skipping it");
+            return;
+        }
+        if self.graphics_pipeline.uses_dynamic_rendering() {
+            warn!("Post-processing is not supported with dynamic rendering, skipping it");
+            return;
+        }
+        let (format, final_layout) = match &self.swap_chain {
+            Some(swap_chain) => (
+                swap_chain.surface_format.format,
+                ImageLayout::PRESENT_SRC_KHR,
+            ),
+            None => (HEADLESS_COLOR_FORMAT, ImageLayout::TRANSFER_SRC_OPTIMAL),
+        };
+        let Ok((_, extent)) = self.render_target(0) else {
+            warn!("The window has no swapchain, skipping post-processing");
+            return;
+        };
+        self.post_process = Some(PostProcess::new(
+            &self.device,
+            extent,
+            format,
+            self.graphics_pipeline.depth_format,
+            final_layout,
+            &self.sampler,
+            fragment_code,
+        ));
+    }
+
+    /// Feeds a window event to the egui overlay, returns `true` if egui consumed it.
+    #[cfg(feature = "egui")]
+    pub fn handle_ui_event(&mut self, event: &WindowEvent) -> bool {
+        self.ui.as_mut().is_some_and(|ui| ui.handle_event(event))
+    }
+
+    /// Builds the egui overlay drawn on top of the following frames.
+    #[cfg(feature = "egui")]
+    pub fn run_ui(&mut self, window: &Window, run: impl FnOnce(&egui::Context)) {
+        if let Some(ui) = &mut self.ui {
+            ui.run(
+                &self.instance.inner,
+                &self.device,
+                &self.sampler,
+                window,
+                run,
+            );
+        }
+    }
+
+    /// Replaces the drawn geometry. The old buffers are dropped once the frames using them have finished.
+    /// Panics if `vertices` or `indices` is empty.
+    pub fn set_mesh(&mut self, vertices: &[Vertex], indices: &[u32]) {
+        let mesh = Mesh::new(&self.device, vertices, indices);
+        self.deletion_queue
+            .push(std::mem::replace(&mut self.mesh, mesh));
+    }
+
+    /// Draws the mesh once per offset, all in a single instanced draw call.
+    pub fn set_instances(&mut self, offsets: &[[f32; 3]]) {
+        self.instance_count = offsets.len() as u32;
+        if offsets.is_empty() {
+            return;
+        }
+        let instances: Vec<InstanceData> = offsets
+            .iter()
+            .map(|offset| InstanceData { offset: *offset })
+            .collect();
+        let instance_buffer = Buffer::new_device_local(
+            &self.device,
+            BufferUsageFlags::VERTEX_BUFFER,
+            bytemuck::cast_slice(&instances),
+        );
+        self.deletion_queue.push(std::mem::replace(
+            &mut self.instance_buffer,
+            instance_buffer,
+        ));
+    }
+
+    /// Replaces the line segments drawn on top of the scene every following frame, in world space.
+    /// Only the positions and colors of the vertices are used.
+    pub fn draw_lines(&mut self, segments: &[(Vertex, Vertex)]) {
+        if self.line_pipeline.is_none() && !segments.is_empty() {
+            warn!("Lines are not supported with dynamic rendering or deferred shading");
+        }
+        self.lines.vertices.clear();
+        self.lines
+            .vertices
+            .extend(segments.iter().flat_map(|(start, end)| [*start, *end]));
+    }
+
+    /// Draws a ground grid on the XZ plane and the XYZ axes in red, green and blue on top of the
+    /// scene with the line pipeline, to keep track of the camera's orientation.
+    pub fn set_show_gizmos(&mut self, show: bool) {
+        if show && self.line_pipeline.is_none() {
+            warn!("Gizmos are not supported with dynamic rendering or deferred shading");
+        }
+        self.gizmos.vertices = if show {
+            mesh::gizmo_lines(GIZMO_GRID_HALF_EXTENT)
+        } else {
+            Vec::new()
+        };
+    }
+
+    /// Replaces the points drawn on top of the scene every following frame, e.g. for particles or
+    /// a point cloud. Only the positions and colors of the vertices are used, the size is set with
+    /// `RendererConfig::point_size`.
+    pub fn set_points(&mut self, points: &[Vertex]) {
+        if self.point_pipeline.is_none() && !points.is_empty() {
+            warn!("Points are not supported with dynamic rendering or deferred shading");
+        }
+        self.points.vertices.clear();
+        self.points.vertices.extend_from_slice(points);
+    }
+
+    /// Sets the camera matrix the vertex shader transforms every vertex with, starting with the next frame.
+    /// Leave out the surface's pre-transform, it's applied on top so the aspect ratio should be
+    /// the window's.
+    pub fn set_view_projection(&mut self, view_projection: Mat4) {
+        self.view_projection = view_projection;
+    }
+
+    /// Allocates a descriptor set for the next frame only, it's freed once the frame after it
+    /// starts. Pools are added as needed.
+    pub fn allocate_transient_descriptor_set(
+        &mut self,
+        layout: DescriptorSetLayout,
+    ) -> DescriptorSet {
+        self.transient_descriptors.allocate(layout)
+    }
+
+    /// Replaces the model transforms `draw_object` picks from, at most `MAX_OBJECTS` of them. The
+    /// first one is used when no objects are drawn.
+    pub fn set_object_transforms(&mut self, transforms: &[Mat4]) {
+        assert!(
+            !transforms.is_empty() && transforms.len() <= MAX_OBJECTS,
+            "Between 1 and {} object transforms are supported",
+            MAX_OBJECTS
+        );
+        self.object_transforms = transforms.to_vec();
+    }
+
+    /// Draws the mesh with its instances once more in the next frame, transformed by the model
+    /// transform at `transform_index`. Each draw only rebinds the descriptor set with another
+    /// dynamic offset. Without any queued draws the mesh is drawn once with the first transform.
+    /// The queue is cleared by `draw_frame`, so call `draw_window` first to draw the same objects.
+    pub fn draw_object(&mut self, transform_index: u32) {
+        assert!(
+            (transform_index as usize) < self.object_transforms.len(),
+            "Object transform {} is not set",
+            transform_index
+        );
+        self.object_draws.push(transform_index);
     }
 
-    pub fn record_commandbuffer(&mut self, image_index: usize) {
+    /// Sets the bindless texture of each object, indices returned by `register_texture`.
+    /// Objects past the end of `texture_indices` use texture 0.
+    pub fn set_object_textures(&mut self, texture_indices: &[u32]) {
+        assert!(
+            texture_indices.len() <= MAX_OBJECTS,
+            "At most {} object textures are supported",
+            MAX_OBJECTS
+        );
+        self.object_textures = texture_indices.to_vec();
+    }
+
+    /// Sets the cull mode and front face of each object, e.g. to draw mirrored transforms or
+    /// double sided meshes. Objects past the end of `face_culling` use `FaceCulling::default()`.
+    /// Needs `VK_EXT_extended_dynamic_state`, every object is drawn with the default otherwise.
+    pub fn set_object_face_culling(&mut self, face_culling: &[FaceCulling]) {
+        assert!(
+            face_culling.len() <= MAX_OBJECTS,
+            "At most {} object face culling states are supported",
+            MAX_OBJECTS
+        );
+        if !self.graphics_pipeline.options.dynamic_face_culling {
+            warn!("Extended dynamic state is not supported, keeping the pipeline's face culling");
+        }
+        self.object_face_culling = face_culling.to_vec();
+    }
+
+    /// Sets the depth bias of the scene's draws, e.g. while rendering shadow casters. Needs
+    /// `RendererConfig::dynamic_depth_bias`, the pipeline's bias is kept otherwise.
+    pub fn set_depth_bias(&mut self, depth_bias: DepthBias) {
+        if !self.graphics_pipeline.options.dynamic_depth_bias {
+            warn!("The depth bias is not dynamic, keeping the pipeline's depth bias");
+        }
+        self.depth_bias = depth_bias.supported(&self.device);
+    }
+
+    /// Writes each object's transform and texture index at the start of its aligned slot, laid
+    /// out like the `Object` block of the vertex shader.
+    fn upload_object_transforms(&mut self) {
+        let stride = self.object_stride as usize;
+        let mut bytes = vec![0; stride * self.object_transforms.len()];
+        for (index, (slot, transform)) in bytes
+            .chunks_mut(stride)
+            .zip(&self.object_transforms)
+            .enumerate()
+        {
+            let texture_index = self.object_textures.get(index).copied().unwrap_or(0);
+            let transform = bytemuck::bytes_of(transform);
+            slot[..transform.len()].copy_from_slice(transform);
+            slot[transform.len()..transform.len() + 4]
+                .copy_from_slice(&texture_index.to_ne_bytes());
+        }
+        self.object_buffer.write(&bytes);
+    }
+
+    /// Transform indices the scene is drawn with this frame.
+    fn scene_objects(&self) -> &[u32] {
+        if self.object_draws.is_empty() {
+            &[0]
+        } else {
+            &self.object_draws
+        }
+    }
+
+    /// Sets the push constant data handed to the shaders on every following frame.
+    pub fn set_push_data<T: Pod>(&mut self, data: &T) {
+        let bytes = bytemuck::bytes_of(data);
+        assert!(
+            bytes.len() <= self.push_data.len(),
+            "Push constant data exceeds {} bytes",
+            PUSH_CONSTANT_SIZE
+        );
+        self.push_data[..bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Loads a PNG or JPEG image and uses it as the texture of the drawn geometry.
+    pub fn load_texture(&mut self, path: &Path) -> ::image::ImageResult<()> {
+        let texture = Texture::load(&self.instance.inner, &self.device, path)?;
+        unsafe {
+            self.device.inner.device_wait_idle().unwrap();
+        }
+        Renderer::write_texture_descriptor(
+            &self.device,
+            self.descriptor_set,
+            &texture,
+            self.texture_sampler.as_ref().unwrap_or(&self.sampler),
+        );
+        if let Some(bindless) = &self.bindless {
+            bindless.write(
+                0,
+                &texture,
+                self.texture_sampler.as_ref().unwrap_or(&self.sampler),
+            );
+        }
+        self.texture = texture;
+        Ok(())
+    }
+
+    /// Loads a PNG or JPEG image into the bindless texture array and returns its index for
+    /// `set_object_textures`. Index 0 is the texture set with `load_texture`. `None` if bindless
+    /// textures weren't enabled with `RendererConfig::bindless` or aren't supported, in which case
+    /// only `load_texture` is available. Registered textures are dropped by `recreate_device`.
+    pub fn register_texture(&mut self, path: &Path) -> ::image::ImageResult<Option<u32>> {
+        let Some(bindless) = &mut self.bindless else {
+            return Ok(None);
+        };
+        let texture = Texture::load(&self.instance.inner, &self.device, path)?;
+        let index = bindless.push(
+            &texture,
+            self.texture_sampler.as_ref().unwrap_or(&self.sampler),
+        );
+        self.bindless_textures.push(texture);
+        Ok(Some(index))
+    }
+
+    /// Like `register_texture`, with already decoded RGBA8 pixels such as the textures of a
+    /// `scene::Scene`.
+    pub fn register_texture_rgba8(
+        &mut self,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Option<u32> {
+        let bindless = self.bindless.as_mut()?;
+        let texture =
+            Texture::from_rgba8(&self.instance.inner, &self.device, width, height, pixels);
+        let index = bindless.push(
+            &texture,
+            self.texture_sampler.as_ref().unwrap_or(&self.sampler),
+        );
+        self.bindless_textures.push(texture);
+        Some(index)
+    }
+
+    /// Replaces the sampler of the texture, e.g. with `SamplerBuilder::nearest()` for pixel art.
+    /// Reset to the default sampler when the device is recreated.
+    pub fn set_texture_sampler(&mut self, builder: SamplerBuilder) {
+        let sampler = builder.build(&self.instance.inner, &self.device, Texture::FORMAT);
+        unsafe {
+            self.device.inner.device_wait_idle().unwrap();
+        }
+        Renderer::write_texture_descriptor(
+            &self.device,
+            self.descriptor_set,
+            &self.texture,
+            &sampler,
+        );
+        if let Some(bindless) = &self.bindless {
+            for (index, texture) in std::iter::once(&self.texture)
+                .chain(&self.bindless_textures)
+                .enumerate()
+            {
+                bindless.write(index as u32, texture, &sampler);
+            }
+        }
+        self.texture_sampler = Some(sampler);
+    }
+
+    fn write_texture_descriptor(
+        device: &Device,
+        descriptor_set: DescriptorSet,
+        texture: &Texture,
+        sampler: &Sampler,
+    ) {
+        let image_infos = [DescriptorImageInfo::builder()
+            .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(texture.image.view)
+            .sampler(sampler.inner)
+            .build()];
+        let writes = [WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&image_infos)
+            .build()];
+        unsafe {
+            device.inner.update_descriptor_sets(&writes, &[]);
+        }
+    }
+
+    /// Device features that were granted, e.g. to hide settings the GPU can't honor. Unsupported
+    /// optional features are already worked around, anisotropy is disabled and lines are thin.
+    pub fn enabled_features(&self) -> DeviceFeatureSet {
+        self.device.feature_set()
+    }
+
+    /// Highest `RendererConfig::msaa_samples` the GPU supports for both color and depth, higher
+    /// counts are clamped to it. Lower ones are in `framebuffer_color_sample_counts` and
+    /// `framebuffer_depth_sample_counts` of `limits`.
+    pub fn max_msaa_samples(&self) -> SampleCountFlags {
+        self.device.physical_device.max_usable_sample_count()
+    }
+
+    /// Limits of the GPU in use, e.g. `min_uniform_buffer_offset_alignment` to pack several
+    /// objects into one uniform buffer or `max_image_dimension2_d` for texture sizes.
+    pub fn limits(&self) -> &PhysicalDeviceLimits {
+        &self.device.physical_device.properties.limits
+    }
+
+    /// Present mode the swapchain was created with, `None` when headless.
+    pub fn present_mode(&self) -> Option<PresentModeKHR> {
+        self.swap_chain
+            .as_ref()
+            .map(|swap_chain| swap_chain.present_mode)
+    }
+
+    /// Format and color space of the main window's swapchain, `None` when headless. With an sRGB
+    /// format the hardware converts the shader output from linear.
+    pub fn surface_format(&self) -> Option<SurfaceFormatKHR> {
+        self.swap_chain
+            .as_ref()
+            .map(|swap_chain| swap_chain.surface_format)
+    }
+
+    /// Color format of the main window's swapchain images, or of the offscreen target when
+    /// headless. Pipelines drawing into the same render target have to use it.
+    pub fn swapchain_format(&self) -> Format {
+        self.swap_chain
+            .as_ref()
+            .map_or(HEADLESS_COLOR_FORMAT, |swap_chain| {
+                swap_chain.surface_format.format
+            })
+    }
+
+    /// Current size of the swapchain images, or of the offscreen target when headless. Changes
+    /// when the swapchain is recreated, zero while the window has no swapchain.
+    pub fn swapchain_extent(&self) -> Extent2D {
+        self.render_target(0)
+            .map_or(Extent2D::default(), |(_, extent)| extent)
+    }
+
+    /// Describes the mastering display's primaries and luminance to the main window's display,
+    /// reapplied whenever the swapchain is recreated. Ignored without `VK_EXT_hdr_metadata` or
+    /// an HDR swapchain, see `RendererConfig::hdr`.
+    pub fn set_hdr_metadata(&mut self, metadata: HdrMetadataEXT) {
+        if self.device.hdr_metadata.is_none() {
+            warn!("VK_EXT_hdr_metadata is not supported, ignoring HDR metadata");
+            return;
+        }
+        self.hdr_metadata = Some(metadata);
+        self.apply_hdr_metadata();
+    }
+
+    fn apply_hdr_metadata(&self) {
+        let (Some(hdr_metadata_fn), Some(swap_chain), Some(metadata)) = (
+            &self.device.hdr_metadata,
+            &self.swap_chain,
+            &self.hdr_metadata,
+        ) else {
+            return;
+        };
+        if swap_chain.surface_format.color_space == ColorSpaceKHR::SRGB_NONLINEAR {
+            return;
+        }
+        unsafe {
+            (hdr_metadata_fn.set_hdr_metadata_ext)(
+                self.device.inner.handle(),
+                1,
+                &swap_chain.inner,
+                metadata,
+            );
+        }
+    }
+
+    /// Rotation the presentation engine applies to the main window, `IDENTITY` when headless.
+    /// The scene is already rendered rotated to match it, while the UI and post-processing aren't.
+    pub fn pre_transform(&self) -> SurfaceTransformFlagsKHR {
+        self.swap_chain
+            .as_ref()
+            .map_or(SurfaceTransformFlagsKHR::IDENTITY, |swap_chain| {
+                swap_chain.pre_transform
+            })
+    }
+
+    /// CPU frame time averaged over the last frames.
+    pub fn frame_time(&self) -> Duration {
+        self.frame_timer.frame_time()
+    }
+
+    /// Seconds between the two most recent `draw_frame` calls, 1/60 before the second frame.
+    pub fn delta_time(&self) -> f32 {
+        self.frame_timer.delta_time().as_secs_f32()
+    }
+
+    pub fn fps(&self) -> f32 {
+        self.frame_timer.fps()
+    }
+
+    /// GPU time spent on the most recently completed frame, zero if timestamps are unsupported.
+    pub fn last_gpu_frame_time(&self) -> Duration {
+        self.gpu_timer
+            .as_ref()
+            .map_or(Duration::ZERO, GpuTimer::last_frame_time)
+    }
+
+    /// Draws `frame_count` frames of the current scene as fast as possible and reports their CPU
+    /// and GPU times. The FPS cap is lifted and the main window's swapchain switched to
+    /// `IMMEDIATE` for the run if the surface supports it, both are restored afterwards. Objects
+    /// queued with `draw_object` are drawn in every frame of the run.
+    pub fn benchmark(&mut self, frame_count: u32) -> Result<BenchReport, RendererError> {
+        let frame_limiter = self.frame_limiter.take();
+        let restore_present_mode = match &self.swap_chain {
+            Some(swap_chain) if swap_chain.present_mode != PresentModeKHR::IMMEDIATE => {
+                Some((swap_chain.extent, swap_chain.present_mode))
+            }
+            _ => None,
+        };
+        if let Some((extent, _)) = restore_present_mode {
+            if let Err(err) = self.rebuild_swapchain(extent, Some(PresentModeKHR::IMMEDIATE)) {
+                self.frame_limiter = frame_limiter;
+                return Err(err);
+            }
+            if self.present_mode() != Some(PresentModeKHR::IMMEDIATE) {
+                warn!("IMMEDIATE present mode is unsupported, benchmarking with VSync");
+            }
+        }
+
+        // Keeps the last frame drawn before the run out of the GPU times.
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            unsafe {
+                self.device.inner.device_wait_idle().unwrap();
+            }
+            gpu_timer.collect();
+        }
+
+        // `draw_frame` clears the queued draws, every frame of the run draws the same ones.
+        let object_draws = self.object_draws.clone();
+        let mut cpu_times = Vec::with_capacity(frame_count as usize);
+        let mut gpu_times = Vec::with_capacity(frame_count as usize);
+        let mut result = Ok(());
+        for _ in 0..frame_count {
+            self.object_draws.clone_from(&object_draws);
+            let collected_frames = self.gpu_timer.as_ref().map(GpuTimer::collected_frames);
+            let start = Instant::now();
+            result = self.draw_frame();
+            cpu_times.push(start.elapsed());
+            if result.is_err() {
+                break;
+            }
+            // The previous frame's GPU time is picked up at the start of each frame.
+            if let Some(gpu_timer) = &self.gpu_timer {
+                if Some(gpu_timer.collected_frames()) != collected_frames {
+                    gpu_times.push(gpu_timer.last_frame_time());
+                }
+            }
+        }
+        if result.is_ok() {
+            unsafe {
+                self.device.inner.device_wait_idle().unwrap();
+            }
+            if let Some(gpu_timer) = &mut self.gpu_timer {
+                let collected_frames = gpu_timer.collected_frames();
+                gpu_timer.collect();
+                if gpu_timer.collected_frames() != collected_frames {
+                    gpu_times.push(gpu_timer.last_frame_time());
+                }
+            }
+        }
+
+        self.frame_limiter = frame_limiter;
+        if let Some((extent, present_mode)) = restore_present_mode {
+            if self.swap_chain.is_some() {
+                result = result.and(self.rebuild_swapchain(extent, Some(present_mode)));
+            }
+        }
+        result?;
+
+        Ok(BenchReport {
+            frame_count,
+            cpu: FrameTimeStats::from_samples(&mut cpu_times).unwrap_or_default(),
+            gpu: self
+                .gpu_timer
+                .as_ref()
+                .and(FrameTimeStats::from_samples(&mut gpu_times)),
+        })
+    }
+
+    /// Creates `count` occlusion queries on the renderer's device, see `OcclusionQuery`.
+    pub fn create_occlusion_query(&self, count: u32, precise: bool) -> OcclusionQuery {
+        OcclusionQuery::new(&self.device, count, precise)
+    }
+
+    /// Statistics of the most recently completed frame, `None` unless enabled with
+    /// `RendererConfig::pipeline_statistics` and supported.
+    pub fn pipeline_stats(&self) -> Option<PipelineStats> {
+        self.pipeline_stats
+            .as_ref()
+            .map(PipelineStatsQuery::last_stats)
+    }
+
+    /// Validation messages reported since the last call. Empty when validation isn't enabled.
+    pub fn take_validation_messages(&self) -> Vec<ValidationMessage> {
+        self.debug_messenger
+            .as_ref()
+            .map_or_else(Vec::new, DebugMessenger::take_messages)
+    }
+
+    pub fn validation_error_count(&self) -> usize {
+        self.debug_messenger
+            .as_ref()
+            .map_or(0, DebugMessenger::error_count)
+    }
+
+    pub fn allocation_stats(&self) -> AllocationStats {
+        self.device.allocator.lock().unwrap().stats()
+    }
+
+    pub fn memory_budget(&self) -> Vec<HeapBudget> {
+        let has_budget_extension = self.device.has_extension(ExtMemoryBudgetFn::name());
+        self.device
+            .allocator
+            .lock()
+            .unwrap()
+            .budget(has_budget_extension)
+    }
+
+    // Raw handles for interop with other Vulkan code. They are owned by the renderer: don't
+    // destroy them, don't use them after the renderer is dropped, and don't use device handles
+    // after `recreate_device`. Vulkan's external synchronization rules apply, e.g. the queue must
+    // not be used while `draw_frame` runs on another thread.
+
+    pub fn vk_instance(&self) -> &ash::Instance {
+        &self.instance.inner
+    }
+
+    pub fn vk_physical_device(&self) -> ash::vk::PhysicalDevice {
+        self.device.physical_device.inner
+    }
+
+    pub fn vk_device(&self) -> &ash::Device {
+        &self.device.inner
+    }
+
+    pub fn graphics_queue(&self) -> ash::vk::Queue {
+        self.device.graphics_queue
+    }
+
+    pub fn graphics_family_index(&self) -> u32 {
+        self.device.graphics_family_index()
+    }
+
+    /// `None` when headless.
+    pub fn present_family_index(&self) -> Option<u32> {
+        self.device.present_family_index()
+    }
+
+    /// Same as `graphics_family_index` if the device has no dedicated transfer family.
+    pub fn transfer_family_index(&self) -> u32 {
+        self.device.transfer_family_index()
+    }
+
+    /// E.g. to check which families support compute or how many queues they offer.
+    pub fn queue_family_properties(&self, family_index: u32) -> &QueueFamilyProperties {
+        self.device.queue_family_properties(family_index)
+    }
+
+    /// The primary command buffer of the frame. It's reset and recorded by `draw_frame`, so
+    /// between frames it holds the last submitted frame, which may still be executing.
+    pub fn command_buffer(&self) -> CommandBuffer {
+        self.command_buffer
+    }
+
+    /// Fails with `SurfaceLost` if there's nothing to render into, see `render_target`.
+    pub fn record_commandbuffer(&mut self, image_index: usize) -> Result<(), RendererError> {
+        let (framebuffer, extent) = self.render_target(image_index)?;
         let begin_info = CommandBufferBeginInfo::builder();
         unsafe {
             self.device
@@ -156,35 +1912,390 @@ impl Renderer {
                 .begin_command_buffer(self.command_buffer, &begin_info)
                 .unwrap();
         }
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.begin(self.command_buffer);
+        }
+        if let Some(pipeline_stats) = &mut self.pipeline_stats {
+            pipeline_stats.begin(self.command_buffer);
+        }
+        self.record_dispatches();
 
-        let clear_color = ClearValue::default();
-        let clear_colors = [clear_color];
-        let render_pass_begin_info = RenderPassBeginInfo::builder()
-            .render_pass(self.graphics_pipeline.render_pass)
-            .framebuffer(self.swap_chain.framebuffers[image_index])
-            .render_area(ash::vk::Rect2D {
-                offset: ash::vk::Offset2D { x: 0, y: 0 },
-                extent: self.swap_chain.extent,
+        // The post-processing pass clears the swapchain image.
+        if let (Some(swap_chain), None) = (&self.swap_chain, &self.post_process) {
+            swap_chain.record_initial_layout(
+                &self.device.inner,
+                self.command_buffer,
+                image_index,
+                self.graphics_pipeline.options.color_initial_layout,
+            );
+        }
+        if let Some(debug_messenger) = &self.debug_messenger {
+            debug_messenger.cmd_begin_label(self.command_buffer, "Main Pass", [0.2, 0.6, 1.0, 1.0]);
+        }
+        match &self.post_process {
+            Some(post_process) => {
+                post_process
+                    .target
+                    .begin(self.command_buffer, self.clear_color);
+                self.record_scene(post_process.target.extent);
+                post_process.target.end(self.command_buffer);
+                self.record_post_process(post_process, framebuffer, extent);
+            }
+            None if self.graphics_pipeline.uses_dynamic_rendering() => {
+                self.record_dynamic_rendering(image_index, extent);
+            }
+            None => {
+                let clear_color = ClearValue {
+                    color: ClearColorValue {
+                        float32: self.clear_color,
+                    },
+                };
+                // The resolve attachment, if any, ignores its clear value.
+                let mut clear_values = vec![clear_color];
+                if self.graphics_pipeline.samples != SampleCountFlags::TYPE_1 {
+                    clear_values.push(clear_color);
+                }
+                // Albedo shows the clear color where there's no geometry, marked by a zero normal.
+                if self.deferred_lighting.is_some() {
+                    clear_values.extend([clear_color, ClearValue::default()]);
+                }
+                clear_values.push(DEPTH_STENCIL_CLEAR_VALUE);
+                let render_pass_begin_info = RenderPassBeginInfo::builder()
+                    .render_pass(self.render_pass.inner)
+                    .framebuffer(framebuffer)
+                    .render_area(Rect2D::builder().extent(extent).build())
+                    .clear_values(&clear_values);
+                let record_inline = self.recording_pools.is_empty()
+                    || self.graphics_pipeline.subpass != 0
+                    || !self.scene_pipeline_ready();
+                let contents = if record_inline {
+                    SubpassContents::INLINE
+                } else {
+                    SubpassContents::SECONDARY_COMMAND_BUFFERS
+                };
+                unsafe {
+                    self.device.inner.cmd_begin_render_pass(
+                        self.command_buffer,
+                        &render_pass_begin_info,
+                        contents,
+                    );
+                }
+                let pipeline = &self.graphics_pipeline;
+                pipeline.cmd_next_subpasses(
+                    &self.device.inner,
+                    self.command_buffer,
+                    0,
+                    pipeline.subpass,
+                );
+                if record_inline {
+                    self.record_scene(extent);
+                } else {
+                    let secondaries = self.record_scene_parallel(framebuffer, extent);
+                    unsafe {
+                        self.device
+                            .inner
+                            .cmd_execute_commands(self.command_buffer, &secondaries);
+                    }
+                }
+                let mut subpass = pipeline.subpass;
+                if let Some(deferred_lighting) = &self.deferred_lighting {
+                    pipeline.cmd_next_subpasses(
+                        &self.device.inner,
+                        self.command_buffer,
+                        subpass,
+                        LIGHTING_SUBPASS,
+                    );
+                    deferred_lighting.record(&self.device.inner, self.command_buffer, extent);
+                    subpass = LIGHTING_SUBPASS;
+                }
+                pipeline.cmd_next_subpasses(
+                    &self.device.inner,
+                    self.command_buffer,
+                    subpass,
+                    pipeline.subpass_count - 1,
+                );
+                unsafe {
+                    self.device.inner.cmd_end_render_pass(self.command_buffer);
+                }
+            }
+        }
+        #[cfg(feature = "egui")]
+        if let Some(ui) = &mut self.ui {
+            ui.upload(&self.device);
+            ui.record(&self.device, self.command_buffer, framebuffer, extent);
+        }
+        if let Some(debug_messenger) = &self.debug_messenger {
+            debug_messenger.cmd_end_label(self.command_buffer);
+        }
+        if let Some(pipeline_stats) = &mut self.pipeline_stats {
+            pipeline_stats.end(self.command_buffer);
+        }
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.end(self.command_buffer);
+        }
+        if let (Some(swap_chain), Some(buffer)) = (&self.swap_chain, &self.capture_buffer) {
+            swap_chain.record_capture(
+                &self.device.inner,
+                self.command_buffer,
+                image_index,
+                buffer.inner,
+            );
+        }
+        if let Some(swap_chain) = &self.swap_chain {
+            swap_chain.record_release(&self.device.inner, self.command_buffer, image_index);
+        }
+        unsafe {
+            self.device
+                .inner
+                .end_command_buffer(self.command_buffer)
+                .unwrap();
+        }
+        Ok(())
+    }
+
+    /// Renders the scene straight into the swapchain image with `VK_KHR_dynamic_rendering`.
+    fn record_dynamic_rendering(&self, image_index: usize, extent: Extent2D) {
+        let swap_chain = self.swap_chain.as_ref().unwrap();
+        let dynamic_rendering = self.device.dynamic_rendering.as_ref().unwrap();
+        let image = swap_chain.images[image_index];
+        let options = &self.graphics_pipeline.options;
+        image::transition_image_layout(
+            &self.device.inner,
+            self.command_buffer,
+            image,
+            1,
+            options.color_initial_layout,
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        );
+        let depth_image = swap_chain.depth_image.as_ref().unwrap();
+        image::transition_image_layout(
+            &self.device.inner,
+            self.command_buffer,
+            depth_image.inner,
+            1,
+            ImageLayout::UNDEFINED,
+            ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        );
+
+        let color_attachments = [RenderingAttachmentInfo::builder()
+            .image_view(swap_chain.image_views[image_index])
+            .image_layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .load_op(options.color_load_op)
+            .store_op(AttachmentStoreOp::STORE)
+            .clear_value(ClearValue {
+                color: ClearColorValue {
+                    float32: self.clear_color,
+                },
             })
-            .clear_values(&clear_colors);
+            .build()];
+        let depth_attachment = RenderingAttachmentInfo::builder()
+            .image_view(depth_image.view)
+            .image_layout(ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .load_op(AttachmentLoadOp::CLEAR)
+            .store_op(AttachmentStoreOp::DONT_CARE)
+            .clear_value(DEPTH_STENCIL_CLEAR_VALUE);
+        let rendering_info = RenderingInfo::builder()
+            .render_area(Rect2D::builder().extent(extent).build())
+            .layer_count(1)
+            .color_attachments(&color_attachments)
+            .depth_attachment(&depth_attachment)
+            .stencil_attachment(&depth_attachment);
+        unsafe {
+            dynamic_rendering.cmd_begin_rendering(self.command_buffer, &rendering_info);
+        }
+        self.record_scene(extent);
+        unsafe {
+            dynamic_rendering.cmd_end_rendering(self.command_buffer);
+        }
+
+        image::transition_image_layout(
+            &self.device.inner,
+            self.command_buffer,
+            image,
+            1,
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ImageLayout::PRESENT_SRC_KHR,
+        );
+    }
+
+    /// Draws the mesh, lines and points inside an already begun render pass. Nothing is drawn
+    /// while the main pipeline compiles, the pass only clears.
+    fn record_scene(&self, extent: Extent2D) {
+        if !self.scene_pipeline_ready() {
+            return;
+        }
+        self.scene_draw().record(
+            &self.device.inner,
+            self.command_buffer,
+            extent,
+            self.scene_objects(),
+            0..self.instance_count,
+        );
+        for primitive_draw in self.primitive_draws() {
+            primitive_draw.record(&self.device.inner, self.command_buffer);
+        }
+    }
+
+    /// Splits the instances across the recording threads, each recording into the secondary
+    /// command buffer of its own pool. Returns the buffers to execute inside the main render pass.
+    fn record_scene_parallel(
+        &self,
+        framebuffer: Framebuffer,
+        extent: Extent2D,
+    ) -> Vec<CommandBuffer> {
+        let scene_draw = self.scene_draw();
+        let primitive_draws = &self.primitive_draws();
+        let objects = self.scene_objects();
+        let device = &self.device.inner;
+        let render_pass = self.render_pass.inner;
+        let pipeline_statistics = if self.pipeline_stats.is_some() {
+            PipelineStatsQuery::flags()
+        } else {
+            QueryPipelineStatisticFlags::empty()
+        };
+        let chunk_size = self
+            .instance_count
+            .div_ceil(self.recording_pools.len() as u32);
+
+        std::thread::scope(|scope| {
+            for (index, (_, command_buffer)) in self.recording_pools.iter().enumerate() {
+                let first_instance = (index as u32 * chunk_size).min(self.instance_count);
+                let end_instance = (first_instance + chunk_size).min(self.instance_count);
+                scope.spawn(move || {
+                    let inheritance_info = CommandBufferInheritanceInfo::builder()
+                        .render_pass(render_pass)
+                        .subpass(0)
+                        .framebuffer(framebuffer)
+                        .pipeline_statistics(pipeline_statistics);
+                    let begin_info = CommandBufferBeginInfo::builder()
+                        .flags(
+                            CommandBufferUsageFlags::RENDER_PASS_CONTINUE
+                                | CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+                        )
+                        .inheritance_info(&inheritance_info);
+                    unsafe {
+                        device
+                            .begin_command_buffer(*command_buffer, &begin_info)
+                            .unwrap();
+                    }
+                    scene_draw.record(
+                        device,
+                        *command_buffer,
+                        extent,
+                        objects,
+                        first_instance..end_instance,
+                    );
+                    // The debug primitives are cheap enough for the first thread to draw on its own.
+                    if index == 0 {
+                        for primitive_draw in primitive_draws {
+                            primitive_draw.record(device, *command_buffer);
+                        }
+                    }
+                    unsafe {
+                        device.end_command_buffer(*command_buffer).unwrap();
+                    }
+                });
+            }
+        });
+
+        self.recording_pools
+            .iter()
+            .map(|(_, command_buffer)| *command_buffer)
+            .collect()
+    }
 
+    fn scene_draw(&self) -> SceneDraw<'_> {
+        SceneDraw {
+            pipeline: self.graphics_pipeline.inner,
+            pipeline_layout: self.graphics_pipeline.pipeline_layout,
+            descriptor_set: self.descriptor_set,
+            object_stride: self.object_stride,
+            bindless_set: self
+                .bindless
+                .as_ref()
+                .map(|bindless| bindless.descriptor_set),
+            push_data: self.push_data,
+            vertex_buffer: self.mesh.vertex_buffer.inner,
+            instance_buffer: self.instance_buffer.inner,
+            index_buffer: self.mesh.index_buffer.inner,
+            index_count: self.mesh.index_count,
+            extended_dynamic_state: self
+                .device
+                .extended_dynamic_state
+                .as_ref()
+                .filter(|_| self.graphics_pipeline.options.dynamic_face_culling),
+            object_face_culling: &self.object_face_culling,
+            depth_bias: self
+                .graphics_pipeline
+                .options
+                .dynamic_depth_bias
+                .then_some(self.depth_bias),
+        }
+    }
+
+    /// Lines and points that have vertices to draw.
+    fn primitive_draws(&self) -> Vec<PrimitiveDraw> {
+        [
+            self.lines.draw(self.line_pipeline.as_ref()),
+            self.gizmos.draw(self.line_pipeline.as_ref()),
+            self.points.draw(self.point_pipeline.as_ref()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Draws the fullscreen post-processing pass into the output framebuffer.
+    fn record_post_process(
+        &self,
+        post_process: &PostProcess,
+        framebuffer: Framebuffer,
+        extent: Extent2D,
+    ) {
+        let device = &self.device.inner;
+        let render_area = Rect2D::builder().extent(extent).build();
+        let render_pass_begin_info = RenderPassBeginInfo::builder()
+            .render_pass(post_process.pipeline.render_pass)
+            .framebuffer(framebuffer)
+            .render_area(render_area);
         unsafe {
-            self.device.inner.cmd_begin_render_pass(
+            device.cmd_begin_render_pass(
                 self.command_buffer,
                 &render_pass_begin_info,
                 SubpassContents::INLINE,
             );
-            self.device.inner.cmd_bind_pipeline(
+            device.cmd_bind_pipeline(
                 self.command_buffer,
                 PipelineBindPoint::GRAPHICS,
-                self.graphics_pipeline.inner,
+                post_process.pipeline.inner,
             );
-            self.device.inner.cmd_draw(self.command_buffer, 3, 1, 0, 0);
-            self.device.inner.cmd_end_render_pass(self.command_buffer);
-            self.device
-                .inner
-                .end_command_buffer(self.command_buffer)
-                .unwrap();
+            device.cmd_set_viewport(
+                self.command_buffer,
+                0,
+                &[Viewport::builder()
+                    .width(extent.width as f32)
+                    .height(extent.height as f32)
+                    .max_depth(1.0)
+                    .build()],
+            );
+            device.cmd_set_scissor(self.command_buffer, 0, &[render_area]);
+            device.cmd_bind_descriptor_sets(
+                self.command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                post_process.pipeline.pipeline_layout,
+                0,
+                &[post_process.descriptor_set],
+                &[],
+            );
+            device.cmd_push_constants(
+                self.command_buffer,
+                post_process.pipeline.pipeline_layout,
+                ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT,
+                0,
+                &self.push_data,
+            );
+            device.cmd_draw(self.command_buffer, 3, 1, 0, 0);
+            device.cmd_end_render_pass(self.command_buffer);
         }
     }
 
@@ -195,6 +2306,467 @@ impl Renderer {
     }
 }
 
+/// Handles needed to draw the scene, copied to the recording threads.
+#[derive(Clone, Copy)]
+struct SceneDraw<'a> {
+    pipeline: Pipeline,
+    pipeline_layout: PipelineLayout,
+    descriptor_set: DescriptorSet,
+    /// Distance between two transforms in the dynamic uniform buffer.
+    object_stride: u32,
+    /// Bound as set 1 if the pipeline samples bindless textures.
+    bindless_set: Option<DescriptorSet>,
+    push_data: [u8; PUSH_CONSTANT_SIZE as usize],
+    vertex_buffer: ash::vk::Buffer,
+    instance_buffer: ash::vk::Buffer,
+    index_buffer: ash::vk::Buffer,
+    index_count: u32,
+    /// Set if the pipeline takes cull mode and front face per draw.
+    extended_dynamic_state: Option<&'a ExtendedDynamicState>,
+    object_face_culling: &'a [FaceCulling],
+    /// Set if the pipeline's depth bias is dynamic.
+    depth_bias: Option<DepthBias>,
+}
+
+impl SceneDraw<'_> {
+    fn record(
+        &self,
+        device: &ash::Device,
+        command_buffer: CommandBuffer,
+        extent: Extent2D,
+        objects: &[u32],
+        instances: Range<u32>,
+    ) {
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::GRAPHICS, self.pipeline);
+            device.cmd_set_viewport(
+                command_buffer,
+                0,
+                &[Viewport::builder()
+                    .width(extent.width as f32)
+                    .height(extent.height as f32)
+                    .max_depth(1.0)
+                    .build()],
+            );
+            device.cmd_set_scissor(
+                command_buffer,
+                0,
+                &[Rect2D::builder().extent(extent).build()],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT,
+                0,
+                &self.push_data,
+            );
+            device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[self.vertex_buffer, self.instance_buffer],
+                &[0, 0],
+            );
+            device.cmd_bind_index_buffer(command_buffer, self.index_buffer, 0, IndexType::UINT32);
+            if let Some(depth_bias) = self.depth_bias {
+                device.cmd_set_depth_bias(
+                    command_buffer,
+                    depth_bias.constant_factor,
+                    depth_bias.clamp,
+                    depth_bias.slope_factor,
+                );
+            }
+            let descriptor_sets: Vec<_> = std::iter::once(self.descriptor_set)
+                .chain(self.bindless_set)
+                .collect();
+            for object in objects {
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    PipelineBindPoint::GRAPHICS,
+                    self.pipeline_layout,
+                    0,
+                    &descriptor_sets,
+                    &[object * self.object_stride],
+                );
+                if let Some(extended_dynamic_state) = self.extended_dynamic_state {
+                    let face_culling = self
+                        .object_face_culling
+                        .get(*object as usize)
+                        .copied()
+                        .unwrap_or_default();
+                    extended_dynamic_state
+                        .cmd_set_cull_mode(command_buffer, face_culling.cull_mode);
+                    extended_dynamic_state
+                        .cmd_set_front_face(command_buffer, face_culling.front_face);
+                }
+                device.cmd_draw_indexed(
+                    command_buffer,
+                    self.index_count,
+                    instances.len() as u32,
+                    0,
+                    0,
+                    instances.start,
+                );
+            }
+        }
+    }
+}
+
+/// Everything created from the `Device`, rebuilt as a whole by `Renderer::recreate_device`.
+struct DeviceResources {
+    image_available_smph: Semaphore,
+    render_finished_smph: Semaphore,
+    in_flight_fence: Fence,
+    frame_timeline: Option<TimelineSemaphore>,
+    mesh: Mesh,
+    instance_buffer: Buffer,
+    texture: Texture,
+    sampler: Sampler,
+    descriptor_set: DescriptorSet,
+    descriptor_pool: DescriptorPool,
+    transient_descriptors: DescriptorAllocator,
+    bindless: Option<BindlessTextures>,
+    uniform_buffer: Buffer,
+    object_buffer: Buffer,
+    object_stride: u32,
+    gpu_timer: Option<GpuTimer>,
+    pipeline_stats: Option<PipelineStatsQuery>,
+    command_buffer: CommandBuffer,
+    command_pool: CommandPool,
+    recording_pools: Vec<(CommandPool, CommandBuffer)>,
+    line_pipeline: Option<PrimitivePipeline>,
+    point_pipeline: Option<PrimitivePipeline>,
+    deferred_lighting: Option<DeferredLighting>,
+    graphics_pipeline: GraphicsPipeline,
+    render_pass: RenderPass,
+    swap_chain: Option<SwapChain>,
+    offscreen: Option<OffscreenTarget>,
+    device: Device,
+}
+
+impl DeviceResources {
+    /// Renders to a swapchain if there's a surface, otherwise into an offscreen image of `extent`.
+    /// For a swapchain `extent` is the window size.
+    fn new(
+        instance: &Instance,
+        surface: Option<&Surface>,
+        extent: Extent2D,
+        config: &RendererConfig,
+    ) -> Result<Self, RendererError> {
+        let physical_device = PhysicalDevice::pick(instance, surface, config)?;
+        let mut requested_features = config.requested_features;
+        if config.require_geometry_shader {
+            requested_features.geometry_shader = ash::vk::TRUE;
+        }
+        if config.max_anisotropy > 1.0 && physical_device.features.sampler_anisotropy != 0 {
+            requested_features.sampler_anisotropy = ash::vk::TRUE;
+        }
+        if config.line_width > 1.0 && physical_device.features.wide_lines != 0 {
+            requested_features.wide_lines = ash::vk::TRUE;
+        }
+        if config.point_size > 1.0 && physical_device.features.large_points != 0 {
+            requested_features.large_points = ash::vk::TRUE;
+        }
+        if config.tessellation_level.is_some() && physical_device.features.tessellation_shader != 0
+        {
+            requested_features.tessellation_shader = ash::vk::TRUE;
+        }
+        if config.depth_clamp {
+            if physical_device.features.depth_clamp != 0 {
+                requested_features.depth_clamp = ash::vk::TRUE;
+            } else {
+                warn!("Depth clamp is not supported, clipping at the near and far planes");
+            }
+        }
+        let depth_bias_clamped = config.dynamic_depth_bias
+            || config
+                .depth_bias
+                .is_some_and(|depth_bias| depth_bias.clamp != 0.0);
+        if depth_bias_clamped && physical_device.features.depth_bias_clamp != 0 {
+            requested_features.depth_bias_clamp = ash::vk::TRUE;
+        }
+        // Unsupported features are filtered out by the device, PipelineStatsQuery warns about them.
+        if config.pipeline_statistics {
+            requested_features.pipeline_statistics_query = ash::vk::TRUE;
+            if config.recording_threads > 0 {
+                requested_features.inherited_queries = ash::vk::TRUE;
+            }
+        }
+        let device = Device::new(&instance.inner, physical_device, &requested_features)?;
+        let msaa_samples = config
+            .msaa_samples
+            .min(device.physical_device.max_usable_sample_count());
+        let depth_format = image::find_depth_stencil_format(&instance.inner, &device);
+        let deferred = config.deferred
+            && surface.is_some()
+            && !config.dynamic_rendering
+            && msaa_samples == SampleCountFlags::TYPE_1;
+        if config.deferred && !deferred {
+            warn!("Deferred shading needs a window, a render pass and no MSAA, shading forward");
+        }
+        let bindless = if config.bindless && deferred {
+            warn!("Bindless textures are not supported with deferred shading");
+            None
+        } else if config.bindless {
+            let bindless = BindlessTextures::new(&device);
+            if bindless.is_none() {
+                warn!("Descriptor indexing is not supported, using a single texture descriptor");
+            }
+            bindless
+        } else {
+            None
+        };
+        let mut pipeline_options = PipelineOptions::from_config(config);
+        pipeline_options.deferred = deferred;
+        pipeline_options.dynamic_face_culling = device.extended_dynamic_state.is_some();
+        pipeline_options.depth_clamp = device.enabled_features.depth_clamp != 0;
+        pipeline_options.depth_bias = config
+            .depth_bias
+            .map(|depth_bias| depth_bias.supported(&device));
+        pipeline_options.bindless_layout = bindless
+            .as_ref()
+            .map(|bindless| bindless.descriptor_set_layout);
+        pipeline_options.tessellation = config.tessellation_level.and_then(|level| {
+            if device.enabled_features.tessellation_shader == 0 {
+                warn!("Tessellation shaders are not supported, drawing the mesh untessellated");
+                return None;
+            }
+            let max_level = device
+                .physical_device
+                .properties
+                .limits
+                .max_tessellation_generation_level as f32;
+            Some(Tessellation {
+                patch_control_points: 3,
+                level: level.clamp(1.0, max_level),
+            })
+        });
+        let (swap_chain, offscreen, render_pass) = match surface {
+            Some(surface) => {
+                let mut swap_chain = SwapChain::new(
+                    instance,
+                    extent,
+                    surface,
+                    &device,
+                    config.present_mode,
+                    config,
+                )?;
+                let dynamic_rendering = config.dynamic_rendering
+                    && device.dynamic_rendering.is_some()
+                    && msaa_samples == SampleCountFlags::TYPE_1;
+                if config.dynamic_rendering && !dynamic_rendering {
+                    warn!("Dynamic rendering is not available, falling back to a render pass");
+                }
+                pipeline_options.dynamic_rendering = dynamic_rendering;
+                let render_pass = RenderPass::new_main(
+                    &device,
+                    swap_chain.surface_format.format,
+                    depth_format,
+                    msaa_samples,
+                    ImageLayout::PRESENT_SRC_KHR,
+                    &mut pipeline_options,
+                );
+                swap_chain.create_framebuffers(&device, &render_pass);
+                (Some(swap_chain), None, render_pass)
+            }
+            None => {
+                let render_pass = RenderPass::new_main(
+                    &device,
+                    HEADLESS_COLOR_FORMAT,
+                    depth_format,
+                    msaa_samples,
+                    ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    &mut pipeline_options,
+                );
+                let offscreen = OffscreenTarget::new(
+                    &device,
+                    extent,
+                    HEADLESS_COLOR_FORMAT,
+                    &render_pass,
+                    pipeline_options.color_initial_layout,
+                );
+                (None, Some(offscreen), render_pass)
+            }
+        };
+        let graphics_pipeline = if config.async_pipelines {
+            GraphicsPipeline::new_async(&device, &render_pass, 0, pipeline_options)
+        } else {
+            GraphicsPipeline::new(&device, &render_pass, 0, pipeline_options)
+        };
+        let deferred_lighting = deferred.then(|| {
+            let deferred_lighting = DeferredLighting::new(&device, &graphics_pipeline);
+            let gbuffer = &swap_chain.as_ref().unwrap().gbuffer;
+            deferred_lighting.write_gbuffer(gbuffer[0].view, gbuffer[1].view);
+            deferred_lighting
+        });
+        let primitive_pipeline = |topology| {
+            (!graphics_pipeline.uses_dynamic_rendering() && !deferred).then(|| {
+                PrimitivePipeline::new(
+                    &device,
+                    &graphics_pipeline,
+                    topology,
+                    config.line_width,
+                    config.point_size,
+                )
+            })
+        };
+        let line_pipeline = primitive_pipeline(PrimitiveTopology::LINE_LIST);
+        let point_pipeline = primitive_pipeline(PrimitiveTopology::POINT_LIST);
+        let mut command_pool = CommandPool::new(&device);
+        let command_buffer = command_pool.allocate(CommandBufferLevel::PRIMARY);
+        let recording_pools = (0..config.recording_threads)
+            .map(|_| {
+                let mut pool = CommandPool::new(&device);
+                let command_buffer = pool.allocate(CommandBufferLevel::SECONDARY);
+                (pool, command_buffer)
+            })
+            .collect();
+        let mesh = Mesh::new(&device, &TRIANGLE_VERTICES, &TRIANGLE_INDICES);
+        let instance_buffer = Buffer::new_device_local(
+            &device,
+            BufferUsageFlags::VERTEX_BUFFER,
+            bytemuck::cast_slice(&[InstanceData {
+                offset: [0.0, 0.0, 0.0],
+            }]),
+        );
+
+        let texture = Texture::from_rgba8(&instance.inner, &device, 1, 1, &[255; 4]);
+        let sampler = Sampler::new(&device, config.max_anisotropy);
+        let descriptor_pool = DescriptorPool::new(
+            &device,
+            &[
+                DescriptorPoolSize::builder()
+                    .ty(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .descriptor_count(1)
+                    .build(),
+                DescriptorPoolSize::builder()
+                    .ty(DescriptorType::UNIFORM_BUFFER)
+                    .descriptor_count(1)
+                    .build(),
+                DescriptorPoolSize::builder()
+                    .ty(DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+                    .descriptor_count(1)
+                    .build(),
+            ],
+            1,
+        );
+        let descriptor_set = descriptor_pool.allocate(graphics_pipeline.descriptor_set_layout);
+        Renderer::write_texture_descriptor(&device, descriptor_set, &texture, &sampler);
+        let mut bindless = bindless;
+        if let Some(bindless) = &mut bindless {
+            bindless.push(&texture, &sampler);
+        }
+        let transient_descriptors = DescriptorAllocator::new(&device);
+
+        let uniform_buffer = Buffer::new(
+            &device,
+            size_of::<Mat4>() as u64,
+            BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        );
+        // Dynamic offsets have to be multiples of the alignment, so every transform gets a slot of
+        // at least that size.
+        let object_stride = OBJECT_DATA_SIZE.next_multiple_of(
+            device
+                .physical_device
+                .properties
+                .limits
+                .min_uniform_buffer_offset_alignment,
+        );
+        let object_buffer = Buffer::new(
+            &device,
+            object_stride * MAX_OBJECTS as u64,
+            BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        );
+        let buffer_infos = [DescriptorBufferInfo::builder()
+            .buffer(uniform_buffer.inner)
+            .offset(0)
+            .range(WHOLE_SIZE)
+            .build()];
+        let object_buffer_infos = [DescriptorBufferInfo::builder()
+            .buffer(object_buffer.inner)
+            .offset(0)
+            .range(OBJECT_DATA_SIZE)
+            .build()];
+        let writes = [
+            WriteDescriptorSet::builder()
+                .dst_set(descriptor_set)
+                .dst_binding(1)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .buffer_info(&buffer_infos)
+                .build(),
+            WriteDescriptorSet::builder()
+                .dst_set(descriptor_set)
+                .dst_binding(2)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+                .buffer_info(&object_buffer_infos)
+                .build(),
+        ];
+        unsafe {
+            device.inner.update_descriptor_sets(&writes, &[]);
+        }
+
+        let gpu_timer = GpuTimer::new(&device);
+        let pipeline_stats = if !config.pipeline_statistics {
+            None
+        } else if config.recording_threads > 0 && device.enabled_features.inherited_queries == 0 {
+            warn!("Inherited queries are not supported, pipeline stats are disabled with recording threads");
+            None
+        } else {
+            PipelineStatsQuery::new(&device)
+        };
+
+        let smph_info = SemaphoreCreateInfo::builder();
+        let fence_info = FenceCreateInfo::builder().flags(FenceCreateFlags::SIGNALED);
+
+        let (image_available_smph, render_finished_smph, in_flight_fence) = unsafe {
+            (
+                device.inner.create_semaphore(&smph_info, None)?,
+                device.inner.create_semaphore(&smph_info, None)?,
+                device.inner.create_fence(&fence_info, None)?,
+            )
+        };
+        let frame_timeline = if config.timeline_semaphore && device.timeline_semaphore {
+            Some(TimelineSemaphore::new(&device)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            image_available_smph,
+            render_finished_smph,
+            in_flight_fence,
+            frame_timeline,
+            mesh,
+            instance_buffer,
+            texture,
+            sampler,
+            descriptor_set,
+            descriptor_pool,
+            transient_descriptors,
+            bindless,
+            uniform_buffer,
+            object_buffer,
+            object_stride: object_stride as u32,
+            gpu_timer,
+            pipeline_stats,
+            command_buffer,
+            command_pool,
+            recording_pools,
+            line_pipeline,
+            point_pipeline,
+            deferred_lighting,
+            graphics_pipeline,
+            render_pass,
+            swap_chain,
+            offscreen,
+            device,
+        })
+    }
+}
+
 impl Drop for Renderer {
     fn drop(&mut self) {
         unsafe {
@@ -208,3 +2780,41 @@ impl Drop for Renderer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 1x1 headless renderer with validation, `None` if the machine has no usable Vulkan driver
+    /// to test against.
+    pub(crate) fn headless_renderer() -> Option<Renderer> {
+        let config = RendererConfig {
+            validation: true,
+            ..RendererConfig::default()
+        };
+        match Renderer::new_headless(1, 1, config) {
+            Ok(renderer) => Some(renderer),
+            Err(
+                RendererError::NoVulkanDriver
+                | RendererError::NoSuitablePhysicalDevice
+                | RendererError::UnsupportedApiVersion { .. },
+            ) => None,
+            Err(err) => panic!("Failed to create a headless renderer: {}", err),
+        }
+    }
+
+    #[test]
+    fn headless_frame_has_no_validation_errors() {
+        let Some(mut renderer) = headless_renderer() else {
+            return;
+        };
+        renderer.draw_frame().unwrap();
+        renderer.read_pixels().unwrap();
+        assert_eq!(
+            renderer.validation_error_count(),
+            0,
+            "{:#?}",
+            renderer.take_validation_messages()
+        );
+    }
+}
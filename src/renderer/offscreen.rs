@@ -0,0 +1,108 @@
+use ash::vk::{
+    Extent2D, Format, Framebuffer, FramebufferCreateInfo, ImageLayout, ImageUsageFlags,
+    MemoryPropertyFlags, SampleCountFlags,
+};
+
+use super::{
+    device::Device,
+    image::{self, Image},
+    render_pass::RenderPass,
+};
+
+/// Color and depth-stencil images rendered into instead of a swapchain when running headless.
+pub struct OffscreenTarget {
+    pub framebuffer: Framebuffer,
+    pub image: Image,
+    /// Multisampled color attachment resolved into `image`, only present with MSAA.
+    pub color_image: Option<Image>,
+    pub depth_image: Image,
+    pub extent: Extent2D,
+    device: ash::Device,
+}
+
+impl OffscreenTarget {
+    /// `initial_layout` is the layout `render_pass` expects the color image in when it's begun.
+    pub fn new(
+        device: &Device,
+        extent: Extent2D,
+        format: Format,
+        render_pass: &RenderPass,
+        initial_layout: ImageLayout,
+    ) -> Self {
+        let image = Image::new(
+            device,
+            extent,
+            1,
+            SampleCountFlags::TYPE_1,
+            format,
+            ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::TRANSFER_SRC,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        let color_image = if render_pass.samples != SampleCountFlags::TYPE_1 {
+            Some(Image::new(
+                device,
+                extent,
+                1,
+                render_pass.samples,
+                format,
+                ImageUsageFlags::TRANSIENT_ATTACHMENT | ImageUsageFlags::COLOR_ATTACHMENT,
+                MemoryPropertyFlags::DEVICE_LOCAL,
+            ))
+        } else {
+            None
+        };
+
+        let depth_image = Image::new(
+            device,
+            extent,
+            1,
+            render_pass.samples,
+            render_pass.depth_format,
+            ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+
+        let mut attachments = match &color_image {
+            Some(color_image) => vec![color_image.view, image.view],
+            None => vec![image.view],
+        };
+        attachments.push(depth_image.view);
+        let create_info = FramebufferCreateInfo::builder()
+            .render_pass(render_pass.inner)
+            .attachments(&attachments)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1);
+        let framebuffer = unsafe { device.inner.create_framebuffer(&create_info, None).unwrap() };
+        // A pass loading the image expects it in its initial layout from the first frame on.
+        if initial_layout != ImageLayout::UNDEFINED {
+            device.one_time_submit(|command_buffer| {
+                image::transition_image_layout(
+                    &device.inner,
+                    command_buffer,
+                    image.inner,
+                    1,
+                    ImageLayout::UNDEFINED,
+                    initial_layout,
+                );
+            });
+        }
+
+        Self {
+            framebuffer,
+            image,
+            color_image,
+            depth_image,
+            extent,
+            device: device.inner.clone(),
+        }
+    }
+}
+
+impl Drop for OffscreenTarget {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_framebuffer(self.framebuffer, None);
+        }
+    }
+}
@@ -1,22 +1,40 @@
-use std::collections::HashSet;
-
-use ash::vk::{
-    Extent2D, PhysicalDeviceFeatures, PhysicalDeviceType, PresentModeKHR, QueueFamilyProperties,
-    QueueFlags, SurfaceCapabilitiesKHR, SurfaceFormatKHR,
+use std::{collections::HashSet, ffi::CStr};
+
+use ash::{
+    prelude::VkResult,
+    vk::{
+        CompositeAlphaFlagsKHR, Extent2D, MemoryHeapFlags, PhysicalDeviceFeatures,
+        PhysicalDeviceType, PresentModeKHR, QueueFamilyProperties, QueueFlags, SampleCountFlags,
+        SurfaceCapabilitiesKHR, SurfaceFormatKHR,
+    },
 };
-use winit::window::Window;
+use log::{info, warn};
 
 use super::{
+    config::RendererConfig,
     constants::{
         INSTANCE_API_VERSION, PHYSICAL_DEVICE_OPTIONAL_EXTENSION_NAMES,
         PHYSICAL_DEVICE_OPTIONAL_LAYER_NAMES, PHYSICAL_DEVICE_REQUIRED_EXTENSION_NAMES,
         PHYSICAL_DEVICE_REQUIRED_LAYER_NAMES,
     },
+    error::RendererError,
     instance::Instance,
     surface::Surface,
     utils::{extension::Extension, layer::Layer, properties::PhysicalDeviceProperties},
 };
 
+/// A device as reported by `PhysicalDevice::enumerate`.
+#[derive(Clone, Debug)]
+pub struct GpuCandidate {
+    pub inner: ash::vk::PhysicalDevice,
+    pub name: String,
+    pub device_type: PhysicalDeviceType,
+    /// Meets the renderer's requirements, only suitable devices can be picked.
+    pub suitable: bool,
+    /// See `PhysicalDevice::rate`, 0 if unsuitable.
+    pub score: u32,
+}
+
 pub struct PhysicalDevice {
     pub inner: ash::vk::PhysicalDevice,
     pub layers: Vec<Layer>,
@@ -25,24 +43,75 @@ pub struct PhysicalDevice {
     pub features: PhysicalDeviceFeatures,
     pub queue_family_properties: Vec<QueueFamilyProperties>,
     pub queue_family_indices: QueueFamiliesIndices,
-    pub swap_chain_support_details: SwapChainSupportDetails,
+    /// `None` when picked for headless rendering.
+    pub swap_chain_support_details: Option<SwapChainSupportDetails>,
 }
 
 impl PhysicalDevice {
-    pub fn pick(instance: &Instance, surface: &Surface) -> Self {
-        let available = unsafe { instance.inner.enumerate_physical_devices().unwrap() };
-        let suitable: Vec<(ash::vk::PhysicalDevice, u32)> = available
+    /// Rates every available device without creating any of them, e.g. for a GPU picker. Fails
+    /// with `NoVulkanDriver` if there are none.
+    pub fn enumerate(
+        instance: &Instance,
+        surface: Option<&Surface>,
+        config: &RendererConfig,
+    ) -> Result<Vec<GpuCandidate>, RendererError> {
+        let available = unsafe { instance.inner.enumerate_physical_devices()? };
+        if available.is_empty() {
+            return Err(RendererError::NoVulkanDriver);
+        }
+        Ok(available
             .into_iter()
-            .map(|x| (x, PhysicalDevice::rate(instance, &x, surface)))
-            .filter(|x| x.1.is_some())
-            .map(|x| (x.0, x.1.unwrap()))
-            .collect();
+            .map(|inner| {
+                let score = PhysicalDevice::rate(instance, &inner, surface, config);
+                let properties = unsafe { instance.inner.get_physical_device_properties(inner) };
+                let name = unsafe { CStr::from_ptr(properties.device_name.as_ptr()) };
+                GpuCandidate {
+                    inner,
+                    name: name.to_string_lossy().into_owned(),
+                    device_type: properties.device_type,
+                    suitable: score.is_some(),
+                    score: score.unwrap_or(0),
+                }
+            })
+            .collect())
+    }
 
-        let inner = suitable
-            .into_iter()
-            .max_by_key(|x| x.1)
-            .expect("No suitable GPU found!")
-            .0;
+    /// Picks the device at `config.gpu_index`, else the first suitable one matching
+    /// `config.preferred_gpu`, else the highest rated suitable one.
+    pub fn pick(
+        instance: &Instance,
+        surface: Option<&Surface>,
+        config: &RendererConfig,
+    ) -> Result<Self, RendererError> {
+        let candidates = PhysicalDevice::enumerate(instance, surface, config)?;
+
+        let selected = config
+            .gpu_index
+            .and_then(|index| match candidates.get(index) {
+                Some(candidate) if candidate.suitable => Some(candidate),
+                _ => {
+                    warn!(
+                        "GPU {} is missing or unsuitable, picking one instead",
+                        index
+                    );
+                    None
+                }
+            });
+        let preferred = config.preferred_gpu.as_ref().and_then(|preferred_gpu| {
+            candidates
+                .iter()
+                .find(|x| x.suitable && x.name.contains(preferred_gpu.as_str()))
+        });
+        let inner = selected
+            .or(preferred)
+            .or_else(|| {
+                candidates
+                    .iter()
+                    .filter(|x| x.suitable)
+                    .max_by_key(|x| x.score)
+            })
+            .ok_or(RendererError::NoSuitablePhysicalDevice)?
+            .inner;
 
         let extensions = Extension::convert_vec(unsafe {
             &instance
@@ -60,17 +129,22 @@ impl PhysicalDevice {
             unsafe { instance.inner.get_physical_device_properties(inner).into() };
 
         let features = unsafe { instance.inner.get_physical_device_features(inner) };
+        if config.max_anisotropy > 1.0 && features.sampler_anisotropy == 0 {
+            warn!("Sampler anisotropy is not supported, falling back to disabled anisotropy");
+        }
         let queue_family_properties = unsafe {
             instance
                 .inner
                 .get_physical_device_queue_family_properties(inner)
         };
         let queue_family_indices =
-            QueueFamiliesIndices::extract(surface, &inner, &queue_family_properties);
+            QueueFamiliesIndices::extract(surface, &inner, &queue_family_properties)?;
 
-        let swap_chain_support_details = SwapChainSupportDetails::extract(surface, inner);
+        let swap_chain_support_details = surface
+            .map(|surface| SwapChainSupportDetails::extract(surface, inner))
+            .transpose()?;
 
-        PhysicalDevice {
+        Ok(PhysicalDevice {
             inner,
             layers,
             extensions,
@@ -79,13 +153,40 @@ impl PhysicalDevice {
             queue_family_properties,
             queue_family_indices,
             swap_chain_support_details,
-        }
+        })
+    }
+
+    /// Sample counts both color and depth attachments support, the main pass multisamples both.
+    pub fn usable_sample_counts(&self) -> SampleCountFlags {
+        let limits = &self.properties.limits;
+        limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts
     }
 
+    /// Highest of `usable_sample_counts`.
+    pub fn max_usable_sample_count(&self) -> SampleCountFlags {
+        let counts = self.usable_sample_counts();
+        [
+            SampleCountFlags::TYPE_64,
+            SampleCountFlags::TYPE_32,
+            SampleCountFlags::TYPE_16,
+            SampleCountFlags::TYPE_8,
+            SampleCountFlags::TYPE_4,
+            SampleCountFlags::TYPE_2,
+        ]
+        .into_iter()
+        .find(|count| counts.contains(*count))
+        .unwrap_or(SampleCountFlags::TYPE_1)
+    }
+
+    /// Scores a suitable device, `None` if it can't be used.
+    ///
+    /// Weights: discrete GPU 1000, integrated or virtual 500, other 300, plus 10 per GiB of
+    /// `DEVICE_LOCAL` heap memory and 1 per 4096 texels of `max_image_dimension2_d` as a tiebreaker.
     fn rate(
         instance: &Instance,
         vkphysical_device: &ash::vk::PhysicalDevice,
-        surface: &Surface,
+        surface: Option<&Surface>,
+        config: &RendererConfig,
     ) -> Option<u32> {
         let mut score = 0;
 
@@ -96,13 +197,10 @@ impl PhysicalDevice {
                     .enumerate_device_layer_properties(*vkphysical_device)
                     .unwrap(),
             );
-            layers = layers
-                .into_iter()
-                .filter(|l| {
-                    PHYSICAL_DEVICE_OPTIONAL_LAYER_NAMES.contains(&l.name)
-                        || PHYSICAL_DEVICE_REQUIRED_LAYER_NAMES.contains(&l.name)
-                })
-                .collect();
+            layers.retain(|l| {
+                PHYSICAL_DEVICE_OPTIONAL_LAYER_NAMES.contains(&l.name)
+                    || PHYSICAL_DEVICE_REQUIRED_LAYER_NAMES.contains(&l.name)
+            });
             for required in &*PHYSICAL_DEVICE_REQUIRED_LAYER_NAMES {
                 let mut is_available = false;
                 for layer in &layers {
@@ -121,13 +219,10 @@ impl PhysicalDevice {
                     .enumerate_device_extension_properties(*vkphysical_device)
                     .unwrap(),
             );
-            extensions = extensions
-                .into_iter()
-                .filter(|l| {
-                    PHYSICAL_DEVICE_OPTIONAL_EXTENSION_NAMES.contains(&l.name)
-                        || PHYSICAL_DEVICE_REQUIRED_EXTENSION_NAMES.contains(&l.name)
-                })
-                .collect();
+            extensions.retain(|l| {
+                PHYSICAL_DEVICE_OPTIONAL_EXTENSION_NAMES.contains(&l.name)
+                    || PHYSICAL_DEVICE_REQUIRED_EXTENSION_NAMES.contains(&l.name)
+            });
             for required in &*PHYSICAL_DEVICE_REQUIRED_EXTENSION_NAMES {
                 let mut is_available = false;
                 for extension in &extensions {
@@ -144,7 +239,7 @@ impl PhysicalDevice {
             let features = instance
                 .inner
                 .get_physical_device_features(*vkphysical_device);
-            if features.geometry_shader == 0 {
+            if config.require_geometry_shader && features.geometry_shader == 0 {
                 return None;
             }
 
@@ -152,16 +247,18 @@ impl PhysicalDevice {
                 .inner
                 .get_physical_device_queue_family_properties(*vkphysical_device);
             let queue_family_indices =
-                QueueFamiliesIndices::extract(surface, vkphysical_device, &queue_family_properties);
+                QueueFamiliesIndices::extract(surface, vkphysical_device, &queue_family_properties)
+                    .ok()?;
 
-            if queue_family_indices.graphics_family.is_none()
-                || queue_family_indices.present_family.is_none()
-            {
-                return None;
-            }
+            queue_family_indices.graphics_family?;
 
-            if !SwapChainSupportDetails::extract(surface, *vkphysical_device).is_suitable() {
-                return None;
+            if let Some(surface) = surface {
+                // A device whose surface queries fail can't present to it either.
+                let suitable = SwapChainSupportDetails::extract(surface, *vkphysical_device)
+                    .is_ok_and(|details| details.is_suitable());
+                if queue_family_indices.present_family.is_none() || !suitable {
+                    return None;
+                }
             }
 
             let properties = instance
@@ -179,6 +276,18 @@ impl PhysicalDevice {
                 PhysicalDeviceType::OTHER => score += 300,
                 _ => {}
             }
+
+            let memory_properties = instance
+                .inner
+                .get_physical_device_memory_properties(*vkphysical_device);
+            let device_local_bytes: u64 = memory_properties.memory_heaps
+                [..memory_properties.memory_heap_count as usize]
+                .iter()
+                .filter(|heap| heap.flags.contains(MemoryHeapFlags::DEVICE_LOCAL))
+                .map(|heap| heap.size)
+                .sum();
+            score += (device_local_bytes >> 30) as u32 * 10;
+            score += properties.limits.max_image_dimension2_d / 4096;
         }
 
         Some(score)
@@ -189,51 +298,82 @@ impl PhysicalDevice {
 pub struct QueueFamiliesIndices {
     pub graphics_family: Option<u32>,
     pub present_family: Option<u32>,
+    /// Family with transfer but without graphics support, preferring one without compute as well.
+    pub transfer_family: Option<u32>,
 }
 
 impl QueueFamiliesIndices {
+    /// Without a surface no present family is looked for. Fails with `ERROR_SURFACE_LOST_KHR` if
+    /// the surface went away.
     pub fn extract(
-        surface: &Surface,
+        surface: Option<&Surface>,
         vkphysical_device: &ash::vk::PhysicalDevice,
-        properties: &Vec<QueueFamilyProperties>,
-    ) -> Self {
+        properties: &[QueueFamilyProperties],
+    ) -> VkResult<Self> {
         let mut graphics_family = None;
         let mut present_family = None;
+        let mut combined_family = None;
+        let mut transfer_family = None;
+        let mut dedicated_transfer_family = None;
 
         for (index, property) in properties.iter().enumerate() {
+            if property.queue_flags.contains(QueueFlags::TRANSFER)
+                && !property.queue_flags.contains(QueueFlags::GRAPHICS)
+            {
+                if !property.queue_flags.contains(QueueFlags::COMPUTE)
+                    && dedicated_transfer_family.is_none()
+                {
+                    dedicated_transfer_family = Some(index as u32);
+                }
+                if transfer_family.is_none() {
+                    transfer_family = Some(index as u32);
+                }
+            }
+
             if property.queue_flags.contains(QueueFlags::GRAPHICS) && graphics_family.is_none() {
                 graphics_family = Some(index as u32);
             }
 
-            if present_family.is_none()
-                && unsafe {
-                    surface
-                        .loader
-                        .get_physical_device_surface_support(
-                            *vkphysical_device,
-                            index as u32,
-                            surface.inner,
-                        )
-                        .unwrap()
+            if let Some(surface) = surface {
+                let supports_present = unsafe {
+                    surface.loader.get_physical_device_surface_support(
+                        *vkphysical_device,
+                        index as u32,
+                        surface.inner,
+                    )?
+                };
+                if supports_present {
+                    present_family.get_or_insert(index as u32);
+                    // One family doing both allows EXCLUSIVE sharing of the swapchain images.
+                    if property.queue_flags.contains(QueueFlags::GRAPHICS) {
+                        combined_family.get_or_insert(index as u32);
+                    }
                 }
-            {
-                present_family = Some(index as u32);
             }
         }
 
-        Self {
+        if let Some(combined_family) = combined_family {
+            graphics_family = Some(combined_family);
+            present_family = Some(combined_family);
+        }
+
+        Ok(Self {
             graphics_family,
             present_family,
-        }
+            transfer_family: dedicated_transfer_family.or(transfer_family),
+        })
     }
 
     pub fn get_unique_indices(&self) -> Vec<u32> {
         let mut result = Vec::new();
-        if self.graphics_family.is_some() {
-            result.push(self.graphics_family.unwrap())
+        if let Some(graphics_family) = self.graphics_family {
+            result.push(graphics_family)
+        }
+        if let Some(present_family) = self.present_family {
+            result.push(present_family)
         }
-        if self.present_family.is_some() {
-            result.push(self.present_family.unwrap())
+        if let Some(transfer_family) = self.transfer_family {
+            result.push(transfer_family)
         }
         let mut unique = HashSet::new();
         result.retain(|i| unique.insert(*i));
@@ -248,26 +388,27 @@ pub struct SwapChainSupportDetails {
 }
 
 impl SwapChainSupportDetails {
-    pub fn extract(surface: &Surface, vkphysical_device: ash::vk::PhysicalDevice) -> Self {
+    /// Fails with `ERROR_SURFACE_LOST_KHR` if the surface went away.
+    pub fn extract(
+        surface: &Surface,
+        vkphysical_device: ash::vk::PhysicalDevice,
+    ) -> VkResult<Self> {
         unsafe {
             let surface_capabilities = surface
                 .loader
-                .get_physical_device_surface_capabilities(vkphysical_device, surface.inner)
-                .unwrap();
+                .get_physical_device_surface_capabilities(vkphysical_device, surface.inner)?;
             let formats = surface
                 .loader
-                .get_physical_device_surface_formats(vkphysical_device, surface.inner)
-                .unwrap();
+                .get_physical_device_surface_formats(vkphysical_device, surface.inner)?;
             let present_modes = surface
                 .loader
-                .get_physical_device_surface_present_modes(vkphysical_device, surface.inner)
-                .unwrap();
+                .get_physical_device_surface_present_modes(vkphysical_device, surface.inner)?;
 
-            Self {
+            Ok(Self {
                 surface_capabilities,
                 formats,
                 present_modes,
-            }
+            })
         }
     }
 
@@ -275,41 +416,178 @@ impl SwapChainSupportDetails {
         !self.formats.is_empty() && !self.present_modes.is_empty()
     }
 
-    pub fn choose_format(&self) -> SurfaceFormatKHR {
-        for available in &self.formats {
-            if available.format == ash::vk::Format::B8G8R8A8_SRGB
-                && available.color_space == ash::vk::ColorSpaceKHR::SRGB_NONLINEAR
-            {
-                return *available;
+    /// The first of `preferred` the surface supports, otherwise the first supported format.
+    pub fn choose_format(&self, preferred: &[SurfaceFormatKHR]) -> SurfaceFormatKHR {
+        for preferred in preferred {
+            if self.formats.contains(preferred) {
+                info!(
+                    "Using surface format {:?} {:?}",
+                    preferred.format, preferred.color_space
+                );
+                return *preferred;
             }
         }
-        self.formats[0]
+        let fallback = self.formats[0];
+        info!(
+            "No preferred surface format is supported, using {:?} {:?}",
+            fallback.format, fallback.color_space
+        );
+        fallback
     }
 
-    pub fn choose_present_mode(&self) -> ash::vk::PresentModeKHR {
+    /// `preferred` if supported, otherwise MAILBOX, falling back to the always available FIFO.
+    pub fn choose_present_mode(
+        &self,
+        preferred: Option<ash::vk::PresentModeKHR>,
+    ) -> ash::vk::PresentModeKHR {
+        if let Some(preferred) = preferred {
+            if self.present_modes.contains(&preferred) {
+                info!("Using present mode {:?} as requested", preferred);
+                return preferred;
+            }
+            info!("Requested present mode {:?} is not supported", preferred);
+        }
         for available in &self.present_modes {
             if *available == ash::vk::PresentModeKHR::MAILBOX {
+                info!("Using present mode MAILBOX");
                 return *available;
             }
         }
+        info!("MAILBOX is not supported, falling back to FIFO (VSync)");
         ash::vk::PresentModeKHR::FIFO
     }
 
-    pub fn choose_swap_extent(&self, window: &Window) -> Extent2D {
-        if self.surface_capabilities.current_extent.width != u32::MAX {
-            return self.surface_capabilities.current_extent;
+    /// `window_size` clamped to the extents the surface supports. `window_size` is in physical
+    /// pixels, like `Window::inner_size`, so the swapchain covers every pixel on HiDPI displays.
+    /// Logical sizes would shrink it by the scale factor. Platforms with a fixed `current_extent`
+    /// usually report it as both bounds, elsewhere the window size can't fall out of sync with
+    /// the surface after a resize.
+    pub fn choose_swap_extent(&self, window_size: Extent2D) -> Extent2D {
+        let extent = clamp_extent(
+            window_size,
+            self.surface_capabilities.min_image_extent,
+            self.surface_capabilities.max_image_extent,
+        );
+        if extent != window_size {
+            warn!(
+                "Swapchain extent {}x{} differs from the window's physical size {}x{}",
+                extent.width, extent.height, window_size.width, window_size.height
+            );
+        }
+        extent
+    }
+
+    /// `requested` if supported, otherwise the first supported mode in the order OPAQUE,
+    /// PRE_MULTIPLIED, POST_MULTIPLIED and INHERIT.
+    pub fn choose_composite_alpha(
+        &self,
+        requested: CompositeAlphaFlagsKHR,
+    ) -> CompositeAlphaFlagsKHR {
+        let supported = self.surface_capabilities.supported_composite_alpha;
+        if supported.contains(requested) {
+            return requested;
         }
+        let fallback = [
+            CompositeAlphaFlagsKHR::OPAQUE,
+            CompositeAlphaFlagsKHR::PRE_MULTIPLIED,
+            CompositeAlphaFlagsKHR::POST_MULTIPLIED,
+            CompositeAlphaFlagsKHR::INHERIT,
+        ]
+        .into_iter()
+        .find(|&mode| supported.contains(mode))
+        .unwrap_or(CompositeAlphaFlagsKHR::OPAQUE);
+        warn!(
+            "Composite alpha {:?} is not supported (supported: {:?}), using {:?}",
+            requested, supported, fallback
+        );
+        fallback
+    }
 
-        let inner_size = window.inner_size();
-        Extent2D {
-            width: inner_size.width.clamp(
-                self.surface_capabilities.min_image_extent.width,
-                self.surface_capabilities.max_image_extent.width,
-            ),
-            height: inner_size.height.clamp(
-                self.surface_capabilities.min_image_extent.height,
-                self.surface_capabilities.max_image_extent.height,
-            ),
+    /// `desired` or one more than the minimum, clamped to the supported range. A maximum of 0
+    /// means there is no upper limit.
+    pub fn choose_image_count(&self, desired: Option<u32>) -> u32 {
+        let min = self.surface_capabilities.min_image_count;
+        let max = match self.surface_capabilities.max_image_count {
+            0 => u32::MAX,
+            max => max,
+        };
+        desired.unwrap_or(min + 1).clamp(min, max)
+    }
+}
+
+/// `extent` clamped to `[min, max]` on each axis.
+fn clamp_extent(extent: Extent2D, min: Extent2D, max: Extent2D) -> Extent2D {
+    Extent2D {
+        width: extent.width.clamp(min.width, max.width),
+        height: extent.height.clamp(min.height, max.height),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN: Extent2D = Extent2D {
+        width: 16,
+        height: 8,
+    };
+    const MAX: Extent2D = Extent2D {
+        width: 4096,
+        height: 2048,
+    };
+
+    fn extent(width: u32, height: u32) -> Extent2D {
+        Extent2D { width, height }
+    }
+
+    fn support_details(current_extent: Extent2D) -> SwapChainSupportDetails {
+        SwapChainSupportDetails {
+            surface_capabilities: SurfaceCapabilitiesKHR {
+                current_extent,
+                min_image_extent: MIN,
+                max_image_extent: MAX,
+                ..Default::default()
+            },
+            formats: Vec::new(),
+            present_modes: Vec::new(),
         }
     }
+
+    #[test]
+    fn swap_extent_matches_physical_window_size() {
+        // A 1280x800 logical window at a scale factor of 2.
+        let physical_size = extent(2560, 1600);
+        let logical_size = extent(1280, 800);
+        let details = support_details(logical_size);
+        assert_eq!(details.choose_swap_extent(physical_size), physical_size);
+        let details = support_details(extent(u32::MAX, u32::MAX));
+        assert_eq!(
+            details.choose_swap_extent(extent(1280, 720)),
+            extent(1280, 720)
+        );
+    }
+
+    #[test]
+    fn clamp_extent_keeps_sizes_in_range() {
+        assert_eq!(clamp_extent(extent(1280, 720), MIN, MAX), extent(1280, 720));
+        assert_eq!(clamp_extent(MIN, MIN, MAX), MIN);
+        assert_eq!(clamp_extent(MAX, MIN, MAX), MAX);
+    }
+
+    #[test]
+    fn clamp_extent_raises_sizes_below_min() {
+        assert_eq!(clamp_extent(extent(0, 0), MIN, MAX), MIN);
+        assert_eq!(clamp_extent(extent(15, 720), MIN, MAX), extent(16, 720));
+        assert_eq!(clamp_extent(extent(1280, 7), MIN, MAX), extent(1280, 8));
+    }
+
+    #[test]
+    fn clamp_extent_lowers_sizes_above_max() {
+        assert_eq!(clamp_extent(extent(u32::MAX, u32::MAX), MIN, MAX), MAX);
+        assert_eq!(clamp_extent(extent(4097, 720), MIN, MAX), extent(4096, 720));
+        assert_eq!(
+            clamp_extent(extent(1280, 2049), MIN, MAX),
+            extent(1280, 2048)
+        );
+    }
 }
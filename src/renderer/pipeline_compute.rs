@@ -0,0 +1,146 @@
+use std::ffi::CString;
+
+use ash::vk::{
+    BufferUsageFlags, ComputePipelineCreateInfo, DescriptorBufferInfo, DescriptorPoolSize,
+    DescriptorSet, DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo,
+    DescriptorType, DeviceSize, MemoryPropertyFlags, PipelineCache, PipelineLayout,
+    PipelineLayoutCreateInfo, PipelineShaderStageCreateInfo, PushConstantRange, ShaderStageFlags,
+    WriteDescriptorSet, WHOLE_SIZE,
+};
+
+use super::{
+    buffer::Buffer, constants::PUSH_CONSTANT_SIZE, descriptor::DescriptorPool, device::Device,
+    reflect, shader_module::ShaderModule,
+};
+
+pub struct ComputePipeline {
+    pub inner: ash::vk::Pipeline,
+    pub pipeline_layout: PipelineLayout,
+    pub descriptor_set_layout: DescriptorSetLayout,
+    pub device: ash::Device,
+}
+
+impl ComputePipeline {
+    /// `bindings` describe the single descriptor set of the shader, e.g. storage and uniform buffers.
+    pub fn new(
+        device: &Device,
+        shader_module: &ShaderModule,
+        bindings: &[DescriptorSetLayoutBinding],
+    ) -> Self {
+        let p_name = CString::new("main").unwrap();
+        let stage_create_info = PipelineShaderStageCreateInfo::builder()
+            .stage(ShaderStageFlags::COMPUTE)
+            .module(shader_module.inner)
+            .name(&p_name);
+
+        let descriptor_set_layout_create_info =
+            DescriptorSetLayoutCreateInfo::builder().bindings(bindings);
+        let descriptor_set_layout = unsafe {
+            device
+                .inner
+                .create_descriptor_set_layout(&descriptor_set_layout_create_info, None)
+                .unwrap()
+        };
+
+        let push_constant_ranges = [PushConstantRange::builder()
+            .stage_flags(ShaderStageFlags::COMPUTE)
+            .offset(0)
+            .size(PUSH_CONSTANT_SIZE)
+            .build()];
+        let set_layouts = [descriptor_set_layout];
+        let pipeline_layout_create_info = PipelineLayoutCreateInfo::builder()
+            .set_layouts(&set_layouts)
+            .push_constant_ranges(&push_constant_ranges);
+        let pipeline_layout = unsafe {
+            device
+                .inner
+                .create_pipeline_layout(&pipeline_layout_create_info, None)
+                .unwrap()
+        };
+
+        let create_infos = [ComputePipelineCreateInfo::builder()
+            .stage(stage_create_info.build())
+            .layout(pipeline_layout)
+            .build()];
+        let inner = unsafe {
+            device
+                .inner
+                .create_compute_pipelines(PipelineCache::null(), &create_infos, None)
+                .unwrap()[0]
+        };
+
+        Self {
+            inner,
+            pipeline_layout,
+            descriptor_set_layout,
+            device: device.inner.clone(),
+        }
+    }
+}
+
+impl Drop for ComputePipeline {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_pipeline(self.inner, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+/// Compute shader with a storage buffer at binding 0. The buffer can also be bound as a vertex buffer.
+pub struct ComputePass {
+    pub descriptor_set: DescriptorSet,
+    pub descriptor_pool: DescriptorPool,
+    pub storage_buffer: Buffer,
+    pub pipeline: ComputePipeline,
+}
+
+impl ComputePass {
+    pub fn new(device: &Device, code: &[u8], storage_buffer_size: DeviceSize) -> Self {
+        let shader_module = ShaderModule::new(device, code);
+        let bindings = reflect::layout_bindings(&[&shader_module.reflection], 0);
+        let pipeline = ComputePipeline::new(device, &shader_module, &bindings);
+
+        let storage_buffer = Buffer::new(
+            device,
+            storage_buffer_size,
+            BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::VERTEX_BUFFER,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        let descriptor_pool = DescriptorPool::new(
+            device,
+            &[DescriptorPoolSize::builder()
+                .ty(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .build()],
+            1,
+        );
+        let descriptor_set = descriptor_pool.allocate(pipeline.descriptor_set_layout);
+
+        let buffer_infos = [DescriptorBufferInfo::builder()
+            .buffer(storage_buffer.inner)
+            .offset(0)
+            .range(WHOLE_SIZE)
+            .build()];
+        let writes = [WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(DescriptorType::STORAGE_BUFFER)
+            .buffer_info(&buffer_infos)
+            .build()];
+        unsafe {
+            device.inner.update_descriptor_sets(&writes, &[]);
+        }
+
+        Self {
+            descriptor_set,
+            descriptor_pool,
+            storage_buffer,
+            pipeline,
+        }
+    }
+}
@@ -1,184 +1,773 @@
-use std::ffi::CString;
+use std::{ffi::CString, thread::JoinHandle};
 
 use ash::vk::{
-    AccessFlags, AttachmentDescription, AttachmentLoadOp, AttachmentReference, AttachmentStoreOp,
-    ColorComponentFlags, CullModeFlags, FrontFace, GraphicsPipelineCreateInfo, ImageLayout,
-    Offset2D, PipelineBindPoint, PipelineCache, PipelineColorBlendAttachmentState,
-    PipelineColorBlendStateCreateInfo, PipelineInputAssemblyStateCreateInfo, PipelineLayout,
+    AttachmentLoadOp, CommandBuffer, CompareOp, CullModeFlags, DescriptorSetLayout,
+    DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType, DynamicState,
+    Format, FrontFace, GraphicsPipelineCreateInfo, ImageLayout, PipelineCache,
+    PipelineColorBlendStateCreateInfo, PipelineCreateFlags, PipelineDepthStencilStateCreateInfo,
+    PipelineDynamicStateCreateInfo, PipelineInputAssemblyStateCreateInfo, PipelineLayout,
     PipelineLayoutCreateInfo, PipelineMultisampleStateCreateInfo,
-    PipelineRasterizationStateCreateInfo, PipelineShaderStageCreateInfo, PipelineStageFlags,
+    PipelineRasterizationStateCreateInfo, PipelineRenderingCreateInfo,
+    PipelineShaderStageCreateInfo, PipelineTessellationStateCreateInfo,
     PipelineVertexInputStateCreateInfo, PipelineViewportStateCreateInfo, PolygonMode,
-    PrimitiveTopology, Rect2D, RenderPass, RenderPassCreateInfo, SampleCountFlags,
-    ShaderStageFlags, SubpassDependency, SubpassDescription, Viewport,
+    PrimitiveTopology, PushConstantRange, SampleCountFlags, ShaderStageFlags, SpecializationInfo,
+    SpecializationMapEntry, StencilOpState, SubpassContents,
 };
+use log::warn;
 
-use super::{device::Device, shader_module::ShaderModule, swapchain::SwapChain};
+use super::{
+    blend::BlendMode,
+    config::RendererConfig,
+    constants::PUSH_CONSTANT_SIZE,
+    device::Device,
+    reflect::{self, ShaderReflection},
+    render_pass::RenderPass,
+    shader_module::ShaderModule,
+    vertex::{InstanceData, Vertex},
+};
+
+/// Optional state of the main pipeline.
+#[derive(Clone, Copy, Debug)]
+pub struct PipelineOptions {
+    /// Defaults to `TRIANGLE_LIST`. The vertex shader writes `gl_PointSize` for `POINT_LIST`.
+    pub topology: PrimitiveTopology,
+    pub blend_mode: BlendMode,
+    /// Stencil test for both faces, disabled if `None`. See the `stencil` module for presets.
+    pub stencil: Option<StencilOpState>,
+    /// Create the pipeline for `VK_KHR_dynamic_rendering`, without a render pass.
+    pub dynamic_rendering: bool,
+    /// Subdivide the mesh's triangles, the topology is `PATCH_LIST` then. Needs the
+    /// `tessellation_shader` feature.
+    pub tessellation: Option<Tessellation>,
+    /// Layout of a `BindlessTextures` set, bound as set 1. The fragment shader then samples the
+    /// texture of the object's texture index from it instead of binding 0.
+    pub bindless_layout: Option<DescriptorSetLayout>,
+    /// Load op of the color attachment. `LOAD` falls back to `CLEAR` with MSAA, the resolved
+    /// image can't be loaded into the multisampled one.
+    pub color_load_op: AttachmentLoadOp,
+    /// Layout of the color image when the pass begins. Replaced by the final layout if it's
+    /// `UNDEFINED` with `LOAD`, which would discard the contents.
+    pub color_initial_layout: ImageLayout,
+    /// Write albedo and normal to a G-buffer in subpass 0 and shade them in a second subpass with
+    /// `DeferredLighting`. Needs a single sample and a render pass.
+    pub deferred: bool,
+    /// Let `GraphicsPipeline::derive` create variants of this pipeline, which the driver can
+    /// compile faster by sharing work with it.
+    pub allow_derivatives: bool,
+    /// Leave cull mode and front face to `FaceCulling` set per draw. Needs
+    /// `VK_EXT_extended_dynamic_state`, otherwise `FaceCulling::default()` is baked in.
+    pub dynamic_face_culling: bool,
+    /// Clamp fragment depths to the viewport's depth range instead of clipping geometry against
+    /// the near and far planes. Needs the `depth_clamp` feature.
+    pub depth_clamp: bool,
+    /// Depth offset baked into the pipeline, e.g. against shadow acne. Ignored with
+    /// `dynamic_depth_bias`.
+    pub depth_bias: Option<DepthBias>,
+    /// Leave the depth bias to `cmd_set_depth_bias` at draw time, see `Renderer::set_depth_bias`.
+    pub dynamic_depth_bias: bool,
+}
+
+/// Patches split by the built-in tessellation shaders.
+#[derive(Clone, Copy, Debug)]
+pub struct Tessellation {
+    /// Vertices per patch, the built-in shaders take triangles so this is 3.
+    pub patch_control_points: u32,
+    /// Segments every edge and the inside of a patch are split into, specialized in the control
+    /// shader.
+    pub level: f32,
+}
+
+/// Which faces of an object are culled and which winding is front facing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FaceCulling {
+    pub cull_mode: CullModeFlags,
+    pub front_face: FrontFace,
+}
+
+/// Offset added to each fragment's depth, `constant_factor` in units of the depth format's
+/// resolution plus `slope_factor` times the polygon's depth slope.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DepthBias {
+    pub constant_factor: f32,
+    pub slope_factor: f32,
+    /// Largest (or smallest, if negative) offset applied, 0.0 for no limit. Other values need the
+    /// `depth_bias_clamp` feature.
+    pub clamp: f32,
+}
+
+impl DepthBias {
+    /// Drops the clamp if the device doesn't have the `depth_bias_clamp` feature enabled.
+    pub fn supported(self, device: &Device) -> Self {
+        if self.clamp != 0.0 && device.enabled_features.depth_bias_clamp == 0 {
+            warn!("Depth bias clamp is not supported, leaving the depth bias unclamped");
+            return Self { clamp: 0.0, ..self };
+        }
+        self
+    }
+}
+
+impl Default for FaceCulling {
+    /// The state baked into the main pipeline, clockwise back faces are culled.
+    fn default() -> Self {
+        Self {
+            cull_mode: CullModeFlags::BACK,
+            front_face: FrontFace::CLOCKWISE,
+        }
+    }
+}
+
+impl PipelineOptions {
+    /// Options set in `config`, without dynamic rendering.
+    pub fn from_config(config: &RendererConfig) -> Self {
+        Self {
+            topology: config.topology,
+            blend_mode: config.blend_mode,
+            stencil: config.stencil,
+            dynamic_rendering: false,
+            tessellation: None,
+            bindless_layout: None,
+            color_load_op: config.color_load_op,
+            color_initial_layout: config.color_initial_layout,
+            deferred: config.deferred,
+            allow_derivatives: false,
+            dynamic_face_culling: false,
+            depth_clamp: config.depth_clamp,
+            depth_bias: config.depth_bias,
+            dynamic_depth_bias: config.dynamic_depth_bias,
+        }
+    }
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self {
+        Self {
+            topology: PrimitiveTopology::TRIANGLE_LIST,
+            blend_mode: BlendMode::default(),
+            stencil: None,
+            dynamic_rendering: false,
+            tessellation: None,
+            bindless_layout: None,
+            color_load_op: AttachmentLoadOp::CLEAR,
+            color_initial_layout: ImageLayout::UNDEFINED,
+            deferred: false,
+            allow_derivatives: false,
+            dynamic_face_culling: false,
+            depth_clamp: false,
+            depth_bias: None,
+            dynamic_depth_bias: false,
+        }
+    }
+}
 
 pub struct GraphicsPipeline {
+    /// Null while a pipeline created with `new_async` compiles, see `poll`.
     pub inner: ash::vk::Pipeline,
+    /// Compile thread of `new_async`, joined by `poll`.
+    pub pending: Option<JoinHandle<ash::vk::Pipeline>>,
+    /// Options the pipeline was created with, defaults for the fullscreen and UI pipelines.
+    pub options: PipelineOptions,
     pub pipeline_layout: PipelineLayout,
-    pub render_pass: RenderPass,
+    pub descriptor_set_layout: DescriptorSetLayout,
+    /// Owned by a `RenderPass`, which may be shared with other pipelines.
+    pub render_pass: ash::vk::RenderPass,
+    /// Subpass of `render_pass` the pipeline draws in.
+    pub subpass: u32,
+    /// Subpasses of `render_pass`, each one has to be begun before the pass ends.
+    pub subpass_count: u32,
+    pub samples: SampleCountFlags,
+    /// Format of the depth-stencil attachment of `render_pass`.
+    pub depth_format: Format,
     pub device: ash::Device,
 }
 
 impl GraphicsPipeline {
-    pub fn new(device: &Device, swapchain: &SwapChain) -> Self {
-        let attachment_description = AttachmentDescription::builder()
-            .format(swapchain.surface_format.format)
-            .samples(SampleCountFlags::TYPE_1)
-            .load_op(AttachmentLoadOp::CLEAR)
-            .store_op(AttachmentStoreOp::STORE)
-            .initial_layout(ImageLayout::UNDEFINED)
-            .final_layout(ImageLayout::PRESENT_SRC_KHR);
-
-        let attachment_reference = AttachmentReference::builder()
-            .attachment(0)
-            .layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
-
-        let color_attachment_refs = [attachment_reference.build()];
-        let subpass_description = SubpassDescription::builder()
-            .pipeline_bind_point(PipelineBindPoint::GRAPHICS)
-            .color_attachments(&color_attachment_refs);
-
-        let color_attachments = [attachment_description.build()];
-
-        let subpass_dependency = SubpassDependency::builder()
-            .src_subpass(ash::vk::SUBPASS_EXTERNAL)
-            .dst_subpass(0)
-            .src_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-            .dst_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-            .dst_access_mask(AccessFlags::COLOR_ATTACHMENT_WRITE);
-
-        let subpass_dependencies = [subpass_dependency.build()];
-        let subpasses = [subpass_description.build()];
-        let render_pass_create_info = RenderPassCreateInfo::builder()
-            .attachments(&color_attachments)
-            .subpasses(&subpasses)
-            .dependencies(&subpass_dependencies);
-
-        let render_pass = unsafe {
+    /// Creates the pipeline for subpass `subpass` of `render_pass`, which the pipeline doesn't
+    /// own and can be shared with other pipelines. With dynamic rendering the pipeline uses the
+    /// formats of a `RenderPass::dynamic`.
+    pub fn new(
+        device: &Device,
+        render_pass: &RenderPass,
+        subpass: u32,
+        options: PipelineOptions,
+    ) -> Self {
+        Self::create(device, render_pass, subpass, options, None, false)
+    }
+
+    /// Like `new`, but compiles the pipeline on a worker thread. The layouts can be used right
+    /// away, `inner` only once `poll` returns true.
+    pub fn new_async(
+        device: &Device,
+        render_pass: &RenderPass,
+        subpass: u32,
+        options: PipelineOptions,
+    ) -> Self {
+        Self::create(device, render_pass, subpass, options, None, true)
+    }
+
+    /// Whether the compilation started by `new_async` has finished.
+    pub fn is_ready(&self) -> bool {
+        self.pending
+            .as_ref()
+            .is_none_or(|pending| pending.is_finished())
+    }
+
+    /// Takes over the compiled pipeline once it's ready. Returns whether `inner` can be bound.
+    pub fn poll(&mut self) -> bool {
+        if self.is_ready() {
+            if let Some(pending) = self.pending.take() {
+                self.inner = pending.join().unwrap();
+            }
+        }
+        self.pending.is_none()
+    }
+
+    /// Variant of this pipeline, e.g. with another blend mode, created with it as the base
+    /// pipeline. Needs `PipelineOptions::allow_derivatives` on this pipeline, otherwise the
+    /// variant is created on its own.
+    pub fn derive(
+        &self,
+        device: &Device,
+        render_pass: &RenderPass,
+        subpass: u32,
+        options: PipelineOptions,
+    ) -> Self {
+        let base = if self.options.allow_derivatives && self.pending.is_none() {
+            Some(self.inner)
+        } else {
+            warn!(
+                "The base pipeline doesn't allow derivatives or is still compiling, creating the \
+                 variant on its own"
+            );
+            None
+        };
+        Self::create(device, render_pass, subpass, options, base, false)
+    }
+
+    fn create(
+        device: &Device,
+        render_pass: &RenderPass,
+        subpass: u32,
+        options: PipelineOptions,
+        base: Option<ash::vk::Pipeline>,
+        background: bool,
+    ) -> Self {
+        let vert_shader_module =
+            ShaderModule::new(device, include_bytes!("shaders/base_shader_vert.spv"));
+        let frag_shader_module = if options.deferred {
+            ShaderModule::new(
+                device,
+                include_bytes!("shaders/base_shader_gbuffer_frag.spv"),
+            )
+        } else if options.bindless_layout.is_some() {
+            ShaderModule::new(
+                device,
+                include_bytes!("shaders/base_shader_bindless_frag.spv"),
+            )
+        } else {
+            ShaderModule::new(device, include_bytes!("shaders/base_shader_frag.spv"))
+        };
+        let tessellation_shader_modules = options.tessellation.map(|_| {
+            (
+                ShaderModule::new(device, include_bytes!("shaders/base_shader_tesc.spv")),
+                ShaderModule::new(device, include_bytes!("shaders/base_shader_tese.spv")),
+            )
+        });
+
+        let mut stages = vec![
+            &vert_shader_module.reflection,
+            &frag_shader_module.reflection,
+        ];
+        if let Some((tesc, tese)) = &tessellation_shader_modules {
+            stages.extend([&tesc.reflection, &tese.reflection]);
+        }
+        for mismatch in main_layout_mismatches(&stages) {
+            warn!(
+                "The main pipeline layout doesn't match its shaders: {}",
+                mismatch
+            );
+        }
+
+        let push_constant_ranges = main_push_constant_ranges();
+        let descriptor_set_layout_bindings = main_set_layout_bindings();
+        let descriptor_set_layout_create_info =
+            DescriptorSetLayoutCreateInfo::builder().bindings(&descriptor_set_layout_bindings);
+        let descriptor_set_layout = unsafe {
             device
                 .inner
-                .create_render_pass(&render_pass_create_info, None)
+                .create_descriptor_set_layout(&descriptor_set_layout_create_info, None)
                 .unwrap()
         };
 
+        let set_layouts: Vec<_> = std::iter::once(descriptor_set_layout)
+            .chain(options.bindless_layout)
+            .collect();
+        let pipeline_layout_create_info = PipelineLayoutCreateInfo::builder()
+            .set_layouts(&set_layouts)
+            .push_constant_ranges(&push_constant_ranges);
+
+        let pipeline_layout = unsafe {
+            device
+                .inner
+                .create_pipeline_layout(&pipeline_layout_create_info, None)
+                .unwrap()
+        };
+
+        let state = PipelineState {
+            options,
+            vert_shader_module,
+            frag_shader_module,
+            tessellation_shader_modules,
+            pipeline_layout,
+            render_pass: render_pass.inner,
+            subpass,
+            color_attachment_count: render_pass.color_attachment_counts[subpass as usize],
+            color_format: render_pass.color_format,
+            depth_format: render_pass.depth_format,
+            samples: render_pass.samples,
+            base,
+        };
+        // No pipeline cache is passed. One shared with the compile thread would have to be
+        // externally synchronized with every other use of it.
+        let (inner, pending) = if background {
+            let device = device.inner.clone();
+            let pending = std::thread::spawn(move || state.compile(&device));
+            (ash::vk::Pipeline::null(), Some(pending))
+        } else {
+            (state.compile(&device.inner), None)
+        };
+
+        Self {
+            inner,
+            pending,
+            options,
+            pipeline_layout,
+            descriptor_set_layout,
+            render_pass: render_pass.inner,
+            subpass,
+            subpass_count: render_pass.subpass_count,
+            samples: render_pass.samples,
+            depth_format: render_pass.depth_format,
+            device: device.inner.clone(),
+        }
+    }
+
+    /// Pipeline drawing a fullscreen triangle without vertex buffers, sampling binding 0 in
+    /// `fragment_code`. Used for post-processing into the swapchain with a
+    /// `RenderPass::new_fullscreen`.
+    pub fn new_fullscreen(device: &Device, render_pass: &RenderPass, fragment_code: &[u8]) -> Self {
         let vert_shader_module =
-            ShaderModule::new(device, include_bytes!("shaders/base_shader_vert.spv"));
-        let frag_shader_module =
-            ShaderModule::new(device, include_bytes!("shaders/base_shader_frag.spv"));
+            ShaderModule::new(device, include_bytes!("shaders/fullscreen_vert.spv"));
+        let frag_shader_module = ShaderModule::new(device, fragment_code);
+        let p_name = CString::new("main").unwrap();
+        let shader_stage_create_infos = [
+            PipelineShaderStageCreateInfo::builder()
+                .stage(ShaderStageFlags::VERTEX)
+                .module(vert_shader_module.inner)
+                .name(&p_name)
+                .build(),
+            PipelineShaderStageCreateInfo::builder()
+                .stage(ShaderStageFlags::FRAGMENT)
+                .module(frag_shader_module.inner)
+                .name(&p_name)
+                .build(),
+        ];
+
+        let vertex_input_create_info = PipelineVertexInputStateCreateInfo::builder();
+        let input_assembly_create_info = PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(PrimitiveTopology::TRIANGLE_LIST)
+            .primitive_restart_enable(false);
+        let viewport_create_info = PipelineViewportStateCreateInfo::builder()
+            .viewport_count(1)
+            .scissor_count(1);
+        let dynamic_states = [DynamicState::VIEWPORT, DynamicState::SCISSOR];
+        let dynamic_state_create_info =
+            PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
+        let rasterizer_create_info = PipelineRasterizationStateCreateInfo::builder()
+            .polygon_mode(PolygonMode::FILL)
+            .line_width(1.0)
+            .cull_mode(CullModeFlags::NONE);
+        let multisample_create_info = PipelineMultisampleStateCreateInfo::builder()
+            .rasterization_samples(render_pass.samples);
+        let color_blend_attachments = [BlendMode::Opaque.attachment_state()];
+        let color_blend_create_info =
+            PipelineColorBlendStateCreateInfo::builder().attachments(&color_blend_attachments);
+
+        // The layout comes from the shaders, so custom fragment code can't disagree with it.
+        let stages = [
+            &vert_shader_module.reflection,
+            &frag_shader_module.reflection,
+        ];
+        if stages.iter().any(|stage| stage.max_set() > Some(0)) {
+            warn!("Fullscreen shaders only get descriptor set 0, the other sets are left out");
+        }
+        let push_constant_ranges = reflect::push_constant_ranges(&stages);
+        let descriptor_set_layout_bindings = reflect::layout_bindings(&stages, 0);
+        let descriptor_set_layout_create_info =
+            DescriptorSetLayoutCreateInfo::builder().bindings(&descriptor_set_layout_bindings);
+        let descriptor_set_layout = unsafe {
+            device
+                .inner
+                .create_descriptor_set_layout(&descriptor_set_layout_create_info, None)
+                .unwrap()
+        };
+        let set_layouts = [descriptor_set_layout];
+        let pipeline_layout_create_info = PipelineLayoutCreateInfo::builder()
+            .set_layouts(&set_layouts)
+            .push_constant_ranges(&push_constant_ranges);
+        let pipeline_layout = unsafe {
+            device
+                .inner
+                .create_pipeline_layout(&pipeline_layout_create_info, None)
+                .unwrap()
+        };
+
+        let depth_stencil_create_info = PipelineDepthStencilStateCreateInfo::builder();
 
+        let create_infos = [GraphicsPipelineCreateInfo::builder()
+            .stages(&shader_stage_create_infos)
+            .vertex_input_state(&vertex_input_create_info)
+            .input_assembly_state(&input_assembly_create_info)
+            .viewport_state(&viewport_create_info)
+            .dynamic_state(&dynamic_state_create_info)
+            .rasterization_state(&rasterizer_create_info)
+            .multisample_state(&multisample_create_info)
+            .color_blend_state(&color_blend_create_info)
+            .depth_stencil_state(&depth_stencil_create_info)
+            .layout(pipeline_layout)
+            .render_pass(render_pass.inner)
+            .subpass(0)
+            .build()];
+        let inner = unsafe {
+            device
+                .inner
+                .create_graphics_pipelines(PipelineCache::null(), &create_infos, None)
+                .unwrap()[0]
+        };
+
+        Self {
+            inner,
+            options: PipelineOptions::default(),
+            pipeline_layout,
+            descriptor_set_layout,
+            render_pass: render_pass.inner,
+            pending: None,
+            subpass: 0,
+            subpass_count: render_pass.subpass_count,
+            samples: render_pass.samples,
+            depth_format: render_pass.depth_format,
+            device: device.inner.clone(),
+        }
+    }
+
+    /// Moves from subpass `from` to `to` of the begun render pass, e.g. to the pipeline's
+    /// `subpass` before drawing and then to the end of the pass with `subpass_count - 1`.
+    pub fn cmd_next_subpasses(
+        &self,
+        device: &ash::Device,
+        command_buffer: CommandBuffer,
+        from: u32,
+        to: u32,
+    ) {
+        for _ in from..to {
+            unsafe {
+                device.cmd_next_subpass(command_buffer, SubpassContents::INLINE);
+            }
+        }
+    }
+
+    /// Created for `VK_KHR_dynamic_rendering`, without a render pass or framebuffers.
+    pub fn uses_dynamic_rendering(&self) -> bool {
+        self.render_pass == ash::vk::RenderPass::null()
+    }
+}
+
+/// Inputs of the main pipeline's `vkCreateGraphicsPipelines`, owned so it can be compiled on
+/// another thread.
+struct PipelineState {
+    options: PipelineOptions,
+    vert_shader_module: ShaderModule,
+    frag_shader_module: ShaderModule,
+    tessellation_shader_modules: Option<(ShaderModule, ShaderModule)>,
+    pipeline_layout: PipelineLayout,
+    /// Null with dynamic rendering.
+    render_pass: ash::vk::RenderPass,
+    subpass: u32,
+    color_attachment_count: usize,
+    color_format: Format,
+    depth_format: Format,
+    samples: SampleCountFlags,
+    base: Option<ash::vk::Pipeline>,
+}
+
+impl PipelineState {
+    /// The slow part of pipeline creation, the shader modules are destroyed afterwards.
+    fn compile(self, device: &ash::Device) -> ash::vk::Pipeline {
         // VERTEX
         let vert_p_name = CString::new("main").unwrap();
         let vert_create_info = PipelineShaderStageCreateInfo::builder()
             .stage(ShaderStageFlags::VERTEX)
-            .module(vert_shader_module.inner)
+            .module(self.vert_shader_module.inner)
             .name(&vert_p_name);
 
         // FRAGMENT
         let frag_p_name = CString::new("main").unwrap();
         let frag_create_info = PipelineShaderStageCreateInfo::builder()
             .stage(ShaderStageFlags::FRAGMENT)
-            .module(frag_shader_module.inner)
+            .module(self.frag_shader_module.inner)
             .name(&frag_p_name);
 
-        let vertex_input_create_info = PipelineVertexInputStateCreateInfo::builder();
+        let vertex_binding_descriptions = [
+            Vertex::binding_description(),
+            InstanceData::binding_description(),
+        ];
+        let vertex_attribute_descriptions: Vec<_> = Vertex::attribute_descriptions()
+            .into_iter()
+            .chain(InstanceData::attribute_descriptions())
+            .collect();
+        let vertex_input_create_info = PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(&vertex_binding_descriptions)
+            .vertex_attribute_descriptions(&vertex_attribute_descriptions);
 
         let input_assembly_create_info = PipelineInputAssemblyStateCreateInfo::builder()
-            .topology(PrimitiveTopology::TRIANGLE_LIST)
+            .topology(if self.options.tessellation.is_some() {
+                PrimitiveTopology::PATCH_LIST
+            } else {
+                self.options.topology
+            })
             .primitive_restart_enable(false);
 
-        let viewport = Viewport::builder()
-            .x(0.0)
-            .y(0.0)
-            .width(swapchain.extent.width as f32)
-            .height(swapchain.extent.height as f32)
-            .min_depth(0.0)
-            .max_depth(1.0);
-
-        let scissor = Rect2D::builder()
-            .offset(Offset2D { x: 0, y: 0 })
-            .extent(swapchain.extent);
-
-        let viewports = [viewport.build()];
-        let scissors = [scissor.build()];
+        // Viewport and scissor are dynamic so the pipeline survives swapchain recreation.
         let viewport_create_info = PipelineViewportStateCreateInfo::builder()
-            .viewports(&viewports)
-            .scissors(&scissors);
+            .viewport_count(1)
+            .scissor_count(1);
+        let mut dynamic_states = vec![DynamicState::VIEWPORT, DynamicState::SCISSOR];
+        if self.options.dynamic_face_culling {
+            dynamic_states.extend([DynamicState::CULL_MODE_EXT, DynamicState::FRONT_FACE_EXT]);
+        }
+        if self.options.dynamic_depth_bias {
+            dynamic_states.push(DynamicState::DEPTH_BIAS);
+        }
+        let dynamic_state_create_info =
+            PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
 
+        let face_culling = FaceCulling::default();
+        let depth_bias = self.options.depth_bias.unwrap_or_default();
         let rasterizer_create_info = PipelineRasterizationStateCreateInfo::builder()
-            .depth_clamp_enable(false)
+            .depth_clamp_enable(self.options.depth_clamp)
             .rasterizer_discard_enable(false)
             .polygon_mode(PolygonMode::FILL)
             .line_width(1.0)
-            .cull_mode(CullModeFlags::BACK)
-            .front_face(FrontFace::CLOCKWISE)
-            .depth_bias_enable(false);
+            .cull_mode(face_culling.cull_mode)
+            .front_face(face_culling.front_face)
+            .depth_bias_enable(self.options.depth_bias.is_some() || self.options.dynamic_depth_bias)
+            .depth_bias_constant_factor(depth_bias.constant_factor)
+            .depth_bias_slope_factor(depth_bias.slope_factor)
+            .depth_bias_clamp(depth_bias.clamp);
 
         let multisample_create_info = PipelineMultisampleStateCreateInfo::builder()
             .sample_shading_enable(false)
-            .rasterization_samples(SampleCountFlags::TYPE_1);
-
-        let color_blend_attachment = PipelineColorBlendAttachmentState::builder()
-            .color_write_mask(
-                ColorComponentFlags::R
-                    | ColorComponentFlags::G
-                    | ColorComponentFlags::B
-                    | ColorComponentFlags::A,
-            )
-            .blend_enable(false);
+            .rasterization_samples(self.samples);
 
-        let color_blend_attachments = [color_blend_attachment.build()];
+        let color_blend_attachments =
+            vec![self.options.blend_mode.attachment_state(); self.color_attachment_count];
         let color_blend_create_info = PipelineColorBlendStateCreateInfo::builder()
             .logic_op_enable(false)
             .attachments(&color_blend_attachments);
 
-        let pipeline_layout_create_info = PipelineLayoutCreateInfo::builder();
-
-        let pipeline_layout = unsafe {
-            device
-                .inner
-                .create_pipeline_layout(&pipeline_layout_create_info, None)
-                .unwrap()
-        };
+        let mut shader_stage_create_infos =
+            vec![vert_create_info.build(), frag_create_info.build()];
+        let tessellation_level = self
+            .options
+            .tessellation
+            .map_or(1.0, |t| t.level)
+            .to_ne_bytes();
+        let map_entries = [SpecializationMapEntry::builder()
+            .constant_id(0)
+            .offset(0)
+            .size(std::mem::size_of::<f32>())
+            .build()];
+        let specialization_info = SpecializationInfo::builder()
+            .map_entries(&map_entries)
+            .data(&tessellation_level);
+        if let Some((control_module, evaluation_module)) = &self.tessellation_shader_modules {
+            shader_stage_create_infos.extend([
+                PipelineShaderStageCreateInfo::builder()
+                    .stage(ShaderStageFlags::TESSELLATION_CONTROL)
+                    .module(control_module.inner)
+                    .name(&vert_p_name)
+                    .specialization_info(&specialization_info)
+                    .build(),
+                PipelineShaderStageCreateInfo::builder()
+                    .stage(ShaderStageFlags::TESSELLATION_EVALUATION)
+                    .module(evaluation_module.inner)
+                    .name(&vert_p_name)
+                    .build(),
+            ]);
+        }
+        let tessellation_create_info = PipelineTessellationStateCreateInfo::builder()
+            .patch_control_points(
+                self.options
+                    .tessellation
+                    .map_or(3, |t| t.patch_control_points),
+            );
+        let stencil = self.options.stencil.unwrap_or_default();
+        let depth_stencil_create_info = PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(true)
+            .depth_write_enable(true)
+            .depth_compare_op(CompareOp::LESS)
+            .stencil_test_enable(self.options.stencil.is_some())
+            .front(stencil)
+            .back(stencil);
 
-        let shader_stage_create_infos = vec![vert_create_info.build(), frag_create_info.build()];
-        let create_info = GraphicsPipelineCreateInfo::builder()
+        let color_attachment_formats = [self.color_format];
+        let mut rendering_create_info = PipelineRenderingCreateInfo::builder()
+            .color_attachment_formats(&color_attachment_formats)
+            .depth_attachment_format(self.depth_format)
+            .stencil_attachment_format(self.depth_format);
+        let mut create_info = GraphicsPipelineCreateInfo::builder()
             .stages(&shader_stage_create_infos)
             .vertex_input_state(&vertex_input_create_info)
             .input_assembly_state(&input_assembly_create_info)
             .viewport_state(&viewport_create_info)
+            .dynamic_state(&dynamic_state_create_info)
             .rasterization_state(&rasterizer_create_info)
             .multisample_state(&multisample_create_info)
             .color_blend_state(&color_blend_create_info)
-            .layout(pipeline_layout)
-            .render_pass(render_pass)
-            .subpass(0);
+            .depth_stencil_state(&depth_stencil_create_info)
+            .layout(self.pipeline_layout)
+            .render_pass(self.render_pass)
+            .subpass(self.subpass);
+        if self.options.tessellation.is_some() {
+            create_info = create_info.tessellation_state(&tessellation_create_info);
+        }
+        if self.render_pass == ash::vk::RenderPass::null() {
+            create_info = create_info.push_next(&mut rendering_create_info);
+        }
+        let mut flags = PipelineCreateFlags::empty();
+        if self.options.allow_derivatives {
+            flags |= PipelineCreateFlags::ALLOW_DERIVATIVES;
+        }
+        if let Some(base) = self.base {
+            flags |= PipelineCreateFlags::DERIVATIVE;
+            create_info = create_info
+                .base_pipeline_handle(base)
+                .base_pipeline_index(-1);
+        }
+        create_info = create_info.flags(flags);
 
         let create_infos = [create_info.build()];
-        let inner = unsafe {
+        unsafe {
             device
-                .inner
                 .create_graphics_pipelines(PipelineCache::null(), &create_infos, None)
                 .unwrap()[0]
-        };
-
-        Self {
-            inner,
-            pipeline_layout,
-            render_pass,
-            device: device.inner.clone(),
         }
     }
 }
 
+/// Set 0 of the main pipeline. Declared by hand because reflection can't tell that binding 2 is
+/// dynamic, `main_layout_mismatches` checks it against the shaders.
+fn main_set_layout_bindings() -> [DescriptorSetLayoutBinding; 3] {
+    [
+        DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(ShaderStageFlags::FRAGMENT)
+            .build(),
+        DescriptorSetLayoutBinding::builder()
+            .binding(1)
+            .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(ShaderStageFlags::VERTEX)
+            .build(),
+        // Per-object transforms, selected with a dynamic offset when binding the set.
+        DescriptorSetLayoutBinding::builder()
+            .binding(2)
+            .descriptor_type(DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+            .descriptor_count(1)
+            .stage_flags(ShaderStageFlags::VERTEX)
+            .build(),
+    ]
+}
+
+/// Sized for `Renderer::set_push_data` rather than the shaders' block.
+fn main_push_constant_ranges() -> [PushConstantRange; 1] {
+    [PushConstantRange::builder()
+        .stage_flags(ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT)
+        .offset(0)
+        .size(PUSH_CONSTANT_SIZE)
+        .build()]
+}
+
+/// Where the hand-written set 0, push constants and vertex attributes of the main pipeline
+/// disagree with `stages`, the vertex shader first. The bindless set is left out.
+fn main_layout_mismatches(stages: &[&ShaderReflection]) -> Vec<String> {
+    let attributes: Vec<_> = Vertex::attribute_descriptions()
+        .into_iter()
+        .chain(InstanceData::attribute_descriptions())
+        .collect();
+    let mut mismatches = reflect::layout_mismatches(stages, 0, &main_set_layout_bindings());
+    mismatches.extend(reflect::push_constant_mismatches(
+        stages,
+        &main_push_constant_ranges(),
+    ));
+    mismatches.extend(reflect::input_mismatches(stages[0], &attributes));
+    mismatches
+}
+
 impl Drop for GraphicsPipeline {
     fn drop(&mut self) {
+        // The compile thread uses the pipeline layout until it's done.
+        if let Some(pending) = self.pending.take() {
+            self.inner = pending.join().unwrap();
+        }
         unsafe {
             self.device.destroy_pipeline(self.inner, None);
             self.device
                 .destroy_pipeline_layout(self.pipeline_layout, None);
-            self.device.destroy_render_pass(self.render_pass, None);
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mismatches(fragment: &[u8], tessellation: bool) -> Vec<String> {
+        let mut stages = vec![
+            ShaderReflection::new(include_bytes!("shaders/base_shader_vert.spv")),
+            ShaderReflection::new(fragment),
+        ];
+        if tessellation {
+            stages.push(ShaderReflection::new(include_bytes!(
+                "shaders/base_shader_tesc.spv"
+            )));
+            stages.push(ShaderReflection::new(include_bytes!(
+                "shaders/base_shader_tese.spv"
+            )));
+        }
+        main_layout_mismatches(&stages.iter().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn main_layout_matches_every_fragment_shader() {
+        let fragment_shaders: [&[u8]; 3] = [
+            include_bytes!("shaders/base_shader_frag.spv"),
+            include_bytes!("shaders/base_shader_gbuffer_frag.spv"),
+            include_bytes!("shaders/base_shader_bindless_frag.spv"),
+        ];
+        for fragment in fragment_shaders {
+            assert_eq!(mismatches(fragment, false), Vec::<String>::new());
+        }
+    }
+
+    #[test]
+    fn main_layout_matches_tessellation_shaders() {
+        assert_eq!(
+            mismatches(include_bytes!("shaders/base_shader_frag.spv"), true),
+            Vec::<String>::new()
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use std::ffi::CString;
+
+use ash::vk::{
+    BufferUsageFlags, CommandBuffer, CompareOp, CullModeFlags, DeviceSize, DynamicState,
+    GraphicsPipelineCreateInfo, MemoryPropertyFlags, PipelineBindPoint, PipelineCache,
+    PipelineColorBlendStateCreateInfo, PipelineDepthStencilStateCreateInfo,
+    PipelineDynamicStateCreateInfo, PipelineInputAssemblyStateCreateInfo,
+    PipelineMultisampleStateCreateInfo, PipelineRasterizationStateCreateInfo,
+    PipelineShaderStageCreateInfo, PipelineVertexInputStateCreateInfo,
+    PipelineViewportStateCreateInfo, PolygonMode, PrimitiveTopology, ShaderStageFlags,
+    SpecializationInfo, SpecializationMapEntry,
+};
+use log::warn;
+
+use super::{
+    blend::BlendMode, buffer::Buffer, device::Device, pipeline_graphics::GraphicsPipeline,
+    shader_module::ShaderModule, vertex::Vertex,
+};
+
+/// Untextured, uninstanced pipeline for debug primitives like lines and points, drawn inside the
+/// main render pass. Shares the layout and descriptor set of the main pipeline, which has to
+/// outlive it.
+pub struct PrimitivePipeline {
+    pub inner: ash::vk::Pipeline,
+    pub topology: PrimitiveTopology,
+    pub device: ash::Device,
+}
+
+impl PrimitivePipeline {
+    /// `line_width` is clamped to the device's line width range and only used with `wide_lines`
+    /// enabled, otherwise lines are 1 pixel wide. `point_size` is written to `gl_PointSize` and
+    /// clamped to the point size range. `main` can't use dynamic rendering.
+    pub fn new(
+        device: &Device,
+        main: &GraphicsPipeline,
+        topology: PrimitiveTopology,
+        line_width: f32,
+        point_size: f32,
+    ) -> Self {
+        let limits = &device.physical_device.properties.limits;
+        let line_width = if device.enabled_features.wide_lines != 0 {
+            let [min, max] = limits.line_width_range;
+            line_width.clamp(min, max)
+        } else {
+            if line_width != 1.0 && topology == PrimitiveTopology::LINE_LIST {
+                warn!("Wide lines are not supported, drawing 1 pixel wide lines");
+            }
+            1.0
+        };
+
+        let vert_shader_module =
+            ShaderModule::new(device, include_bytes!("shaders/primitive_vert.spv"));
+        let frag_shader_module =
+            ShaderModule::new(device, include_bytes!("shaders/primitive_frag.spv"));
+        let p_name = CString::new("main").unwrap();
+        // Point size is specialization constant 0 of the vertex shader.
+        let point_size = if device.enabled_features.large_points != 0 {
+            let [min, max] = limits.point_size_range;
+            point_size.clamp(min, max)
+        } else {
+            if point_size != 1.0 && topology == PrimitiveTopology::POINT_LIST {
+                warn!("Large points are not supported, drawing 1 pixel points");
+            }
+            1.0
+        };
+        let map_entries = [SpecializationMapEntry::builder()
+            .constant_id(0)
+            .offset(0)
+            .size(std::mem::size_of::<f32>())
+            .build()];
+        let specialization_data = point_size.to_ne_bytes();
+        let specialization_info = SpecializationInfo::builder()
+            .map_entries(&map_entries)
+            .data(&specialization_data);
+        let shader_stage_create_infos = [
+            PipelineShaderStageCreateInfo::builder()
+                .stage(ShaderStageFlags::VERTEX)
+                .module(vert_shader_module.inner)
+                .name(&p_name)
+                .specialization_info(&specialization_info)
+                .build(),
+            PipelineShaderStageCreateInfo::builder()
+                .stage(ShaderStageFlags::FRAGMENT)
+                .module(frag_shader_module.inner)
+                .name(&p_name)
+                .build(),
+        ];
+
+        let vertex_binding_descriptions = [Vertex::binding_description()];
+        let vertex_attribute_descriptions = Vertex::attribute_descriptions();
+        let vertex_input_create_info = PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(&vertex_binding_descriptions)
+            .vertex_attribute_descriptions(&vertex_attribute_descriptions);
+        let input_assembly_create_info = PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(topology)
+            .primitive_restart_enable(false);
+        let viewport_create_info = PipelineViewportStateCreateInfo::builder()
+            .viewport_count(1)
+            .scissor_count(1);
+        let dynamic_states = [DynamicState::VIEWPORT, DynamicState::SCISSOR];
+        let dynamic_state_create_info =
+            PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
+        let rasterizer_create_info = PipelineRasterizationStateCreateInfo::builder()
+            .polygon_mode(PolygonMode::FILL)
+            .line_width(line_width)
+            .cull_mode(CullModeFlags::NONE);
+        let multisample_create_info =
+            PipelineMultisampleStateCreateInfo::builder().rasterization_samples(main.samples);
+        let color_blend_attachments = [BlendMode::Opaque.attachment_state()];
+        let color_blend_create_info =
+            PipelineColorBlendStateCreateInfo::builder().attachments(&color_blend_attachments);
+        // Lines on the surface of the geometry stay visible.
+        let depth_stencil_create_info = PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(true)
+            .depth_write_enable(true)
+            .depth_compare_op(CompareOp::LESS_OR_EQUAL);
+
+        let create_infos = [GraphicsPipelineCreateInfo::builder()
+            .stages(&shader_stage_create_infos)
+            .vertex_input_state(&vertex_input_create_info)
+            .input_assembly_state(&input_assembly_create_info)
+            .viewport_state(&viewport_create_info)
+            .dynamic_state(&dynamic_state_create_info)
+            .rasterization_state(&rasterizer_create_info)
+            .multisample_state(&multisample_create_info)
+            .color_blend_state(&color_blend_create_info)
+            .depth_stencil_state(&depth_stencil_create_info)
+            .layout(main.pipeline_layout)
+            .render_pass(main.render_pass)
+            .subpass(0)
+            .build()];
+        let inner = unsafe {
+            device
+                .inner
+                .create_graphics_pipelines(PipelineCache::null(), &create_infos, None)
+                .unwrap()[0]
+        };
+
+        Self {
+            inner,
+            topology,
+            device: device.inner.clone(),
+        }
+    }
+}
+
+impl Drop for PrimitivePipeline {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_pipeline(self.inner, None);
+        }
+    }
+}
+
+/// Vertices drawn with a `PrimitivePipeline` every frame, kept on the CPU and uploaded to a
+/// host-visible buffer once the previous frame has finished.
+#[derive(Default)]
+pub struct PrimitiveBatch {
+    pub vertices: Vec<Vertex>,
+    /// Reset to `None` when the device is recreated.
+    pub buffer: Option<Buffer>,
+}
+
+impl PrimitiveBatch {
+    /// Writes `vertices` to `buffer`, growing it if needed. The previous frame has to be finished.
+    pub fn upload(&mut self, device: &Device) {
+        if self.vertices.is_empty() {
+            return;
+        }
+        let bytes = bytemuck::cast_slice(&self.vertices);
+        if self
+            .buffer
+            .as_ref()
+            .is_none_or(|buffer| buffer.size < bytes.len() as DeviceSize)
+        {
+            self.buffer = Some(Buffer::new(
+                device,
+                bytes.len() as DeviceSize,
+                BufferUsageFlags::VERTEX_BUFFER,
+                MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+            ));
+        }
+        self.buffer.as_mut().unwrap().write(bytes);
+    }
+
+    /// `None` if there is nothing to draw.
+    pub fn draw(&self, pipeline: Option<&PrimitivePipeline>) -> Option<PrimitiveDraw> {
+        match (pipeline, &self.buffer) {
+            (Some(pipeline), Some(buffer)) if !self.vertices.is_empty() => Some(PrimitiveDraw {
+                pipeline: pipeline.inner,
+                vertex_buffer: buffer.inner,
+                vertex_count: self.vertices.len() as u32,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Non-indexed draw with a `PrimitivePipeline`. Recorded after a `SceneDraw`, whose viewport,
+/// descriptor set and push constants it reuses.
+#[derive(Clone, Copy)]
+pub struct PrimitiveDraw {
+    pub pipeline: ash::vk::Pipeline,
+    pub vertex_buffer: ash::vk::Buffer,
+    pub vertex_count: u32,
+}
+
+impl PrimitiveDraw {
+    pub fn record(&self, device: &ash::Device, command_buffer: CommandBuffer) {
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::GRAPHICS, self.pipeline);
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer], &[0]);
+            device.cmd_draw(command_buffer, self.vertex_count, 1, 0, 0);
+        }
+    }
+}
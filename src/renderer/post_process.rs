@@ -0,0 +1,77 @@
+use ash::vk::{
+    DescriptorImageInfo, DescriptorPoolSize, DescriptorSet, DescriptorType, Extent2D, Format,
+    ImageLayout, WriteDescriptorSet,
+};
+
+use super::{
+    descriptor::DescriptorPool, device::Device, pipeline_graphics::GraphicsPipeline,
+    render_pass::RenderPass, render_target::RenderTarget, sampler::Sampler,
+};
+
+/// Converts the scene to grayscale, ready to be passed to `Renderer::set_post_process`.
+pub const GRAYSCALE_SHADER: &[u8] = include_bytes!("shaders/post_grayscale_frag.spv");
+
+/// Fullscreen pass that samples the scene, rendered into `target`, with a custom fragment shader.
+pub struct PostProcess {
+    pub descriptor_set: DescriptorSet,
+    pub descriptor_pool: DescriptorPool,
+    /// The scene is rendered into this instead of the swapchain.
+    pub target: RenderTarget,
+    pub pipeline: GraphicsPipeline,
+    /// Dropped after `pipeline`, which is created for it.
+    pub render_pass: RenderPass,
+    /// Kept to rebuild the pass when the swapchain is recreated.
+    pub fragment_code: Vec<u8>,
+}
+
+impl PostProcess {
+    /// `format` and `final_layout` are those of the image the pass writes to, e.g. the swapchain image.
+    /// `depth_format` is that of the main pipeline, which renders the scene into `target`.
+    pub fn new(
+        device: &Device,
+        extent: Extent2D,
+        format: Format,
+        depth_format: Format,
+        final_layout: ImageLayout,
+        sampler: &Sampler,
+        fragment_code: &[u8],
+    ) -> Self {
+        let render_pass = RenderPass::new_fullscreen(device, format, depth_format, final_layout);
+        let pipeline = GraphicsPipeline::new_fullscreen(device, &render_pass, fragment_code);
+        let target = RenderTarget::new(device, extent, format, Some(depth_format));
+
+        let descriptor_pool = DescriptorPool::new(
+            device,
+            &[DescriptorPoolSize::builder()
+                .ty(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .build()],
+            1,
+        );
+        let descriptor_set = descriptor_pool.allocate(pipeline.descriptor_set_layout);
+        let image_infos = [DescriptorImageInfo::builder()
+            .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(target.view())
+            .sampler(sampler.inner)
+            .build()];
+        let writes = [WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&image_infos)
+            .build()];
+        unsafe {
+            device.inner.update_descriptor_sets(&writes, &[]);
+        }
+
+        Self {
+            descriptor_set,
+            descriptor_pool,
+            target,
+            pipeline,
+            render_pass,
+            fragment_code: fragment_code.to_vec(),
+        }
+    }
+}
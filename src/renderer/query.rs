@@ -0,0 +1,318 @@
+use std::mem::size_of;
+
+use ash::vk::{
+    CommandBuffer, QueryControlFlags, QueryPipelineStatisticFlags, QueryPoolCreateInfo,
+    QueryResultFlags, QueryType, Result,
+};
+use log::warn;
+
+use super::device::Device;
+
+pub struct QueryPool {
+    pub inner: ash::vk::QueryPool,
+    pub query_type: QueryType,
+    pub count: u32,
+    /// Number of results each query writes, one per statistic for `PIPELINE_STATISTICS` pools.
+    pub values_per_query: u32,
+    device: ash::Device,
+}
+
+impl QueryPool {
+    pub fn new(device: &Device, query_type: QueryType, count: u32) -> Self {
+        Self::create(
+            device,
+            query_type,
+            QueryPipelineStatisticFlags::empty(),
+            count,
+        )
+    }
+
+    /// Requires the `pipeline_statistics_query` feature to be enabled on the device.
+    pub fn new_pipeline_statistics(
+        device: &Device,
+        statistics: QueryPipelineStatisticFlags,
+        count: u32,
+    ) -> Self {
+        Self::create(device, QueryType::PIPELINE_STATISTICS, statistics, count)
+    }
+
+    fn create(
+        device: &Device,
+        query_type: QueryType,
+        statistics: QueryPipelineStatisticFlags,
+        count: u32,
+    ) -> Self {
+        let create_info = QueryPoolCreateInfo::builder()
+            .query_type(query_type)
+            .query_count(count)
+            .pipeline_statistics(statistics);
+
+        let inner = unsafe { device.inner.create_query_pool(&create_info, None).unwrap() };
+
+        Self {
+            inner,
+            query_type,
+            count,
+            values_per_query: if query_type == QueryType::PIPELINE_STATISTICS {
+                statistics.as_raw().count_ones()
+            } else {
+                1
+            },
+            device: device.inner.clone(),
+        }
+    }
+
+    /// Records a reset of all queries. Has to happen outside of a render pass before the queries are reused.
+    pub fn reset(&self, command_buffer: CommandBuffer) {
+        unsafe {
+            self.device
+                .cmd_reset_query_pool(command_buffer, self.inner, 0, self.count);
+        }
+    }
+
+    /// Reads all query results without waiting. Returns `None` while any of them is still pending.
+    pub fn results(&self) -> Option<Vec<u64>> {
+        self.results_range(0, self.count)
+    }
+
+    /// Like `results`, for `count` queries starting at `first`.
+    pub fn results_range(&self, first: u32, count: u32) -> Option<Vec<u64>> {
+        let (stride, data_size) = result_layout(self.values_per_query, count);
+        let mut data = vec![0u64; data_size / size_of::<u64>()];
+        // ash's wrapper derives the stride from the element type, which is too small for queries
+        // with several values.
+        match unsafe {
+            (self.device.fp_v1_0().get_query_pool_results)(
+                self.device.handle(),
+                self.inner,
+                first,
+                count,
+                data_size,
+                data.as_mut_ptr().cast(),
+                stride,
+                QueryResultFlags::TYPE_64,
+            )
+            .result()
+        } {
+            Ok(()) => Some(data),
+            Err(Result::NOT_READY) => None,
+            Err(err) => panic!("Failed to read query results: {:?}", err),
+        }
+    }
+}
+
+/// Stride between the results of consecutive queries and the size of the results of `count`
+/// queries in bytes, with 64 bit values.
+fn result_layout(values_per_query: u32, count: u32) -> (u64, usize) {
+    let stride = values_per_query as usize * size_of::<u64>();
+    (stride as u64, stride * count as usize)
+}
+
+/// Statistics collected by `PipelineStatsQuery`, see `QueryPipelineStatisticFlags` for their meaning.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PipelineStats {
+    pub input_assembly_vertices: u64,
+    pub input_assembly_primitives: u64,
+    pub vertex_shader_invocations: u64,
+    pub clipping_invocations: u64,
+    pub clipping_primitives: u64,
+    pub fragment_shader_invocations: u64,
+    pub compute_shader_invocations: u64,
+}
+
+impl PipelineStats {
+    /// Results are written in order of the flag bits, which matches the field order.
+    const FLAGS: QueryPipelineStatisticFlags = QueryPipelineStatisticFlags::from_raw(
+        QueryPipelineStatisticFlags::INPUT_ASSEMBLY_VERTICES.as_raw()
+            | QueryPipelineStatisticFlags::INPUT_ASSEMBLY_PRIMITIVES.as_raw()
+            | QueryPipelineStatisticFlags::VERTEX_SHADER_INVOCATIONS.as_raw()
+            | QueryPipelineStatisticFlags::CLIPPING_INVOCATIONS.as_raw()
+            | QueryPipelineStatisticFlags::CLIPPING_PRIMITIVES.as_raw()
+            | QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS.as_raw()
+            | QueryPipelineStatisticFlags::COMPUTE_SHADER_INVOCATIONS.as_raw(),
+    );
+}
+
+/// Counts the work of everything recorded between `begin` and `end`. Like `GpuTimer`, results
+/// are read after the frame's fence and lag one frame behind.
+pub struct PipelineStatsQuery {
+    pool: QueryPool,
+    pending: bool,
+    last_stats: PipelineStats,
+    device: ash::Device,
+}
+
+impl PipelineStatsQuery {
+    /// Returns `None` with a warning if `pipeline_statistics_query` isn't enabled on the device.
+    pub fn new(device: &Device) -> Option<Self> {
+        if device.enabled_features.pipeline_statistics_query == 0 {
+            warn!("Pipeline statistics queries are not supported, pipeline stats are disabled");
+            return None;
+        }
+        Some(Self {
+            pool: QueryPool::new_pipeline_statistics(device, Self::flags(), 1),
+            pending: false,
+            last_stats: PipelineStats::default(),
+            device: device.inner.clone(),
+        })
+    }
+
+    /// Statistics secondary command buffers have to inherit while the query is active.
+    pub fn flags() -> QueryPipelineStatisticFlags {
+        PipelineStats::FLAGS
+    }
+
+    /// Has to be recorded outside of a render pass.
+    pub fn begin(&mut self, command_buffer: CommandBuffer) {
+        self.pool.reset(command_buffer);
+        unsafe {
+            self.device.cmd_begin_query(
+                command_buffer,
+                self.pool.inner,
+                0,
+                QueryControlFlags::empty(),
+            );
+        }
+    }
+
+    pub fn end(&mut self, command_buffer: CommandBuffer) {
+        unsafe {
+            self.device
+                .cmd_end_query(command_buffer, self.pool.inner, 0);
+        }
+        self.pending = true;
+    }
+
+    /// Picks up the statistics of the last submitted frame if they are available.
+    pub fn collect(&mut self) {
+        if !self.pending {
+            return;
+        }
+        if let Some(results) = self.pool.results() {
+            self.last_stats = PipelineStats {
+                input_assembly_vertices: results[0],
+                input_assembly_primitives: results[1],
+                vertex_shader_invocations: results[2],
+                clipping_invocations: results[3],
+                clipping_primitives: results[4],
+                fragment_shader_invocations: results[5],
+                compute_shader_invocations: results[6],
+            };
+            self.pending = false;
+        }
+    }
+
+    pub fn last_stats(&self) -> PipelineStats {
+        self.last_stats
+    }
+}
+
+/// Hardware occlusion queries, e.g. to skip drawing objects whose bounding box is hidden.
+///
+/// Draw the bounding box between `begin` and `end` of a query, with color and depth writes
+/// disabled, and skip the real draw if `get_samples_passed` returns zero. Results are read after
+/// the frame's fence, so they describe the previous frame.
+///
+/// Precise queries count the exact number of passing samples, which needs the
+/// `occlusion_query_precise` feature and can be slower on some hardware. Non-precise queries only
+/// guarantee a nonzero result if any sample passed, which is all visibility testing needs.
+pub struct OcclusionQuery {
+    pool: QueryPool,
+    control_flags: QueryControlFlags,
+    /// Queries begun since the last `reset`, only these produce results.
+    recorded: Vec<bool>,
+    samples_passed: Vec<Option<u64>>,
+    device: ash::Device,
+}
+
+impl OcclusionQuery {
+    /// Falls back to non-precise queries with a warning if `precise` is requested but
+    /// `occlusion_query_precise` isn't enabled on the device.
+    pub fn new(device: &Device, count: u32, precise: bool) -> Self {
+        let precise = if precise && device.enabled_features.occlusion_query_precise == 0 {
+            warn!("Precise occlusion queries are not supported, using non-precise ones");
+            false
+        } else {
+            precise
+        };
+        Self {
+            pool: QueryPool::new(device, QueryType::OCCLUSION, count),
+            control_flags: if precise {
+                QueryControlFlags::PRECISE
+            } else {
+                QueryControlFlags::empty()
+            },
+            recorded: vec![false; count as usize],
+            samples_passed: vec![None; count as usize],
+            device: device.inner.clone(),
+        }
+    }
+
+    /// Has to be recorded outside of a render pass before the queries of a frame are begun.
+    pub fn reset(&mut self, command_buffer: CommandBuffer) {
+        self.pool.reset(command_buffer);
+        self.recorded.fill(false);
+    }
+
+    /// Has to be recorded inside a render pass.
+    pub fn begin(&mut self, command_buffer: CommandBuffer, index: u32) {
+        unsafe {
+            self.device
+                .cmd_begin_query(command_buffer, self.pool.inner, index, self.control_flags);
+        }
+        self.recorded[index as usize] = true;
+    }
+
+    pub fn end(&self, command_buffer: CommandBuffer, index: u32) {
+        unsafe {
+            self.device
+                .cmd_end_query(command_buffer, self.pool.inner, index);
+        }
+    }
+
+    /// Reads the results of the queries recorded in the last submitted frame. Call after waiting
+    /// for that frame and before `reset`.
+    pub fn collect(&mut self) {
+        for (index, recorded) in self.recorded.iter().enumerate() {
+            if *recorded {
+                self.samples_passed[index] = self
+                    .pool
+                    .results_range(index as u32, 1)
+                    .map(|results| results[0]);
+            }
+        }
+    }
+
+    /// Samples that passed the depth and stencil tests in query `index` of the last collected
+    /// frame, `None` if it wasn't recorded or isn't available yet.
+    pub fn get_samples_passed(&self, index: u32) -> Option<u64> {
+        self.samples_passed[index as usize]
+    }
+}
+
+impl Drop for QueryPool {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_query_pool(self.inner, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipeline_stats_results_are_strided_by_all_their_values() {
+        let values_per_query = PipelineStats::FLAGS.as_raw().count_ones();
+        assert_eq!(values_per_query, 7);
+        assert_eq!(result_layout(values_per_query, 1), (56, 56));
+        assert_eq!(result_layout(values_per_query, 3), (56, 168));
+    }
+
+    #[test]
+    fn single_value_results_are_eight_bytes_apart() {
+        assert_eq!(result_layout(1, 4), (8, 32));
+        assert_eq!(result_layout(1, 0), (8, 0));
+    }
+}
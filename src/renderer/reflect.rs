@@ -0,0 +1,798 @@
+use std::collections::HashMap;
+
+use ash::vk::{
+    DescriptorSetLayoutBinding, DescriptorType, Format, PushConstantRange, ShaderStageFlags,
+    VertexInputAttributeDescription,
+};
+
+const MAGIC: u32 = 0x0723_0203;
+
+// Opcodes
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+// Decorations
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+// Storage classes
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+/// A descriptor the shader declares.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReflectedBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: DescriptorType,
+    /// Array length, 1 for a single descriptor and 0 for a runtime array, whose size the layout
+    /// has to choose.
+    pub count: u32,
+}
+
+/// A vertex input, or an input from the previous stage for the other stages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReflectedInput {
+    pub location: u32,
+    /// `UNDEFINED` for types other than 32-bit scalars and vectors.
+    pub format: Format,
+}
+
+/// Interface of a SPIR-V module, read from its decorations and types.
+#[derive(Clone, Debug, Default)]
+pub struct ShaderReflection {
+    /// Stage of the first entry point.
+    pub stage: ShaderStageFlags,
+    /// Sorted by set and binding.
+    pub bindings: Vec<ReflectedBinding>,
+    /// Bytes of the push constant block, 0 without one.
+    pub push_constant_size: u32,
+    /// Sorted by location, built-ins are left out.
+    pub inputs: Vec<ReflectedInput>,
+}
+
+#[derive(Clone, Debug)]
+enum Type {
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: u32 },
+    RuntimeArray { element: u32 },
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 },
+}
+
+#[derive(Default)]
+struct Decorations {
+    decorations: HashMap<(u32, u32), u32>,
+    member_decorations: HashMap<(u32, u32, u32), u32>,
+}
+
+impl Decorations {
+    fn get(&self, id: u32, decoration: u32) -> Option<u32> {
+        self.decorations.get(&(id, decoration)).copied()
+    }
+
+    fn has(&self, id: u32, decoration: u32) -> bool {
+        self.decorations.contains_key(&(id, decoration))
+    }
+
+    fn member(&self, id: u32, member: u32, decoration: u32) -> Option<u32> {
+        self.member_decorations
+            .get(&(id, member, decoration))
+            .copied()
+    }
+}
+
+impl ShaderReflection {
+    /// Panics if `code` isn't SPIR-V.
+    pub fn new(code: &[u8]) -> Self {
+        assert!(
+            code.len().is_multiple_of(4),
+            "SPIR-V code has to be a whole number of words"
+        );
+        let words: Vec<u32> = code
+            .chunks_exact(4)
+            .map(|word| u32::from_ne_bytes(word.try_into().unwrap()))
+            .collect();
+        assert!(
+            words.len() >= 5 && words[0] == MAGIC,
+            "Shader code is not SPIR-V"
+        );
+
+        let mut stage = ShaderStageFlags::empty();
+        let mut types = HashMap::new();
+        let mut constants = HashMap::new();
+        let mut decorations = Decorations::default();
+        // (id, pointer type, storage class)
+        let mut variables = Vec::new();
+
+        let mut offset = 5;
+        while offset < words.len() {
+            let word_count = (words[offset] >> 16) as usize;
+            let opcode = words[offset] & 0xffff;
+            assert!(word_count > 0, "Malformed SPIR-V instruction");
+            let operands = &words[offset + 1..offset + word_count];
+            offset += word_count;
+
+            match opcode {
+                OP_ENTRY_POINT if stage.is_empty() => stage = execution_model_stage(operands[0]),
+                OP_TYPE_INT => {
+                    types.insert(
+                        operands[0],
+                        Type::Int {
+                            width: operands[1],
+                            signed: operands[2] != 0,
+                        },
+                    );
+                }
+                OP_TYPE_FLOAT => {
+                    types.insert(operands[0], Type::Float { width: operands[1] });
+                }
+                OP_TYPE_VECTOR => {
+                    types.insert(
+                        operands[0],
+                        Type::Vector {
+                            component: operands[1],
+                            count: operands[2],
+                        },
+                    );
+                }
+                OP_TYPE_MATRIX => {
+                    types.insert(
+                        operands[0],
+                        Type::Matrix {
+                            column: operands[1],
+                            count: operands[2],
+                        },
+                    );
+                }
+                OP_TYPE_IMAGE => {
+                    types.insert(
+                        operands[0],
+                        Type::Image {
+                            dim: operands[2],
+                            sampled: operands[6],
+                        },
+                    );
+                }
+                OP_TYPE_SAMPLER => {
+                    types.insert(operands[0], Type::Sampler);
+                }
+                OP_TYPE_SAMPLED_IMAGE => {
+                    types.insert(operands[0], Type::SampledImage);
+                }
+                OP_TYPE_ARRAY => {
+                    types.insert(
+                        operands[0],
+                        Type::Array {
+                            element: operands[1],
+                            length: operands[2],
+                        },
+                    );
+                }
+                OP_TYPE_RUNTIME_ARRAY => {
+                    types.insert(
+                        operands[0],
+                        Type::RuntimeArray {
+                            element: operands[1],
+                        },
+                    );
+                }
+                OP_TYPE_STRUCT => {
+                    types.insert(
+                        operands[0],
+                        Type::Struct {
+                            members: operands[1..].to_vec(),
+                        },
+                    );
+                }
+                OP_TYPE_POINTER => {
+                    types.insert(
+                        operands[0],
+                        Type::Pointer {
+                            pointee: operands[2],
+                        },
+                    );
+                }
+                // Only the low word matters for array lengths.
+                OP_CONSTANT if operands.len() > 2 => {
+                    constants.insert(operands[1], operands[2]);
+                }
+                OP_VARIABLE => variables.push((operands[1], operands[0], operands[2])),
+                OP_DECORATE => {
+                    let value = operands.get(2).copied().unwrap_or(0);
+                    decorations
+                        .decorations
+                        .insert((operands[0], operands[1]), value);
+                }
+                OP_MEMBER_DECORATE => {
+                    let value = operands.get(3).copied().unwrap_or(0);
+                    decorations
+                        .member_decorations
+                        .insert((operands[0], operands[1], operands[2]), value);
+                }
+                _ => {}
+            }
+        }
+
+        let module = Module {
+            types,
+            constants,
+            decorations,
+        };
+        let mut reflection = Self {
+            stage,
+            ..Self::default()
+        };
+        for (id, pointer, storage_class) in variables {
+            let pointee = match module.types.get(&pointer) {
+                Some(Type::Pointer { pointee }) => *pointee,
+                _ => continue,
+            };
+            match storage_class {
+                STORAGE_UNIFORM_CONSTANT | STORAGE_UNIFORM | STORAGE_STORAGE_BUFFER => {
+                    let (element, count) = module.array_element(pointee);
+                    if let Some(descriptor_type) = module.descriptor_type(element, storage_class) {
+                        reflection.bindings.push(ReflectedBinding {
+                            set: module
+                                .decorations
+                                .get(id, DECORATION_DESCRIPTOR_SET)
+                                .unwrap_or(0),
+                            binding: module.decorations.get(id, DECORATION_BINDING).unwrap_or(0),
+                            descriptor_type,
+                            count,
+                        });
+                    }
+                }
+                STORAGE_PUSH_CONSTANT => {
+                    reflection.push_constant_size = module.size(pointee);
+                }
+                STORAGE_INPUT if !module.decorations.has(id, DECORATION_BUILT_IN) => {
+                    if let Some(location) = module.decorations.get(id, DECORATION_LOCATION) {
+                        reflection.inputs.push(ReflectedInput {
+                            location,
+                            format: module.format(pointee),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        reflection
+            .bindings
+            .sort_by_key(|binding| (binding.set, binding.binding));
+        reflection.inputs.sort_by_key(|input| input.location);
+        reflection
+    }
+
+    /// Highest set the shader uses, `None` without descriptors.
+    pub fn max_set(&self) -> Option<u32> {
+        self.bindings.iter().map(|binding| binding.set).max()
+    }
+}
+
+/// Bindings of descriptor set `set` across `stages`, with the stage flags of every stage that
+/// declares them.
+pub fn layout_bindings(stages: &[&ShaderReflection], set: u32) -> Vec<DescriptorSetLayoutBinding> {
+    let mut bindings: Vec<DescriptorSetLayoutBinding> = Vec::new();
+    for stage in stages {
+        for reflected in stage.bindings.iter().filter(|binding| binding.set == set) {
+            match bindings
+                .iter_mut()
+                .find(|binding| binding.binding == reflected.binding)
+            {
+                Some(binding) => binding.stage_flags |= stage.stage,
+                None => bindings.push(
+                    DescriptorSetLayoutBinding::builder()
+                        .binding(reflected.binding)
+                        .descriptor_type(reflected.descriptor_type)
+                        .descriptor_count(reflected.count)
+                        .stage_flags(stage.stage)
+                        .build(),
+                ),
+            }
+        }
+    }
+    bindings.sort_by_key(|binding| binding.binding);
+    bindings
+}
+
+/// A single range covering the largest push constant block, visible to every stage with one.
+pub fn push_constant_ranges(stages: &[&ShaderReflection]) -> Vec<PushConstantRange> {
+    let size = stages
+        .iter()
+        .map(|stage| stage.push_constant_size)
+        .max()
+        .unwrap_or(0);
+    if size == 0 {
+        return Vec::new();
+    }
+    let stage_flags = stages
+        .iter()
+        .filter(|stage| stage.push_constant_size > 0)
+        .fold(ShaderStageFlags::empty(), |flags, stage| {
+            flags | stage.stage
+        });
+    vec![PushConstantRange::builder()
+        .stage_flags(stage_flags)
+        .offset(0)
+        .size(size)
+        .build()]
+}
+
+/// Bindings `stages` declare in set `set` that `layout` doesn't provide for them, described for a
+/// warning. Bindings only the layout has are fine. SPIR-V doesn't tell dynamic uniform and storage
+/// buffers apart from plain ones, so those match either.
+pub fn layout_mismatches(
+    stages: &[&ShaderReflection],
+    set: u32,
+    layout: &[DescriptorSetLayoutBinding],
+) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    for reflected in layout_bindings(stages, set) {
+        let binding = match layout
+            .iter()
+            .find(|binding| binding.binding == reflected.binding)
+        {
+            Some(binding) => binding,
+            None => {
+                mismatches.push(format!(
+                    "set {} binding {} ({:?}) is missing",
+                    set, reflected.binding, reflected.descriptor_type
+                ));
+                continue;
+            }
+        };
+        let descriptor_type = match binding.descriptor_type {
+            DescriptorType::UNIFORM_BUFFER_DYNAMIC => DescriptorType::UNIFORM_BUFFER,
+            DescriptorType::STORAGE_BUFFER_DYNAMIC => DescriptorType::STORAGE_BUFFER,
+            descriptor_type => descriptor_type,
+        };
+        if descriptor_type != reflected.descriptor_type {
+            mismatches.push(format!(
+                "set {} binding {} is {:?}, the shaders use {:?}",
+                set, reflected.binding, binding.descriptor_type, reflected.descriptor_type
+            ));
+        }
+        // Runtime arrays are sized by the layout.
+        if binding.descriptor_count < reflected.descriptor_count {
+            mismatches.push(format!(
+                "set {} binding {} has {} descriptors, the shaders use {}",
+                set, reflected.binding, binding.descriptor_count, reflected.descriptor_count
+            ));
+        }
+        if !binding.stage_flags.contains(reflected.stage_flags) {
+            mismatches.push(format!(
+                "set {} binding {} is visible to {:?}, the shaders use it in {:?}",
+                set, reflected.binding, binding.stage_flags, reflected.stage_flags
+            ));
+        }
+    }
+    mismatches
+}
+
+/// Push constant blocks of `stages` that `ranges` don't cover, like `layout_mismatches`.
+pub fn push_constant_mismatches(
+    stages: &[&ShaderReflection],
+    ranges: &[PushConstantRange],
+) -> Vec<String> {
+    stages
+        .iter()
+        .filter(|stage| stage.push_constant_size > 0)
+        .filter(|stage| {
+            !ranges.iter().any(|range| {
+                range.stage_flags.contains(stage.stage)
+                    && range.offset == 0
+                    && range.size >= stage.push_constant_size
+            })
+        })
+        .map(|stage| {
+            format!(
+                "no push constant range covers the {} bytes of the {:?} stage",
+                stage.push_constant_size, stage.stage
+            )
+        })
+        .collect()
+}
+
+/// Inputs of a vertex shader that `attributes` don't provide in the same format, like
+/// `layout_mismatches`. Attributes the shader doesn't read are fine.
+pub fn input_mismatches(
+    vertex: &ShaderReflection,
+    attributes: &[VertexInputAttributeDescription],
+) -> Vec<String> {
+    vertex
+        .inputs
+        .iter()
+        .filter_map(|input| {
+            match attributes
+                .iter()
+                .find(|attribute| attribute.location == input.location)
+            {
+                None => Some(format!("vertex input {} is missing", input.location)),
+                Some(attribute) if attribute.format != input.format => Some(format!(
+                    "vertex input {} is {:?}, the shader reads {:?}",
+                    input.location, attribute.format, input.format
+                )),
+                Some(_) => None,
+            }
+        })
+        .collect()
+}
+
+fn execution_model_stage(execution_model: u32) -> ShaderStageFlags {
+    match execution_model {
+        0 => ShaderStageFlags::VERTEX,
+        1 => ShaderStageFlags::TESSELLATION_CONTROL,
+        2 => ShaderStageFlags::TESSELLATION_EVALUATION,
+        3 => ShaderStageFlags::GEOMETRY,
+        4 => ShaderStageFlags::FRAGMENT,
+        5 => ShaderStageFlags::COMPUTE,
+        _ => ShaderStageFlags::empty(),
+    }
+}
+
+struct Module {
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    decorations: Decorations,
+}
+
+impl Module {
+    /// Element type and descriptor count of a possibly arrayed descriptor.
+    fn array_element(&self, id: u32) -> (u32, u32) {
+        match self.types.get(&id) {
+            Some(Type::Array { element, length }) => {
+                (*element, self.constants.get(length).copied().unwrap_or(1))
+            }
+            Some(Type::RuntimeArray { element }) => (*element, 0),
+            _ => (id, 1),
+        }
+    }
+
+    fn descriptor_type(&self, id: u32, storage_class: u32) -> Option<DescriptorType> {
+        let descriptor_type = match (self.types.get(&id)?, storage_class) {
+            (Type::SampledImage, _) => DescriptorType::COMBINED_IMAGE_SAMPLER,
+            (Type::Sampler, _) => DescriptorType::SAMPLER,
+            (Type::Image { dim, .. }, _) if *dim == DIM_SUBPASS_DATA => {
+                DescriptorType::INPUT_ATTACHMENT
+            }
+            (Type::Image { dim, sampled }, _) if *dim == DIM_BUFFER => {
+                if *sampled == 2 {
+                    DescriptorType::STORAGE_TEXEL_BUFFER
+                } else {
+                    DescriptorType::UNIFORM_TEXEL_BUFFER
+                }
+            }
+            (Type::Image { sampled, .. }, _) => {
+                if *sampled == 2 {
+                    DescriptorType::STORAGE_IMAGE
+                } else {
+                    DescriptorType::SAMPLED_IMAGE
+                }
+            }
+            (Type::Struct { .. }, STORAGE_STORAGE_BUFFER) => DescriptorType::STORAGE_BUFFER,
+            // Before SPIR-V 1.3 storage buffers are uniform blocks decorated with `BufferBlock`.
+            (Type::Struct { .. }, _) if self.decorations.has(id, DECORATION_BUFFER_BLOCK) => {
+                DescriptorType::STORAGE_BUFFER
+            }
+            (Type::Struct { .. }, _) if self.decorations.has(id, DECORATION_BLOCK) => {
+                DescriptorType::UNIFORM_BUFFER
+            }
+            _ => return None,
+        };
+        Some(descriptor_type)
+    }
+
+    /// Bytes of a type laid out with the module's explicit offsets and strides.
+    fn size(&self, id: u32) -> u32 {
+        match self.types.get(&id) {
+            Some(Type::Int { width, .. }) | Some(Type::Float { width }) => width / 8,
+            Some(Type::Vector { component, count }) => self.size(*component) * count,
+            Some(Type::Matrix { column, count }) => self.size(*column) * count,
+            Some(Type::Array { element, length }) => {
+                let stride = self
+                    .decorations
+                    .get(id, DECORATION_ARRAY_STRIDE)
+                    .unwrap_or_else(|| self.size(*element));
+                stride * self.constants.get(length).copied().unwrap_or(1)
+            }
+            Some(Type::Struct { members }) => members
+                .iter()
+                .enumerate()
+                .map(|(i, member)| {
+                    let i = i as u32;
+                    let offset = self
+                        .decorations
+                        .member(id, i, DECORATION_OFFSET)
+                        .unwrap_or(0);
+                    let size = match (
+                        self.types.get(member),
+                        self.decorations.member(id, i, DECORATION_MATRIX_STRIDE),
+                    ) {
+                        (Some(Type::Matrix { count, .. }), Some(stride)) => stride * count,
+                        _ => self.size(*member),
+                    };
+                    offset + size
+                })
+                .max()
+                .unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    /// Vertex attribute format of a 32-bit scalar or vector input.
+    fn format(&self, id: u32) -> Format {
+        let (component, count) = match self.types.get(&id) {
+            Some(Type::Vector { component, count }) => (*component, *count),
+            _ => (id, 1),
+        };
+        let formats = match self.types.get(&component) {
+            Some(Type::Float { width: 32 }) => [
+                Format::R32_SFLOAT,
+                Format::R32G32_SFLOAT,
+                Format::R32G32B32_SFLOAT,
+                Format::R32G32B32A32_SFLOAT,
+            ],
+            Some(Type::Int {
+                width: 32,
+                signed: true,
+            }) => [
+                Format::R32_SINT,
+                Format::R32G32_SINT,
+                Format::R32G32B32_SINT,
+                Format::R32G32B32A32_SINT,
+            ],
+            Some(Type::Int {
+                width: 32,
+                signed: false,
+            }) => [
+                Format::R32_UINT,
+                Format::R32G32_UINT,
+                Format::R32G32B32_UINT,
+                Format::R32G32B32A32_UINT,
+            ],
+            _ => return Format::UNDEFINED,
+        };
+        formats
+            .get(count as usize - 1)
+            .copied()
+            .unwrap_or(Format::UNDEFINED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reflect(code: &[u8]) -> ShaderReflection {
+        ShaderReflection::new(code)
+    }
+
+    fn binding(binding: u32, descriptor_type: DescriptorType, count: u32) -> ReflectedBinding {
+        ReflectedBinding {
+            set: 0,
+            binding,
+            descriptor_type,
+            count,
+        }
+    }
+
+    fn input(location: u32, format: Format) -> ReflectedInput {
+        ReflectedInput { location, format }
+    }
+
+    #[test]
+    fn reflects_base_vertex_shader() {
+        let reflection = reflect(include_bytes!("shaders/base_shader_vert.spv"));
+        assert_eq!(reflection.stage, ShaderStageFlags::VERTEX);
+        assert_eq!(
+            reflection.bindings,
+            [
+                binding(1, DescriptorType::UNIFORM_BUFFER, 1),
+                binding(2, DescriptorType::UNIFORM_BUFFER, 1),
+            ]
+        );
+        assert_eq!(reflection.push_constant_size, 4);
+        assert_eq!(
+            reflection.inputs,
+            [
+                input(0, Format::R32G32B32_SFLOAT),
+                input(1, Format::R32G32B32A32_SFLOAT),
+                input(2, Format::R32G32_SFLOAT),
+                input(3, Format::R32G32B32_SFLOAT),
+                input(4, Format::R32G32B32_SFLOAT),
+            ]
+        );
+    }
+
+    #[test]
+    fn reflects_base_fragment_shader() {
+        let reflection = reflect(include_bytes!("shaders/base_shader_frag.spv"));
+        assert_eq!(reflection.stage, ShaderStageFlags::FRAGMENT);
+        assert_eq!(
+            reflection.bindings,
+            [binding(0, DescriptorType::COMBINED_IMAGE_SAMPLER, 1)]
+        );
+        assert_eq!(reflection.push_constant_size, 0);
+        assert_eq!(
+            reflection.inputs,
+            [
+                input(0, Format::R32G32B32A32_SFLOAT),
+                input(1, Format::R32G32_SFLOAT),
+            ]
+        );
+    }
+
+    #[test]
+    fn reflects_input_attachments() {
+        let reflection = reflect(include_bytes!("shaders/deferred_lighting_frag.spv"));
+        assert_eq!(reflection.stage, ShaderStageFlags::FRAGMENT);
+        assert_eq!(
+            reflection.bindings,
+            [
+                binding(0, DescriptorType::INPUT_ATTACHMENT, 1),
+                binding(1, DescriptorType::INPUT_ATTACHMENT, 1),
+            ]
+        );
+        assert!(reflection.inputs.is_empty());
+    }
+
+    #[test]
+    fn reflects_runtime_array_in_second_set() {
+        let reflection = reflect(include_bytes!("shaders/base_shader_bindless_frag.spv"));
+        assert_eq!(
+            reflection.bindings,
+            [ReflectedBinding {
+                set: 1,
+                ..binding(0, DescriptorType::COMBINED_IMAGE_SAMPLER, 0)
+            }]
+        );
+        assert_eq!(reflection.max_set(), Some(1));
+        assert!(layout_bindings(&[&reflection], 0).is_empty());
+    }
+
+    #[test]
+    fn reflects_compute_shader() {
+        let reflection = reflect(include_bytes!("shaders/scale_storage_comp.spv"));
+        assert_eq!(reflection.stage, ShaderStageFlags::COMPUTE);
+        assert_eq!(
+            reflection.bindings,
+            [binding(0, DescriptorType::STORAGE_BUFFER, 1)]
+        );
+        assert_eq!(reflection.push_constant_size, 8);
+        // `gl_GlobalInvocationID` is a built-in.
+        assert!(reflection.inputs.is_empty());
+    }
+
+    #[test]
+    fn merges_stage_flags_of_shared_bindings() {
+        let vertex = reflect(include_bytes!("shaders/base_shader_vert.spv"));
+        let fragment = reflect(include_bytes!("shaders/base_shader_frag.spv"));
+        let bindings = layout_bindings(&[&vertex, &fragment], 0);
+        let stages: Vec<_> = bindings
+            .iter()
+            .map(|binding| (binding.binding, binding.stage_flags))
+            .collect();
+        assert_eq!(
+            stages,
+            [
+                (0, ShaderStageFlags::FRAGMENT),
+                (1, ShaderStageFlags::VERTEX),
+                (2, ShaderStageFlags::VERTEX),
+            ]
+        );
+        assert_eq!(
+            push_constant_ranges(&[&vertex, &fragment])
+                .iter()
+                .map(|range| (range.stage_flags, range.size))
+                .collect::<Vec<_>>(),
+            [(ShaderStageFlags::VERTEX, 4)]
+        );
+    }
+
+    #[test]
+    fn layout_mismatches_accept_dynamic_buffers_and_report_the_rest() {
+        let vertex = reflect(include_bytes!("shaders/base_shader_vert.spv"));
+        let layout_binding = |binding, descriptor_type, stage_flags| {
+            DescriptorSetLayoutBinding::builder()
+                .binding(binding)
+                .descriptor_type(descriptor_type)
+                .descriptor_count(1)
+                .stage_flags(stage_flags)
+                .build()
+        };
+        let layout = [
+            layout_binding(1, DescriptorType::UNIFORM_BUFFER, ShaderStageFlags::VERTEX),
+            layout_binding(
+                2,
+                DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+                ShaderStageFlags::VERTEX,
+            ),
+        ];
+        assert!(layout_mismatches(&[&vertex], 0, &layout).is_empty());
+
+        let layout = [layout_binding(
+            1,
+            DescriptorType::STORAGE_BUFFER,
+            ShaderStageFlags::FRAGMENT,
+        )];
+        assert_eq!(layout_mismatches(&[&vertex], 0, &layout).len(), 3);
+    }
+
+    #[test]
+    fn input_mismatches_report_missing_and_mistyped_attributes() {
+        let vertex = reflect(include_bytes!("shaders/base_shader_vert.spv"));
+        let attribute = |location, format| {
+            VertexInputAttributeDescription::builder()
+                .location(location)
+                .format(format)
+                .build()
+        };
+        let attributes = [
+            attribute(0, Format::R32G32B32_SFLOAT),
+            attribute(1, Format::R32G32B32_SFLOAT),
+            attribute(2, Format::R32G32_SFLOAT),
+            attribute(3, Format::R32G32B32_SFLOAT),
+        ];
+        let mismatches = input_mismatches(&vertex, &attributes);
+        assert_eq!(mismatches.len(), 2);
+        assert!(mismatches[0].starts_with("vertex input 1 "));
+        assert!(mismatches[1].starts_with("vertex input 4 "));
+    }
+
+    #[test]
+    fn push_constant_mismatches_need_a_large_enough_range_for_the_stage() {
+        let compute = reflect(include_bytes!("shaders/scale_storage_comp.spv"));
+        let range = |stage_flags, size| {
+            PushConstantRange::builder()
+                .stage_flags(stage_flags)
+                .size(size)
+                .build()
+        };
+        assert!(
+            push_constant_mismatches(&[&compute], &[range(ShaderStageFlags::COMPUTE, 8)])
+                .is_empty()
+        );
+        assert_eq!(
+            push_constant_mismatches(&[&compute], &[range(ShaderStageFlags::COMPUTE, 4)]).len(),
+            1
+        );
+        assert_eq!(
+            push_constant_mismatches(&[&compute], &[range(ShaderStageFlags::VERTEX, 8)]).len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use ash::vk::{
+    AccessFlags, AttachmentDescription, AttachmentLoadOp, AttachmentReference, AttachmentStoreOp,
+    DependencyFlags, Format, ImageLayout, PipelineBindPoint, PipelineStageFlags,
+    RenderPassCreateInfo, SampleCountFlags, SubpassDependency, SubpassDescription,
+};
+use log::warn;
+
+use super::{
+    constants::{GBUFFER_ALBEDO_FORMAT, GBUFFER_NORMAL_FORMAT},
+    deferred::LIGHTING_SUBPASS,
+    device::Device,
+    pipeline_graphics::PipelineOptions,
+};
+
+/// Attachments used by one subpass, as indices into the render pass's attachments.
+#[derive(Clone, Debug, Default)]
+pub struct Subpass {
+    pub color_attachments: Vec<AttachmentReference>,
+    /// Empty, or one per color attachment the multisampled color attachment is resolved into.
+    pub resolve_attachments: Vec<AttachmentReference>,
+    /// Attachments written by earlier subpasses, read with `subpassLoad` in the fragment shader.
+    pub input_attachments: Vec<AttachmentReference>,
+    pub depth_stencil_attachment: Option<AttachmentReference>,
+}
+
+impl Subpass {
+    pub fn color(mut self, attachment: u32) -> Self {
+        self.color_attachments
+            .push(reference(attachment, ImageLayout::COLOR_ATTACHMENT_OPTIMAL));
+        self
+    }
+
+    pub fn resolve(mut self, attachment: u32) -> Self {
+        self.resolve_attachments
+            .push(reference(attachment, ImageLayout::COLOR_ATTACHMENT_OPTIMAL));
+        self
+    }
+
+    pub fn input(mut self, attachment: u32, layout: ImageLayout) -> Self {
+        self.input_attachments.push(reference(attachment, layout));
+        self
+    }
+
+    pub fn depth_stencil(mut self, attachment: u32) -> Self {
+        self.depth_stencil_attachment = Some(reference(
+            attachment,
+            ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        ));
+        self
+    }
+}
+
+/// Attachments, subpasses and the dependencies between them. Subpasses are begun in order,
+/// moving to the next one with `cmd_next_subpass`.
+#[derive(Clone, Debug, Default)]
+pub struct RenderPassBuilder {
+    pub attachments: Vec<AttachmentDescription>,
+    pub subpasses: Vec<Subpass>,
+    pub dependencies: Vec<SubpassDependency>,
+}
+
+impl RenderPassBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn attachment(mut self, attachment: AttachmentDescription) -> Self {
+        self.attachments.push(attachment);
+        self
+    }
+
+    pub fn subpass(mut self, subpass: Subpass) -> Self {
+        self.subpasses.push(subpass);
+        self
+    }
+
+    pub fn dependency(mut self, dependency: SubpassDependency) -> Self {
+        self.dependencies.push(dependency);
+        self
+    }
+
+    /// Dependency from the work before the pass into `dst_subpass`, with stages and accesses
+    /// derived from its color and depth-stencil attachments: the previous frame's writes to them
+    /// have to finish before the load ops and draws of this one. Add the subpass first. Input
+    /// attachments aren't covered, they're written by earlier subpasses of the same pass.
+    pub fn external_dependency(self, dst_subpass: u32) -> Self {
+        let subpass = &self.subpasses[dst_subpass as usize];
+        let mut src_stage_mask = PipelineStageFlags::empty();
+        let mut dst_stage_mask = PipelineStageFlags::empty();
+        let mut src_access_mask = AccessFlags::empty();
+        let mut dst_access_mask = AccessFlags::empty();
+        if !subpass.color_attachments.is_empty() || !subpass.resolve_attachments.is_empty() {
+            src_stage_mask |= PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT;
+            dst_stage_mask |= PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT;
+            src_access_mask |= AccessFlags::COLOR_ATTACHMENT_WRITE;
+            // Blending reads the attachment as well.
+            dst_access_mask |=
+                AccessFlags::COLOR_ATTACHMENT_READ | AccessFlags::COLOR_ATTACHMENT_WRITE;
+        }
+        if let Some(reference) = subpass.depth_stencil_attachment {
+            let attachment = &self.attachments[reference.attachment as usize];
+            let fragment_tests =
+                PipelineStageFlags::EARLY_FRAGMENT_TESTS | PipelineStageFlags::LATE_FRAGMENT_TESTS;
+            src_stage_mask |= fragment_tests;
+            dst_stage_mask |= fragment_tests;
+            src_access_mask |= AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE;
+            // Clear and don't care load ops write the attachment.
+            dst_access_mask |= AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE;
+            if attachment.load_op == AttachmentLoadOp::LOAD
+                || attachment.stencil_load_op == AttachmentLoadOp::LOAD
+            {
+                dst_access_mask |= AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ;
+            }
+        }
+        self.dependency(
+            SubpassDependency::builder()
+                .src_subpass(ash::vk::SUBPASS_EXTERNAL)
+                .dst_subpass(dst_subpass)
+                .src_stage_mask(src_stage_mask)
+                .dst_stage_mask(dst_stage_mask)
+                .src_access_mask(src_access_mask)
+                .dst_access_mask(dst_access_mask)
+                .build(),
+        )
+    }
+
+    /// Format and samples are taken from the first subpass's first color and depth-stencil
+    /// attachments.
+    pub fn build(&self, device: &Device) -> RenderPass {
+        let subpasses: Vec<_> = self
+            .subpasses
+            .iter()
+            .map(|subpass| {
+                let mut description = SubpassDescription::builder()
+                    .pipeline_bind_point(PipelineBindPoint::GRAPHICS)
+                    .color_attachments(&subpass.color_attachments)
+                    .input_attachments(&subpass.input_attachments);
+                if !subpass.resolve_attachments.is_empty() {
+                    description = description.resolve_attachments(&subpass.resolve_attachments);
+                }
+                if let Some(depth_stencil_attachment) = &subpass.depth_stencil_attachment {
+                    description = description.depth_stencil_attachment(depth_stencil_attachment);
+                }
+                description.build()
+            })
+            .collect();
+        let create_info = RenderPassCreateInfo::builder()
+            .attachments(&self.attachments)
+            .subpasses(&subpasses)
+            .dependencies(&self.dependencies);
+
+        let inner = unsafe { device.inner.create_render_pass(&create_info, None).unwrap() };
+
+        let first = &self.subpasses[0];
+        let color = first
+            .color_attachments
+            .first()
+            .map(|reference| self.attachments[reference.attachment as usize]);
+        let depth_format = first
+            .depth_stencil_attachment
+            .map_or(Format::UNDEFINED, |reference| {
+                self.attachments[reference.attachment as usize].format
+            });
+        RenderPass {
+            inner,
+            color_format: color.map_or(Format::UNDEFINED, |color| color.format),
+            depth_format,
+            samples: color.map_or(SampleCountFlags::TYPE_1, |color| color.samples),
+            subpass_count: subpasses.len() as u32,
+            color_attachment_counts: self
+                .subpasses
+                .iter()
+                .map(|subpass| subpass.color_attachments.len())
+                .collect(),
+            gbuffer: false,
+            device: device.inner.clone(),
+        }
+    }
+}
+
+/// Created before the pipelines drawing in it, which can share it, and the framebuffers
+/// rendered into with it.
+pub struct RenderPass {
+    /// Null for `VK_KHR_dynamic_rendering`, see `RenderPass::dynamic`.
+    pub inner: ash::vk::RenderPass,
+    pub color_format: Format,
+    /// Format of the depth-stencil attachment, `UNDEFINED` without one.
+    pub depth_format: Format,
+    pub samples: SampleCountFlags,
+    /// Each one has to be begun with `cmd_next_subpass` before the pass ends.
+    pub subpass_count: u32,
+    /// Color attachments written by each subpass, one blend state each.
+    pub color_attachment_counts: Vec<usize>,
+    /// The framebuffers need the G-buffer attachments of a deferred main pass.
+    pub gbuffer: bool,
+    device: ash::Device,
+}
+
+impl RenderPass {
+    /// Render pass of the main pipeline. Unsupported combinations in `options` are fixed up with
+    /// a warning, the pipeline has to be created with the fixed options.
+    pub fn new_main(
+        device: &Device,
+        color_format: Format,
+        depth_format: Format,
+        samples: SampleCountFlags,
+        final_layout: ImageLayout,
+        options: &mut PipelineOptions,
+    ) -> Self {
+        if options.dynamic_rendering {
+            return Self::dynamic(device, color_format, depth_format, samples);
+        }
+
+        let multisampled = samples != SampleCountFlags::TYPE_1;
+        if options.color_load_op == AttachmentLoadOp::LOAD {
+            if multisampled {
+                warn!("Loading the color attachment is not supported with MSAA, clearing it");
+                options.color_load_op = AttachmentLoadOp::CLEAR;
+                options.color_initial_layout = ImageLayout::UNDEFINED;
+            } else if options.color_initial_layout == ImageLayout::UNDEFINED {
+                warn!(
+                    "Loading the color attachment needs an initial layout, using {:?}",
+                    final_layout
+                );
+                options.color_initial_layout = final_layout;
+            }
+        }
+
+        if options.deferred {
+            let mut render_pass =
+                deferred_render_pass(color_format, depth_format, final_layout, options)
+                    .build(device);
+            render_pass.gbuffer = true;
+            return render_pass;
+        }
+
+        // With MSAA the multisampled image is rendered to and resolved into the swapchain image
+        // at the end of the subpass, so only the resolve attachment has to be stored.
+        let attachment_description = AttachmentDescription::builder()
+            .format(color_format)
+            .samples(samples)
+            .load_op(options.color_load_op)
+            .store_op(if multisampled {
+                AttachmentStoreOp::DONT_CARE
+            } else {
+                AttachmentStoreOp::STORE
+            })
+            .initial_layout(options.color_initial_layout)
+            .final_layout(if multisampled {
+                ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+            } else {
+                final_layout
+            });
+
+        let resolve_attachment_description = AttachmentDescription::builder()
+            .format(color_format)
+            .samples(SampleCountFlags::TYPE_1)
+            .load_op(AttachmentLoadOp::DONT_CARE)
+            .store_op(AttachmentStoreOp::STORE)
+            .initial_layout(ImageLayout::UNDEFINED)
+            .final_layout(final_layout);
+
+        let mut render_pass = RenderPassBuilder::new().attachment(attachment_description.build());
+        let mut subpass = Subpass::default().color(0);
+        if multisampled {
+            render_pass = render_pass.attachment(resolve_attachment_description.build());
+            subpass = subpass.resolve(1);
+        }
+        // The depth-stencil attachment always comes last.
+        let subpass = subpass.depth_stencil(render_pass.attachments.len() as u32);
+        render_pass
+            .attachment(depth_stencil_attachment(
+                depth_format,
+                samples,
+                AttachmentLoadOp::CLEAR,
+            ))
+            .subpass(subpass)
+            .external_dependency(0)
+            .build(device)
+    }
+
+    /// Pass of `GraphicsPipeline::new_fullscreen`. `depth_format` is that of the main pass.
+    /// Depth isn't used, but the attachment keeps the render pass compatible with the framebuffers
+    /// the scene was drawn into.
+    pub fn new_fullscreen(
+        device: &Device,
+        color_format: Format,
+        depth_format: Format,
+        final_layout: ImageLayout,
+    ) -> Self {
+        let samples = SampleCountFlags::TYPE_1;
+        // Every pixel is overwritten, so the previous contents don't have to be loaded.
+        RenderPassBuilder::new()
+            .attachment(
+                AttachmentDescription::builder()
+                    .format(color_format)
+                    .samples(samples)
+                    .load_op(AttachmentLoadOp::DONT_CARE)
+                    .store_op(AttachmentStoreOp::STORE)
+                    .initial_layout(ImageLayout::UNDEFINED)
+                    .final_layout(final_layout)
+                    .build(),
+            )
+            .attachment(depth_stencil_attachment(
+                depth_format,
+                samples,
+                AttachmentLoadOp::DONT_CARE,
+            ))
+            .subpass(Subpass::default().color(0).depth_stencil(1))
+            .external_dependency(0)
+            .build(device)
+    }
+
+    /// Stands in for a render pass with dynamic rendering, pipelines take the formats from it.
+    pub fn dynamic(
+        device: &Device,
+        color_format: Format,
+        depth_format: Format,
+        samples: SampleCountFlags,
+    ) -> Self {
+        Self {
+            inner: ash::vk::RenderPass::null(),
+            color_format,
+            depth_format,
+            samples,
+            subpass_count: 1,
+            color_attachment_counts: vec![1],
+            gbuffer: false,
+            device: device.inner.clone(),
+        }
+    }
+
+    /// Created for `VK_KHR_dynamic_rendering`, without a render pass or framebuffers.
+    pub fn uses_dynamic_rendering(&self) -> bool {
+        self.inner == ash::vk::RenderPass::null()
+    }
+}
+
+impl Drop for RenderPass {
+    fn drop(&mut self) {
+        if !self.uses_dynamic_rendering() {
+            unsafe {
+                self.device.destroy_render_pass(self.inner, None);
+            }
+        }
+    }
+}
+
+fn reference(attachment: u32, layout: ImageLayout) -> AttachmentReference {
+    AttachmentReference::builder()
+        .attachment(attachment)
+        .layout(layout)
+        .build()
+}
+
+/// Scene subpass 0 writes the G-buffer attachments 1 and 2, `LIGHTING_SUBPASS` reads them as
+/// input attachments and writes the color attachment 0.
+fn deferred_render_pass(
+    color_format: Format,
+    depth_format: Format,
+    final_layout: ImageLayout,
+    options: &PipelineOptions,
+) -> RenderPassBuilder {
+    let samples = SampleCountFlags::TYPE_1;
+    // Only needed until the lighting subpass has read them.
+    let gbuffer_attachment = |format| {
+        AttachmentDescription::builder()
+            .format(format)
+            .samples(samples)
+            .load_op(AttachmentLoadOp::CLEAR)
+            .store_op(AttachmentStoreOp::DONT_CARE)
+            .initial_layout(ImageLayout::UNDEFINED)
+            .final_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .build()
+    };
+    RenderPassBuilder::new()
+        .attachment(
+            AttachmentDescription::builder()
+                .format(color_format)
+                .samples(samples)
+                .load_op(options.color_load_op)
+                .store_op(AttachmentStoreOp::STORE)
+                .initial_layout(options.color_initial_layout)
+                .final_layout(final_layout)
+                .build(),
+        )
+        .attachment(gbuffer_attachment(GBUFFER_ALBEDO_FORMAT))
+        .attachment(gbuffer_attachment(GBUFFER_NORMAL_FORMAT))
+        .attachment(depth_stencil_attachment(
+            depth_format,
+            samples,
+            AttachmentLoadOp::CLEAR,
+        ))
+        .subpass(Subpass::default().color(1).color(2).depth_stencil(3))
+        .subpass(
+            Subpass::default()
+                .color(0)
+                .input(1, ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .input(2, ImageLayout::SHADER_READ_ONLY_OPTIMAL),
+        )
+        .external_dependency(0)
+        // The swapchain image is only written by the lighting subpass.
+        .external_dependency(LIGHTING_SUBPASS)
+        .dependency(
+            SubpassDependency::builder()
+                .src_subpass(0)
+                .dst_subpass(LIGHTING_SUBPASS)
+                .src_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_stage_mask(PipelineStageFlags::FRAGMENT_SHADER)
+                .src_access_mask(AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_access_mask(AccessFlags::INPUT_ATTACHMENT_READ)
+                .dependency_flags(DependencyFlags::BY_REGION)
+                .build(),
+        )
+}
+
+/// Depth-stencil attachment, last in every render pass drawing into the main framebuffers. Passes
+/// that don't test depth still declare it to stay compatible with those framebuffers.
+pub fn depth_stencil_attachment(
+    format: Format,
+    samples: SampleCountFlags,
+    load_op: AttachmentLoadOp,
+) -> AttachmentDescription {
+    AttachmentDescription::builder()
+        .format(format)
+        .samples(samples)
+        .load_op(load_op)
+        .store_op(AttachmentStoreOp::DONT_CARE)
+        .stencil_load_op(load_op)
+        .stencil_store_op(AttachmentStoreOp::DONT_CARE)
+        .initial_layout(ImageLayout::UNDEFINED)
+        .final_layout(ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+        .build()
+}
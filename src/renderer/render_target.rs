@@ -0,0 +1,173 @@
+use ash::vk::{
+    AccessFlags, AttachmentDescription, AttachmentLoadOp, AttachmentStoreOp, ClearColorValue,
+    ClearDepthStencilValue, ClearValue, CommandBuffer, Extent2D, Format, Framebuffer,
+    FramebufferCreateInfo, ImageLayout, ImageUsageFlags, ImageView, MemoryPropertyFlags, Offset2D,
+    PipelineStageFlags, Rect2D, RenderPassBeginInfo, SampleCountFlags, SubpassContents,
+    SubpassDependency, Viewport,
+};
+
+use super::{
+    device::Device,
+    image::Image,
+    render_pass::{depth_stencil_attachment, RenderPass, RenderPassBuilder, Subpass},
+};
+
+/// Color image with an optional depth image that can be rendered into and then sampled by a later pass.
+pub struct RenderTarget {
+    pub framebuffer: Framebuffer,
+    pub render_pass: RenderPass,
+    pub color: Image,
+    pub depth: Option<Image>,
+    pub extent: Extent2D,
+    device: ash::Device,
+}
+
+impl RenderTarget {
+    /// The color image ends up in `SHADER_READ_ONLY_OPTIMAL` after each pass.
+    pub fn new(
+        device: &Device,
+        extent: Extent2D,
+        color_format: Format,
+        depth_format: Option<Format>,
+    ) -> Self {
+        let color = Image::new(
+            device,
+            extent,
+            1,
+            SampleCountFlags::TYPE_1,
+            color_format,
+            ImageUsageFlags::COLOR_ATTACHMENT
+                | ImageUsageFlags::SAMPLED
+                | ImageUsageFlags::TRANSFER_SRC,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        let depth = depth_format.map(|depth_format| {
+            Image::new(
+                device,
+                extent,
+                1,
+                SampleCountFlags::TYPE_1,
+                depth_format,
+                ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                MemoryPropertyFlags::DEVICE_LOCAL,
+            )
+        });
+
+        let mut render_pass = RenderPassBuilder::new().attachment(
+            AttachmentDescription::builder()
+                .format(color_format)
+                .samples(SampleCountFlags::TYPE_1)
+                .load_op(AttachmentLoadOp::CLEAR)
+                .store_op(AttachmentStoreOp::STORE)
+                .initial_layout(ImageLayout::UNDEFINED)
+                .final_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .build(),
+        );
+        let mut subpass = Subpass::default().color(0);
+        if let Some(depth_format) = depth_format {
+            render_pass = render_pass.attachment(depth_stencil_attachment(
+                depth_format,
+                SampleCountFlags::TYPE_1,
+                AttachmentLoadOp::CLEAR,
+            ));
+            subpass = subpass.depth_stencil(1);
+        }
+        let render_pass = render_pass
+            .subpass(subpass)
+            .external_dependency(0)
+            // The next pass samples the color image.
+            .dependency(
+                SubpassDependency::builder()
+                    .src_subpass(0)
+                    .dst_subpass(ash::vk::SUBPASS_EXTERNAL)
+                    .src_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                    .dst_stage_mask(PipelineStageFlags::FRAGMENT_SHADER)
+                    .src_access_mask(AccessFlags::COLOR_ATTACHMENT_WRITE)
+                    .dst_access_mask(AccessFlags::SHADER_READ)
+                    .build(),
+            )
+            .build(device);
+
+        let mut views = vec![color.view];
+        views.extend(depth.as_ref().map(|depth| depth.view));
+        let create_info = FramebufferCreateInfo::builder()
+            .render_pass(render_pass.inner)
+            .attachments(&views)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1);
+        let framebuffer = unsafe { device.inner.create_framebuffer(&create_info, None).unwrap() };
+
+        Self {
+            framebuffer,
+            render_pass,
+            color,
+            depth,
+            extent,
+            device: device.inner.clone(),
+        }
+    }
+
+    /// View of the color image for sampling in a later pass.
+    pub fn view(&self) -> ImageView {
+        self.color.view
+    }
+
+    /// Begins the render pass, clearing color to `clear_color` and depth to 1, and sets the
+    /// viewport and scissor to the whole target.
+    pub fn begin(&self, command_buffer: CommandBuffer, clear_color: [f32; 4]) {
+        let clear_values = [
+            ClearValue {
+                color: ClearColorValue {
+                    float32: clear_color,
+                },
+            },
+            ClearValue {
+                depth_stencil: ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+        let clear_value_count = if self.depth.is_some() { 2 } else { 1 };
+        let render_area = Rect2D::builder()
+            .offset(Offset2D { x: 0, y: 0 })
+            .extent(self.extent)
+            .build();
+        let begin_info = RenderPassBeginInfo::builder()
+            .render_pass(self.render_pass.inner)
+            .framebuffer(self.framebuffer)
+            .render_area(render_area)
+            .clear_values(&clear_values[..clear_value_count]);
+
+        unsafe {
+            self.device
+                .cmd_begin_render_pass(command_buffer, &begin_info, SubpassContents::INLINE);
+            self.device.cmd_set_viewport(
+                command_buffer,
+                0,
+                &[Viewport::builder()
+                    .width(self.extent.width as f32)
+                    .height(self.extent.height as f32)
+                    .max_depth(1.0)
+                    .build()],
+            );
+            self.device
+                .cmd_set_scissor(command_buffer, 0, &[render_area]);
+        }
+    }
+
+    pub fn end(&self, command_buffer: CommandBuffer) {
+        unsafe {
+            self.device.cmd_end_render_pass(command_buffer);
+        }
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_framebuffer(self.framebuffer, None);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use ash::vk::{
+    BorderColor, CompareOp, Filter, Format, FormatFeatureFlags, SamplerAddressMode,
+    SamplerCreateInfo, SamplerMipmapMode, LOD_CLAMP_NONE,
+};
+use log::warn;
+
+use super::device::Device;
+
+pub struct Sampler {
+    pub inner: ash::vk::Sampler,
+    device: ash::Device,
+}
+
+impl Sampler {
+    /// Linear filtering with repeating texture coordinates. Anisotropic filtering is used if
+    /// `max_anisotropy` is above 1.0 and the feature was enabled on `device`.
+    pub fn new(device: &Device, max_anisotropy: f32) -> Self {
+        Sampler::builder()
+            .max_anisotropy(max_anisotropy)
+            .create(device)
+    }
+
+    pub fn builder() -> SamplerBuilder {
+        SamplerBuilder::default()
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_sampler(self.inner, None);
+        }
+    }
+}
+
+/// Sampler state, defaults to trilinear filtering with repeating texture coordinates and no
+/// anisotropy. `nearest` is meant for pixel art.
+#[derive(Clone, Copy, Debug)]
+pub struct SamplerBuilder {
+    pub mag_filter: Filter,
+    pub min_filter: Filter,
+    pub mipmap_mode: SamplerMipmapMode,
+    /// U, V and W address modes.
+    pub address_modes: [SamplerAddressMode; 3],
+    pub mip_lod_bias: f32,
+    /// Clamped to the device limit. 1.0 or an unsupported device disables it.
+    pub max_anisotropy: f32,
+}
+
+impl Default for SamplerBuilder {
+    fn default() -> Self {
+        Self {
+            mag_filter: Filter::LINEAR,
+            min_filter: Filter::LINEAR,
+            mipmap_mode: SamplerMipmapMode::LINEAR,
+            address_modes: [SamplerAddressMode::REPEAT; 3],
+            mip_lod_bias: 0.0,
+            max_anisotropy: 1.0,
+        }
+    }
+}
+
+impl SamplerBuilder {
+    /// Unfiltered texels and mip levels, keeping pixel art sharp.
+    pub fn nearest() -> Self {
+        Self {
+            mag_filter: Filter::NEAREST,
+            min_filter: Filter::NEAREST,
+            mipmap_mode: SamplerMipmapMode::NEAREST,
+            ..Self::default()
+        }
+    }
+
+    pub fn mag_filter(mut self, filter: Filter) -> Self {
+        self.mag_filter = filter;
+        self
+    }
+
+    pub fn min_filter(mut self, filter: Filter) -> Self {
+        self.min_filter = filter;
+        self
+    }
+
+    pub fn mipmap_mode(mut self, mipmap_mode: SamplerMipmapMode) -> Self {
+        self.mipmap_mode = mipmap_mode;
+        self
+    }
+
+    pub fn address_modes(
+        mut self,
+        u: SamplerAddressMode,
+        v: SamplerAddressMode,
+        w: SamplerAddressMode,
+    ) -> Self {
+        self.address_modes = [u, v, w];
+        self
+    }
+
+    /// Same address mode on all axes.
+    pub fn address_mode(self, address_mode: SamplerAddressMode) -> Self {
+        self.address_modes(address_mode, address_mode, address_mode)
+    }
+
+    pub fn mip_lod_bias(mut self, mip_lod_bias: f32) -> Self {
+        self.mip_lod_bias = mip_lod_bias;
+        self
+    }
+
+    pub fn max_anisotropy(mut self, max_anisotropy: f32) -> Self {
+        self.max_anisotropy = max_anisotropy;
+        self
+    }
+
+    /// Creates a sampler for images of `format`. Linear filtering falls back to `NEAREST` if the
+    /// format doesn't support it.
+    pub fn build(&self, instance: &ash::Instance, device: &Device, format: Format) -> Sampler {
+        let format_properties = unsafe {
+            instance.get_physical_device_format_properties(device.physical_device.inner, format)
+        };
+        let mut builder = *self;
+        if !format_properties
+            .optimal_tiling_features
+            .contains(FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR)
+        {
+            if builder.mipmap_mode == SamplerMipmapMode::LINEAR
+                || builder.mag_filter == Filter::LINEAR
+                || builder.min_filter == Filter::LINEAR
+            {
+                warn!(
+                    "{:?} does not support linear filtering, using nearest filtering",
+                    format
+                );
+            }
+            builder.mag_filter = Filter::NEAREST;
+            builder.min_filter = Filter::NEAREST;
+            builder.mipmap_mode = SamplerMipmapMode::NEAREST;
+        }
+        builder.create(device)
+    }
+
+    fn create(&self, device: &Device) -> Sampler {
+        let max_anisotropy = self.max_anisotropy.min(
+            device
+                .physical_device
+                .properties
+                .limits
+                .max_sampler_anisotropy,
+        );
+        let anisotropy_enable =
+            device.enabled_features.sampler_anisotropy != 0 && max_anisotropy > 1.0;
+        let [address_mode_u, address_mode_v, address_mode_w] = self.address_modes;
+        let create_info = SamplerCreateInfo::builder()
+            .mag_filter(self.mag_filter)
+            .min_filter(self.min_filter)
+            .mipmap_mode(self.mipmap_mode)
+            .address_mode_u(address_mode_u)
+            .address_mode_v(address_mode_v)
+            .address_mode_w(address_mode_w)
+            .anisotropy_enable(anisotropy_enable)
+            .max_anisotropy(if anisotropy_enable {
+                max_anisotropy
+            } else {
+                1.0
+            })
+            .border_color(BorderColor::INT_OPAQUE_BLACK)
+            .unnormalized_coordinates(false)
+            .compare_enable(false)
+            .compare_op(CompareOp::ALWAYS)
+            .min_lod(0.0)
+            .max_lod(LOD_CLAMP_NONE)
+            .mip_lod_bias(self.mip_lod_bias);
+
+        let inner = unsafe { device.inner.create_sampler(&create_info, None).unwrap() };
+
+        Sampler {
+            inner,
+            device: device.inner.clone(),
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::path::Path;
+
+use glam::{Mat3, Mat4, Vec3};
+use gltf::{image::Format, mesh::Mode, Node};
+use log::warn;
+
+use super::vertex::Vertex;
+
+/// Meshes, materials and images of a glTF file, ready to upload.
+#[derive(Clone, Debug, Default)]
+pub struct Scene {
+    pub draw_items: Vec<DrawItem>,
+    pub materials: Vec<Material>,
+    /// Images converted to RGBA8, e.g. for `Renderer::register_texture_rgba8`.
+    pub textures: Vec<TextureData>,
+}
+
+impl Scene {
+    /// Every draw item transformed to world space and merged into one mesh for
+    /// `Renderer::set_mesh`, with the base color of its material multiplied into the vertex
+    /// colors. Textures are left out, the renderer samples a single one per object.
+    pub fn flatten(&self) -> (Vec<Vertex>, Vec<u32>) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for item in &self.draw_items {
+            let base = vertices.len() as u32;
+            let normal_matrix = Mat3::from_mat4(item.transform).inverse().transpose();
+            let base_color = item
+                .material
+                .and_then(|index| self.materials.get(index))
+                .copied()
+                .unwrap_or_default()
+                .base_color;
+            vertices.extend(item.vertices.iter().map(|vertex| {
+                Vertex {
+                    pos: item
+                        .transform
+                        .transform_point3(Vec3::from(vertex.pos))
+                        .into(),
+                    color: [0, 1, 2, 3].map(|i| vertex.color[i] * base_color[i]),
+                    uv: vertex.uv,
+                    normal: (normal_matrix * Vec3::from(vertex.normal))
+                        .normalize_or_zero()
+                        .into(),
+                }
+            }));
+            indices.extend(item.indices.iter().map(|index| base + index));
+        }
+        (vertices, indices)
+    }
+}
+
+/// One triangle primitive of a mesh, in the space of the node it's attached to.
+#[derive(Clone, Debug)]
+pub struct DrawItem {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    /// Index into `Scene::materials`, `None` for the default material.
+    pub material: Option<usize>,
+    /// Node to world transform.
+    pub transform: Mat4,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Material {
+    pub base_color: [f32; 4],
+    /// Index into `Scene::textures`.
+    pub base_color_texture: Option<usize>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            base_color: [1.0; 4],
+            base_color_texture: None,
+        }
+    }
+}
+
+/// Tightly packed RGBA8 pixels.
+#[derive(Clone, Debug)]
+pub struct TextureData {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Loads the default scene of a `.gltf` or `.glb` file, or its first scene if there's no
+/// default. Only triangle lists are kept, other primitives are skipped with a warning.
+pub fn load_gltf(path: &Path) -> gltf::Result<Scene> {
+    let (document, buffers, images) = gltf::import(path)?;
+
+    let materials = document
+        .materials()
+        .map(|material| {
+            let pbr = material.pbr_metallic_roughness();
+            Material {
+                base_color: pbr.base_color_factor(),
+                base_color_texture: pbr
+                    .base_color_texture()
+                    .map(|info| info.texture().source().index()),
+            }
+        })
+        .collect();
+    let textures = images.iter().map(to_rgba8).collect();
+
+    let mut draw_items = Vec::new();
+    if let Some(scene) = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        for node in scene.nodes() {
+            visit_node(&node, Mat4::IDENTITY, &buffers, &mut draw_items);
+        }
+    }
+
+    Ok(Scene {
+        draw_items,
+        materials,
+        textures,
+    })
+}
+
+fn visit_node(
+    node: &Node,
+    parent_transform: Mat4,
+    buffers: &[gltf::buffer::Data],
+    draw_items: &mut Vec<DrawItem>,
+) {
+    let transform = parent_transform * Mat4::from_cols_array_2d(&node.transform().matrix());
+    if let Some(mesh) = node.mesh() {
+        for primitive in mesh.primitives() {
+            if primitive.mode() != Mode::Triangles {
+                warn!(
+                    "Skipping {:?} primitive of mesh {}, only triangles are supported",
+                    primitive.mode(),
+                    mesh.index()
+                );
+                continue;
+            }
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let Some(positions) = reader.read_positions() else {
+                continue;
+            };
+            let mut vertices: Vec<_> = positions
+                .map(|pos| Vertex {
+                    pos,
+                    color: [1.0; 4],
+                    uv: [0.0; 2],
+                    normal: [0.0, 1.0, 0.0],
+                })
+                .collect();
+            if let Some(normals) = reader.read_normals() {
+                for (vertex, normal) in vertices.iter_mut().zip(normals) {
+                    vertex.normal = normal;
+                }
+            }
+            if let Some(uvs) = reader.read_tex_coords(0) {
+                for (vertex, uv) in vertices.iter_mut().zip(uvs.into_f32()) {
+                    vertex.uv = uv;
+                }
+            }
+            if let Some(colors) = reader.read_colors(0) {
+                for (vertex, color) in vertices.iter_mut().zip(colors.into_rgba_f32()) {
+                    vertex.color = color;
+                }
+            }
+            let indices = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..vertices.len() as u32).collect(),
+            };
+            draw_items.push(DrawItem {
+                vertices,
+                indices,
+                material: primitive.material().index(),
+                transform,
+            });
+        }
+    }
+    for child in node.children() {
+        visit_node(&child, transform, buffers, draw_items);
+    }
+}
+
+/// Expands 8 bit images to RGBA. Other formats are replaced by a white pixel.
+fn to_rgba8(image: &gltf::image::Data) -> TextureData {
+    let channels = match image.format {
+        Format::R8 => 1,
+        Format::R8G8 => 2,
+        Format::R8G8B8 => 3,
+        Format::R8G8B8A8 => 4,
+        format => {
+            warn!("Unsupported glTF image format {:?}, using white", format);
+            return TextureData {
+                width: 1,
+                height: 1,
+                pixels: vec![255; 4],
+            };
+        }
+    };
+    let pixels = image
+        .pixels
+        .chunks_exact(channels)
+        .flat_map(|texel| match texel {
+            [r] => [*r, *r, *r, 255],
+            [r, g] => [*r, *r, *r, *g],
+            [r, g, b] => [*r, *g, *b, 255],
+            [r, g, b, a] => [*r, *g, *b, *a],
+            _ => unreachable!(),
+        })
+        .collect();
+    TextureData {
+        width: image.width,
+        height: image.height,
+        pixels,
+    }
+}
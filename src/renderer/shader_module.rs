@@ -1,9 +1,11 @@
 use ash::vk::ShaderModuleCreateInfo;
 
-use super::device::Device;
+use super::{device::Device, reflect::ShaderReflection};
 
 pub struct ShaderModule {
     pub inner: ash::vk::ShaderModule,
+    /// Descriptors, push constants and inputs the code declares.
+    pub reflection: ShaderReflection,
     device: ash::Device,
 }
 
@@ -22,6 +24,7 @@ impl ShaderModule {
 
         ShaderModule {
             inner,
+            reflection: ShaderReflection::new(code),
             device: device.inner.clone(),
         }
     }
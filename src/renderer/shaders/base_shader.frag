@@ -1,9 +1,12 @@
 #version 450
 
-layout(location = 0) in vec3 fragColor;
+layout(set = 0, binding = 0) uniform sampler2D texSampler;
+
+layout(location = 0) in vec4 fragColor;
+layout(location = 1) in vec2 fragUv;
 
 layout(location = 0) out vec4 outColor;
 
 void main() {
-    outColor = vec4(fragColor, 1.0);
+    outColor = fragColor * texture(texSampler, fragUv);
 }
\ No newline at end of file
@@ -1,20 +1,39 @@
 #version 450
 
-layout(location = 0) out vec3 fragColor;
+layout(push_constant) uniform PushConstants {
+    float time;
+} push;
 
-vec2 positions[3] = vec2[](
-    vec2(0.0, -0.5),
-    vec2(0.5, 0.5),
-    vec2(-0.5, 0.5)
-);
+layout(set = 0, binding = 1) uniform Camera {
+    mat4 viewProjection;
+} camera;
 
-vec3 colors[3] = vec3[](
-    vec3(1.0, 0.0, 0.0),
-    vec3(0.0, 1.0, 0.0),
-    vec3(0.0, 0.0, 1.0)
-);
+layout(set = 0, binding = 2) uniform Object {
+    mat4 model;
+    uint textureIndex;
+} object;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec4 inColor;
+layout(location = 2) in vec2 inUv;
+layout(location = 3) in vec3 inNormal;
+layout(location = 4) in vec3 inOffset;
+
+layout(location = 0) out vec4 fragColor;
+layout(location = 1) out vec2 fragUv;
+layout(location = 2) flat out uint fragTextureIndex;
+layout(location = 3) out vec3 fragNormal;
 
 void main() {
-    gl_Position = vec4(positions[gl_VertexIndex], 0.0, 1.0);
-    fragColor = colors[gl_VertexIndex];
+    float s = sin(push.time);
+    float c = cos(push.time);
+    vec2 rotated = vec2(c * inPosition.x - s * inPosition.y, s * inPosition.x + c * inPosition.y);
+    gl_Position = camera.viewProjection * object.model * vec4(vec3(rotated, inPosition.z) + inOffset, 1.0);
+    // Only used when the mesh is drawn as a point list.
+    gl_PointSize = 1.0;
+    fragColor = inColor;
+    fragUv = inUv;
+    fragTextureIndex = object.textureIndex;
+    vec3 normal = vec3(c * inNormal.x - s * inNormal.y, s * inNormal.x + c * inNormal.y, inNormal.z);
+    fragNormal = mat3(object.model) * normal;
 }
\ No newline at end of file
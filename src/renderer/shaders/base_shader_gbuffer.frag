@@ -0,0 +1,16 @@
+#version 450
+
+// Writes the surface to the G-buffer, shaded later by the lighting subpass.
+layout(set = 0, binding = 0) uniform sampler2D texSampler;
+
+layout(location = 0) in vec4 fragColor;
+layout(location = 1) in vec2 fragUv;
+layout(location = 3) in vec3 fragNormal;
+
+layout(location = 0) out vec4 outAlbedo;
+layout(location = 1) out vec4 outNormal;
+
+void main() {
+    outAlbedo = vec4(fragColor.rgb, 1.0) * texture(texSampler, fragUv);
+    outNormal = vec4(normalize(fragNormal), 0.0);
+}
\ No newline at end of file
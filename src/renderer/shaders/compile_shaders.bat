@@ -1,3 +1,15 @@
 C:\VulkanSDK\1.3.216.0\Bin\glslc.exe base_shader.vert -o base_shader_vert.spv
 C:\VulkanSDK\1.3.216.0\Bin\glslc.exe base_shader.frag -o base_shader_frag.spv
+C:\VulkanSDK\1.3.216.0\Bin\glslc.exe fullscreen.vert -o fullscreen_vert.spv
+C:\VulkanSDK\1.3.216.0\Bin\glslc.exe post_grayscale.frag -o post_grayscale_frag.spv
+C:\VulkanSDK\1.3.216.0\Bin\glslc.exe ui.vert -o ui_vert.spv
+C:\VulkanSDK\1.3.216.0\Bin\glslc.exe ui.frag -o ui_frag.spv
+C:\VulkanSDK\1.3.216.0\Bin\glslc.exe primitive.vert -o primitive_vert.spv
+C:\VulkanSDK\1.3.216.0\Bin\glslc.exe primitive.frag -o primitive_frag.spv
+C:\VulkanSDK\1.3.216.0\Bin\glslc.exe base_shader_bindless.frag -o base_shader_bindless_frag.spv
+C:\VulkanSDK\1.3.216.0\Bin\glslc.exe base_shader.tesc -o base_shader_tesc.spv
+C:\VulkanSDK\1.3.216.0\Bin\glslc.exe base_shader.tese -o base_shader_tese.spv
+C:\VulkanSDK\1.3.216.0\Bin\glslc.exe base_shader_gbuffer.frag -o base_shader_gbuffer_frag.spv
+C:\VulkanSDK\1.3.216.0\Bin\glslc.exe deferred_lighting.frag -o deferred_lighting_frag.spv
+C:\VulkanSDK\1.3.216.0\Bin\glslc.exe scale_storage.comp -o scale_storage_comp.spv
 pause
\ No newline at end of file
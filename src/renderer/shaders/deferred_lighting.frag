@@ -0,0 +1,22 @@
+#version 450
+
+// Reads the G-buffer of the current pixel written by the previous subpass.
+layout(input_attachment_index = 0, set = 0, binding = 0) uniform subpassInput albedo;
+layout(input_attachment_index = 1, set = 0, binding = 1) uniform subpassInput normal;
+
+layout(location = 0) out vec4 outColor;
+
+const vec3 LIGHT_DIRECTION = normalize(vec3(0.3, 1.0, 0.5));
+const float AMBIENT = 0.2;
+
+void main() {
+    vec4 surface = subpassLoad(albedo);
+    vec3 surfaceNormal = subpassLoad(normal).xyz;
+    // The normal is cleared to zero, pixels without geometry keep the clear color.
+    if (surfaceNormal == vec3(0.0)) {
+        outColor = surface;
+        return;
+    }
+    float diffuse = max(dot(surfaceNormal, LIGHT_DIRECTION), 0.0);
+    outColor = vec4(surface.rgb * (AMBIENT + diffuse), surface.a);
+}
\ No newline at end of file
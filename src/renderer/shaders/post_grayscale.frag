@@ -0,0 +1,13 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(location = 0) in vec2 fragUv;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec4 color = texture(source, fragUv);
+    float luminance = dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+    outColor = vec4(vec3(luminance), color.a);
+}
\ No newline at end of file
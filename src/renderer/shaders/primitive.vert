@@ -0,0 +1,19 @@
+#version 450
+
+// Untextured debug primitives such as lines and points, in world space.
+layout(constant_id = 0) const float pointSize = 1.0;
+
+layout(set = 0, binding = 1) uniform Camera {
+    mat4 viewProjection;
+} camera;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec4 inColor;
+
+layout(location = 0) out vec4 fragColor;
+
+void main() {
+    gl_Position = camera.viewProjection * vec4(inPosition, 1.0);
+    gl_PointSize = pointSize;
+    fragColor = inColor;
+}
\ No newline at end of file
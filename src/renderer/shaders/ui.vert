@@ -0,0 +1,26 @@
+#version 450
+
+// egui meshes, positions are in points and colors are premultiplied sRGB.
+layout(push_constant) uniform PushConstants {
+    vec2 screenSize;
+} push;
+
+layout(location = 0) in vec2 inPosition;
+layout(location = 1) in vec2 inUv;
+layout(location = 2) in vec4 inColor;
+
+layout(location = 0) out vec4 fragColor;
+layout(location = 1) out vec2 fragUv;
+
+vec3 srgbToLinear(vec3 srgb) {
+    bvec3 cutoff = lessThan(srgb, vec3(0.04045));
+    vec3 lower = srgb / 12.92;
+    vec3 higher = pow((srgb + 0.055) / 1.055, vec3(2.4));
+    return mix(higher, lower, cutoff);
+}
+
+void main() {
+    gl_Position = vec4(2.0 * inPosition / push.screenSize - 1.0, 0.0, 1.0);
+    fragColor = vec4(srgbToLinear(inColor.rgb), inColor.a);
+    fragUv = inUv;
+}
\ No newline at end of file
@@ -0,0 +1,41 @@
+//! Stencil presets for `PipelineOptions::stencil`.
+//!
+//! For an outline, draw the object with `write(1)`, then draw it again slightly scaled up with
+//! `test_not_equal(1)`: only the rim outside the first draw passes.
+
+use ash::vk::{CompareOp, StencilOp, StencilOpState};
+
+/// Always passes and writes `reference` wherever the depth test passes.
+pub fn write(reference: u32) -> StencilOpState {
+    StencilOpState {
+        fail_op: StencilOp::KEEP,
+        pass_op: StencilOp::REPLACE,
+        depth_fail_op: StencilOp::KEEP,
+        compare_op: CompareOp::ALWAYS,
+        compare_mask: 0xff,
+        write_mask: 0xff,
+        reference,
+    }
+}
+
+/// Only draws where the stencil equals `reference`, e.g. inside a portal, without modifying it.
+pub fn test_equal(reference: u32) -> StencilOpState {
+    test(CompareOp::EQUAL, reference)
+}
+
+/// Only draws where the stencil differs from `reference`, without modifying it.
+pub fn test_not_equal(reference: u32) -> StencilOpState {
+    test(CompareOp::NOT_EQUAL, reference)
+}
+
+fn test(compare_op: CompareOp, reference: u32) -> StencilOpState {
+    StencilOpState {
+        fail_op: StencilOp::KEEP,
+        pass_op: StencilOp::KEEP,
+        depth_fail_op: StencilOp::KEEP,
+        compare_op,
+        compare_mask: 0xff,
+        write_mask: 0,
+        reference,
+    }
+}
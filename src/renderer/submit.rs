@@ -0,0 +1,121 @@
+use ash::{
+    prelude::VkResult,
+    vk::{
+        CommandBuffer, Fence, PipelineStageFlags, Queue, Semaphore, SubmitInfo,
+        TimelineSemaphoreSubmitInfo,
+    },
+};
+
+/// Command buffers with the semaphores they wait on and signal, one `SubmitInfo` of a batch.
+#[derive(Clone, Debug, Default)]
+pub struct Submission {
+    pub command_buffers: Vec<CommandBuffer>,
+    pub wait_semaphores: Vec<Semaphore>,
+    /// One per wait semaphore.
+    pub wait_stages: Vec<PipelineStageFlags>,
+    pub signal_semaphores: Vec<Semaphore>,
+    /// Values of timeline semaphores, empty if there are none. Entries for binary semaphores are
+    /// ignored.
+    pub wait_values: Vec<u64>,
+    pub signal_values: Vec<u64>,
+}
+
+impl Submission {
+    pub fn new(command_buffers: &[CommandBuffer]) -> Self {
+        Self {
+            command_buffers: command_buffers.to_vec(),
+            ..Self::default()
+        }
+    }
+
+    pub fn wait(mut self, semaphore: Semaphore, stage: PipelineStageFlags) -> Self {
+        self.wait_semaphores.push(semaphore);
+        self.wait_stages.push(stage);
+        self
+    }
+
+    pub fn signal(mut self, semaphore: Semaphore) -> Self {
+        self.signal_semaphores.push(semaphore);
+        self
+    }
+
+    fn uses_timeline(&self) -> bool {
+        !self.wait_values.is_empty() || !self.signal_values.is_empty()
+    }
+}
+
+/// Submissions to one queue, issued with a single `queue_submit`.
+#[derive(Clone, Debug, Default)]
+pub struct SubmitBatch {
+    pub submissions: Vec<Submission>,
+}
+
+impl SubmitBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appended to the previous submission when no semaphore separates them, they execute in
+    /// order either way.
+    pub fn submission(mut self, submission: Submission) -> Self {
+        match self.submissions.last_mut() {
+            Some(last)
+                if last.signal_semaphores.is_empty()
+                    && submission.wait_semaphores.is_empty()
+                    && !last.uses_timeline()
+                    && !submission.uses_timeline() =>
+            {
+                last.command_buffers.extend(submission.command_buffers);
+                last.signal_semaphores = submission.signal_semaphores;
+            }
+            _ => self.submissions.push(submission),
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.submissions.is_empty()
+    }
+
+    /// `fence` is signaled once every submission has completed.
+    pub unsafe fn submit(&self, device: &ash::Device, queue: Queue, fence: Fence) -> VkResult<()> {
+        // Binary semaphores next to timeline ones need a value too, it's ignored.
+        let values: Vec<_> = self
+            .submissions
+            .iter()
+            .map(|submission| {
+                let mut wait_values = submission.wait_values.clone();
+                wait_values.resize(submission.wait_semaphores.len(), 0);
+                let mut signal_values = submission.signal_values.clone();
+                signal_values.resize(submission.signal_semaphores.len(), 0);
+                (wait_values, signal_values)
+            })
+            .collect();
+        let mut timeline_submit_infos: Vec<_> = values
+            .iter()
+            .map(|(wait_values, signal_values)| {
+                TimelineSemaphoreSubmitInfo::builder()
+                    .wait_semaphore_values(wait_values)
+                    .signal_semaphore_values(signal_values)
+                    .build()
+            })
+            .collect();
+        let submit_infos: Vec<_> = self
+            .submissions
+            .iter()
+            .zip(&mut timeline_submit_infos)
+            .map(|(submission, timeline_submit_info)| {
+                let mut submit_info = SubmitInfo::builder()
+                    .wait_semaphores(&submission.wait_semaphores)
+                    .wait_dst_stage_mask(&submission.wait_stages)
+                    .command_buffers(&submission.command_buffers)
+                    .signal_semaphores(&submission.signal_semaphores);
+                if submission.uses_timeline() {
+                    submit_info = submit_info.push_next(timeline_submit_info);
+                }
+                submit_info.build()
+            })
+            .collect();
+        device.queue_submit(queue, &submit_infos, fence)
+    }
+}
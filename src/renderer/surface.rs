@@ -1,7 +1,7 @@
-use ash::{vk::SurfaceKHR, Entry};
+use ash::{prelude::VkResult, vk::SurfaceKHR, Entry};
 use winit::window::Window;
 
-use super::instance::Instance;
+use super::{instance::Instance, physical_device::PhysicalDevice};
 
 pub struct Surface {
     pub inner: SurfaceKHR,
@@ -9,12 +9,25 @@ pub struct Surface {
 }
 
 impl Surface {
-    pub fn new(entry: &Entry, instance: &Instance, window: &Window) -> Self {
-        let inner =
-            unsafe { ash_window::create_surface(entry, &instance.inner, window, None).unwrap() };
+    pub fn new(entry: &Entry, instance: &Instance, window: &Window) -> VkResult<Self> {
+        let inner = unsafe { ash_window::create_surface(entry, &instance.inner, window, None)? };
         let loader = ash::extensions::khr::Surface::new(entry, &instance.inner);
 
-        Surface { inner, loader }
+        Ok(Surface { inner, loader })
+    }
+
+    /// Whether the present family picked for `physical_device` can present to this surface.
+    pub fn is_supported(&self, physical_device: &PhysicalDevice) -> VkResult<bool> {
+        match physical_device.queue_family_indices.present_family {
+            Some(present_family) => unsafe {
+                self.loader.get_physical_device_surface_support(
+                    physical_device.inner,
+                    present_family,
+                    self.inner,
+                )
+            },
+            None => Ok(false),
+        }
     }
 }
 
@@ -1,12 +1,32 @@
-use ash::vk::{
-    ComponentMapping, ComponentSwizzle, Extent2D, Framebuffer, FramebufferCreateInfo,
-    ImageAspectFlags, ImageSubresourceRange, ImageView, ImageViewCreateInfo, ImageViewType,
-    PresentModeKHR, SurfaceFormatKHR, SwapchainCreateInfoKHR,
+use std::{
+    cell::Cell,
+    f32::consts::{FRAC_PI_2, PI},
 };
-use winit::window::Window;
+
+use ash::{
+    prelude::VkResult,
+    vk::{
+        AccessFlags, AttachmentLoadOp, CommandBuffer, CommandBufferBeginInfo, CommandBufferLevel,
+        CommandBufferUsageFlags, ComponentMapping, ComponentSwizzle, DependencyFlags, Extent2D,
+        Fence, Framebuffer, FramebufferCreateInfo, ImageAspectFlags, ImageLayout,
+        ImageMemoryBarrier, ImageSubresourceRange, ImageUsageFlags, ImageView, ImageViewCreateInfo,
+        ImageViewType, MemoryPropertyFlags, PipelineStageFlags, PresentModeKHR, SampleCountFlags,
+        Semaphore, SemaphoreCreateInfo, SubmitInfo, SurfaceFormatKHR, SurfaceTransformFlagsKHR,
+        SwapchainCreateInfoKHR,
+    },
+};
+use glam::Mat4;
 
 use super::{
-    device::Device, instance::Instance, pipeline_graphics::GraphicsPipeline, surface::Surface,
+    command_pool::CommandPool,
+    config::RendererConfig,
+    constants::{GBUFFER_ALBEDO_FORMAT, GBUFFER_NORMAL_FORMAT, HDR_SURFACE_FORMATS},
+    device::Device,
+    image::{self, Image},
+    instance::Instance,
+    physical_device::SwapChainSupportDetails,
+    render_pass::RenderPass,
+    surface::Surface,
 };
 
 pub struct SwapChain {
@@ -17,42 +37,71 @@ pub struct SwapChain {
     pub surface_format: SurfaceFormatKHR,
     pub extent: Extent2D,
     pub present_mode: PresentModeKHR,
+    /// Rotation the presentation engine applies, `IDENTITY` on most desktops.
+    pub pre_transform: SurfaceTransformFlagsKHR,
+    /// The images were created with `TRANSFER_SRC` usage, which `Renderer::capture_frame` copies
+    /// from. Only `COLOR_ATTACHMENT` usage is guaranteed to be supported.
+    pub transfer_src: bool,
     pub framebuffers: Vec<Framebuffer>,
+    /// Multisampled render target resolved into the swapchain images, present when MSAA is enabled.
+    pub color_image: Option<Image>,
+    /// Depth-stencil attachment shared by all framebuffers, created by `create_framebuffers`.
+    pub depth_image: Option<Image>,
+    /// Albedo and normal attachments shared by all framebuffers of a deferred main pass, empty
+    /// otherwise.
+    pub gbuffer: Vec<Image>,
+    /// Present when the graphics and present queue families differ.
+    pub present_transfer: Option<PresentTransfer>,
+    /// Images that haven't been given the pass's initial layout yet, see `record_initial_layout`.
+    uninitialized: Vec<Cell<bool>>,
     device: ash::Device,
 }
 
 impl SwapChain {
-    pub fn new(instance: &Instance, window: &Window, surface: &Surface, device: &Device) -> Self {
+    /// `window_size` is the window's inner size in physical pixels. `preferred_present_mode` is
+    /// used if the surface supports it, the config's surface formats, image count and composite
+    /// alpha as far as the surface supports them. Fails with `ERROR_SURFACE_LOST_KHR` if the surface
+    /// went away.
+    pub fn new(
+        instance: &Instance,
+        window_size: Extent2D,
+        surface: &Surface,
+        device: &Device,
+        preferred_present_mode: Option<PresentModeKHR>,
+        config: &RendererConfig,
+    ) -> VkResult<Self> {
         let physical_device = &device.physical_device;
-        let surface_format = physical_device.swap_chain_support_details.choose_format();
-        let present_mode = physical_device
-            .swap_chain_support_details
-            .choose_present_mode();
-        let extent = physical_device
-            .swap_chain_support_details
-            .choose_swap_extent(window);
-
-        let mut image_count = physical_device
-            .swap_chain_support_details
+        // Queried again instead of using the cached details, the surface extent changes on resize.
+        let swap_chain_support_details =
+            SwapChainSupportDetails::extract(surface, physical_device.inner)?;
+        let surface_format = if config.hdr {
+            let formats: Vec<SurfaceFormatKHR> = HDR_SURFACE_FORMATS
+                .iter()
+                .chain(&config.surface_formats)
+                .copied()
+                .collect();
+            swap_chain_support_details.choose_format(&formats)
+        } else {
+            swap_chain_support_details.choose_format(&config.surface_formats)
+        };
+        let present_mode = swap_chain_support_details.choose_present_mode(preferred_present_mode);
+        let extent = swap_chain_support_details.choose_swap_extent(window_size);
+
+        let image_count = swap_chain_support_details.choose_image_count(config.desired_image_count);
+        let composite_alpha =
+            swap_chain_support_details.choose_composite_alpha(config.composite_alpha);
+        let pre_transform = swap_chain_support_details
             .surface_capabilities
-            .min_image_count
-            + 1;
-        if physical_device
-            .swap_chain_support_details
+            .current_transform;
+        let transfer_src = swap_chain_support_details
             .surface_capabilities
-            .max_image_count
-            > 0
-            && image_count
-                > physical_device
-                    .swap_chain_support_details
-                    .surface_capabilities
-                    .max_image_count
-        {
-            image_count = physical_device
-                .swap_chain_support_details
-                .surface_capabilities
-                .max_image_count;
-        }
+            .supported_usage_flags
+            .contains(ImageUsageFlags::TRANSFER_SRC);
+        let image_usage = if transfer_src {
+            ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::TRANSFER_SRC
+        } else {
+            ImageUsageFlags::COLOR_ATTACHMENT
+        };
 
         let mut create_info = SwapchainCreateInfoKHR::builder()
             .surface(surface.inner)
@@ -61,41 +110,37 @@ impl SwapChain {
             .image_color_space(surface_format.color_space)
             .image_extent(extent)
             .image_array_layers(1)
-            .image_usage(ash::vk::ImageUsageFlags::COLOR_ATTACHMENT)
-            .pre_transform(
-                physical_device
-                    .swap_chain_support_details
-                    .surface_capabilities
-                    .current_transform,
-            )
-            .composite_alpha(ash::vk::CompositeAlphaFlagsKHR::OPAQUE)
+            .image_usage(image_usage)
+            .pre_transform(pre_transform)
+            .composite_alpha(composite_alpha)
             .present_mode(present_mode)
             .clipped(true);
 
-        let queue_family_indices;
-        if physical_device
+        let graphics_family = physical_device
             .queue_family_indices
             .graphics_family
-            .unwrap()
-            != physical_device.queue_family_indices.present_family.unwrap()
-        {
-            queue_family_indices = [
-                physical_device
-                    .queue_family_indices
-                    .graphics_family
-                    .unwrap(),
-                physical_device.queue_family_indices.present_family.unwrap(),
-            ];
-            create_info = create_info
+            .unwrap();
+        let present_family = physical_device.queue_family_indices.present_family.unwrap();
+        // Images are handed to a separate present queue family with ownership transfers instead
+        // of `CONCURRENT` sharing, which can be slower. Loaded contents would have to be
+        // transferred back every frame, so those use `CONCURRENT` sharing.
+        let concurrent =
+            graphics_family != present_family && config.color_load_op == AttachmentLoadOp::LOAD;
+        let queue_family_indices = [graphics_family, present_family];
+        create_info = if concurrent {
+            create_info
                 .image_sharing_mode(ash::vk::SharingMode::CONCURRENT)
-                .queue_family_indices(&queue_family_indices);
+                .queue_family_indices(&queue_family_indices)
         } else {
-            create_info = create_info.image_sharing_mode(ash::vk::SharingMode::EXCLUSIVE);
-        }
+            create_info.image_sharing_mode(ash::vk::SharingMode::EXCLUSIVE)
+        };
 
         let loader = ash::extensions::khr::Swapchain::new(&instance.inner, &device.inner);
-        let inner = unsafe { loader.create_swapchain(&create_info, None).unwrap() };
+        let inner = unsafe { loader.create_swapchain(&create_info, None)? };
         let images = unsafe { loader.get_swapchain_images(inner).unwrap() };
+        let present_transfer = (graphics_family != present_family && !concurrent)
+            .then(|| PresentTransfer::new(device, &images, graphics_family, present_family));
+        let uninitialized = images.iter().map(|_| Cell::new(true)).collect();
         let mut image_views = Vec::new();
 
         for image in &images {
@@ -129,25 +174,214 @@ impl SwapChain {
             image_views.push(image_view);
         }
 
-        Self {
+        Ok(Self {
             inner,
             loader,
             images,
             image_views,
             surface_format,
             present_mode,
+            pre_transform,
+            transfer_src,
             framebuffers: Vec::new(),
+            color_image: None,
+            depth_image: None,
+            gbuffer: Vec::new(),
+            present_transfer,
+            uninitialized,
             extent,
             device: device.inner.clone(),
+        })
+    }
+
+    /// Rotates clip space so the image is upright after the presentation engine applied
+    /// `pre_transform`, instead of having the compositor rotate it. Mirrored transforms aren't
+    /// handled and use the identity.
+    pub fn pre_transform_matrix(&self) -> Mat4 {
+        let angle = match self.pre_transform {
+            SurfaceTransformFlagsKHR::ROTATE_90 => FRAC_PI_2,
+            SurfaceTransformFlagsKHR::ROTATE_180 => PI,
+            SurfaceTransformFlagsKHR::ROTATE_270 => 3.0 * FRAC_PI_2,
+            _ => 0.0,
+        };
+        Mat4::from_rotation_z(angle)
+    }
+
+    /// Transitions the image from `UNDEFINED` to `layout` the first time it's rendered to, for
+    /// passes that load it. Does nothing for `UNDEFINED` or an image that was already rendered to.
+    pub fn record_initial_layout(
+        &self,
+        device: &ash::Device,
+        command_buffer: CommandBuffer,
+        index: usize,
+        layout: ImageLayout,
+    ) {
+        if layout != ImageLayout::UNDEFINED && self.uninitialized[index].replace(false) {
+            image::transition_image_layout(
+                device,
+                command_buffer,
+                self.images[index],
+                1,
+                ImageLayout::UNDEFINED,
+                layout,
+            );
         }
     }
 
-    pub fn create_framebuffers(&mut self, device: &Device, graphics_pipeline: &GraphicsPipeline) {
+    /// Copies the image, rendered to and in `PRESENT_SRC_KHR` layout, into `buffer` as tightly
+    /// packed pixels. Recorded before `record_release`, requires `transfer_src`.
+    pub fn record_capture(
+        &self,
+        device: &ash::Device,
+        command_buffer: CommandBuffer,
+        index: usize,
+        buffer: ash::vk::Buffer,
+    ) {
+        let image = self.images[index];
+        image::transition_image_layout(
+            device,
+            command_buffer,
+            image,
+            1,
+            ImageLayout::PRESENT_SRC_KHR,
+            ImageLayout::TRANSFER_SRC_OPTIMAL,
+        );
+        image::copy_image_to_buffer(device, command_buffer, image, buffer, self.extent);
+        image::transition_image_layout(
+            device,
+            command_buffer,
+            image,
+            1,
+            ImageLayout::TRANSFER_SRC_OPTIMAL,
+            ImageLayout::PRESENT_SRC_KHR,
+        );
+    }
+
+    /// Records the release of the image to the present queue family at the end of the frame's
+    /// command buffer, after it was transitioned to `PRESENT_SRC_KHR`. Does nothing if the
+    /// families are the same.
+    pub fn record_release(
+        &self,
+        device: &ash::Device,
+        command_buffer: CommandBuffer,
+        index: usize,
+    ) {
+        if let Some(present_transfer) = &self.present_transfer {
+            let barrier = ownership_barrier(
+                self.images[index],
+                present_transfer.graphics_family,
+                present_transfer.present_family,
+                AccessFlags::COLOR_ATTACHMENT_WRITE,
+            );
+            unsafe {
+                device.cmd_pipeline_barrier(
+                    command_buffer,
+                    // `TRANSFER` for the copy of `record_capture`.
+                    PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | PipelineStageFlags::TRANSFER,
+                    PipelineStageFlags::BOTTOM_OF_PIPE,
+                    DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[barrier],
+                );
+            }
+        }
+    }
+
+    /// Acquires the image on the present queue after `render_finished` was signaled and returns
+    /// the semaphore the present has to wait on. Without a transfer that's `render_finished`.
+    pub fn acquire_for_present(
+        &self,
+        device: &Device,
+        index: usize,
+        render_finished: Semaphore,
+    ) -> VkResult<Semaphore> {
+        let present_transfer = match &self.present_transfer {
+            Some(present_transfer) => present_transfer,
+            None => return Ok(render_finished),
+        };
+        let wait_semaphores = [render_finished];
+        let wait_stages = [PipelineStageFlags::ALL_COMMANDS];
+        let command_buffers = [present_transfer.command_buffers[index]];
+        let signal_semaphores = [present_transfer.semaphore];
+        let submit_infos = [SubmitInfo::builder()
+            .wait_semaphores(&wait_semaphores)
+            .wait_dst_stage_mask(&wait_stages)
+            .command_buffers(&command_buffers)
+            .signal_semaphores(&signal_semaphores)
+            .build()];
+        unsafe {
+            device
+                .inner
+                .queue_submit(device.present_queue, &submit_infos, Fence::null())?;
+        }
+        Ok(present_transfer.semaphore)
+    }
+
+    pub fn create_framebuffers(&mut self, device: &Device, render_pass: &RenderPass) {
         self.framebuffers.clear();
+        self.depth_image = Some(Image::new(
+            device,
+            self.extent,
+            1,
+            render_pass.samples,
+            render_pass.depth_format,
+            ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+        ));
+        // Dynamic rendering draws straight into the image views.
+        if render_pass.uses_dynamic_rendering() {
+            return;
+        }
+        self.color_image = if render_pass.samples != SampleCountFlags::TYPE_1 {
+            Some(Image::new(
+                device,
+                self.extent,
+                1,
+                render_pass.samples,
+                self.surface_format.format,
+                ImageUsageFlags::TRANSIENT_ATTACHMENT | ImageUsageFlags::COLOR_ATTACHMENT,
+                MemoryPropertyFlags::DEVICE_LOCAL,
+            ))
+        } else {
+            None
+        };
+
+        // Only read within the render pass, so they can live in tile memory.
+        self.gbuffer = if render_pass.gbuffer {
+            [GBUFFER_ALBEDO_FORMAT, GBUFFER_NORMAL_FORMAT]
+                .into_iter()
+                .map(|format| {
+                    Image::new(
+                        device,
+                        self.extent,
+                        1,
+                        SampleCountFlags::TYPE_1,
+                        format,
+                        ImageUsageFlags::COLOR_ATTACHMENT
+                            | ImageUsageFlags::INPUT_ATTACHMENT
+                            | ImageUsageFlags::TRANSIENT_ATTACHMENT,
+                        MemoryPropertyFlags::DEVICE_LOCAL,
+                    )
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
         for i in 0..self.image_views.len() {
-            let attachments = [self.image_views[i]];
+            let mut attachments = match &self.color_image {
+                Some(color_image) => vec![color_image.view, self.image_views[i]],
+                None => vec![self.image_views[i]],
+            };
+            attachments.extend(self.gbuffer.iter().map(|image| image.view));
+            attachments.extend(
+                self.depth_image
+                    .as_ref()
+                    .map(|depth_image| depth_image.view),
+            );
             let create_info = FramebufferCreateInfo::builder()
-                .render_pass(graphics_pipeline.render_pass)
+                .render_pass(render_pass.inner)
                 .attachments(&attachments)
                 .width(self.extent.width)
                 .height(self.extent.height)
@@ -173,3 +407,110 @@ impl Drop for SwapChain {
         }
     }
 }
+
+/// Queue family ownership transfer of the swapchain images from the graphics to the present
+/// queue family. Images don't need to be transferred back, their contents are discarded when the
+/// next frame starts rendering from `UNDEFINED`.
+pub struct PresentTransfer {
+    pub graphics_family: u32,
+    pub present_family: u32,
+    /// Acquire barrier for each swapchain image, recorded once and submitted to the present queue.
+    pub command_buffers: Vec<CommandBuffer>,
+    /// Signaled by the acquire submission and waited on by the present.
+    pub semaphore: Semaphore,
+    _command_pool: CommandPool,
+    device: ash::Device,
+}
+
+impl PresentTransfer {
+    fn new(
+        device: &Device,
+        images: &[ash::vk::Image],
+        graphics_family: u32,
+        present_family: u32,
+    ) -> Self {
+        let mut command_pool = CommandPool::new_present(device);
+        // The previous frame's submission of the same image may still be pending.
+        let begin_info =
+            CommandBufferBeginInfo::builder().flags(CommandBufferUsageFlags::SIMULTANEOUS_USE);
+        let command_buffers =
+            command_pool.allocate_many(images.len() as u32, CommandBufferLevel::PRIMARY);
+        let command_buffers = images
+            .iter()
+            .zip(command_buffers)
+            .map(|(image, command_buffer)| {
+                let barrier = ownership_barrier(
+                    *image,
+                    graphics_family,
+                    present_family,
+                    AccessFlags::empty(),
+                );
+                unsafe {
+                    device
+                        .inner
+                        .begin_command_buffer(command_buffer, &begin_info)
+                        .unwrap();
+                    device.inner.cmd_pipeline_barrier(
+                        command_buffer,
+                        PipelineStageFlags::TOP_OF_PIPE,
+                        PipelineStageFlags::BOTTOM_OF_PIPE,
+                        DependencyFlags::empty(),
+                        &[],
+                        &[],
+                        &[barrier],
+                    );
+                    device.inner.end_command_buffer(command_buffer).unwrap();
+                }
+                command_buffer
+            })
+            .collect();
+        let semaphore = unsafe {
+            device
+                .inner
+                .create_semaphore(&SemaphoreCreateInfo::builder(), None)
+                .unwrap()
+        };
+
+        Self {
+            graphics_family,
+            present_family,
+            command_buffers,
+            semaphore,
+            _command_pool: command_pool,
+            device: device.inner.clone(),
+        }
+    }
+}
+
+/// Release and acquire use the same barrier, only the access mask of the side recording it
+/// matters.
+fn ownership_barrier(
+    image: ash::vk::Image,
+    graphics_family: u32,
+    present_family: u32,
+    src_access_mask: AccessFlags,
+) -> ImageMemoryBarrier {
+    ImageMemoryBarrier::builder()
+        .old_layout(ImageLayout::PRESENT_SRC_KHR)
+        .new_layout(ImageLayout::PRESENT_SRC_KHR)
+        .src_queue_family_index(graphics_family)
+        .dst_queue_family_index(present_family)
+        .image(image)
+        .subresource_range(
+            ImageSubresourceRange::builder()
+                .aspect_mask(ImageAspectFlags::COLOR)
+                .level_count(1)
+                .layer_count(1)
+                .build(),
+        )
+        .src_access_mask(src_access_mask)
+        .build()
+}
+
+impl Drop for PresentTransfer {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_semaphore(self.semaphore, None);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::path::Path;
+
+use ash::vk::{
+    BufferUsageFlags, DeviceSize, Extent2D, Format, FormatFeatureFlags, ImageLayout,
+    ImageUsageFlags, MemoryPropertyFlags, SampleCountFlags,
+};
+use log::warn;
+
+use super::{
+    buffer::Buffer,
+    device::Device,
+    image::{self, Image},
+};
+
+/// A sampled `R8G8B8A8_SRGB` image with a full mip chain in `SHADER_READ_ONLY_OPTIMAL` layout.
+pub struct Texture {
+    pub image: Image,
+}
+
+impl Texture {
+    pub const FORMAT: Format = Format::R8G8B8A8_SRGB;
+
+    pub fn load(
+        instance: &ash::Instance,
+        device: &Device,
+        path: &Path,
+    ) -> ::image::ImageResult<Self> {
+        let rgba = ::image::open(path)?.into_rgba8();
+        Ok(Texture::from_rgba8(
+            instance,
+            device,
+            rgba.width(),
+            rgba.height(),
+            rgba.as_raw(),
+        ))
+    }
+
+    pub fn from_rgba8(
+        instance: &ash::Instance,
+        device: &Device,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Self {
+        assert_eq!(pixels.len(), (width * height * 4) as usize);
+
+        let mut staging = Buffer::new(
+            device,
+            pixels.len() as DeviceSize,
+            BufferUsageFlags::TRANSFER_SRC,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        );
+        staging.write(pixels);
+
+        let format = Self::FORMAT;
+        let mut mip_levels = width.max(height).ilog2() + 1;
+        let format_properties = unsafe {
+            instance.get_physical_device_format_properties(device.physical_device.inner, format)
+        };
+        if mip_levels > 1
+            && !format_properties
+                .optimal_tiling_features
+                .contains(FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR)
+        {
+            warn!(
+                "{:?} does not support linear blitting, skipping mipmap generation",
+                format
+            );
+            mip_levels = 1;
+        }
+
+        let extent = Extent2D { width, height };
+        let image = Image::new(
+            device,
+            extent,
+            mip_levels,
+            SampleCountFlags::TYPE_1,
+            format,
+            ImageUsageFlags::TRANSFER_SRC
+                | ImageUsageFlags::TRANSFER_DST
+                | ImageUsageFlags::SAMPLED,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+
+        device.one_time_submit(|command_buffer| {
+            image::transition_image_layout(
+                &device.inner,
+                command_buffer,
+                image.inner,
+                mip_levels,
+                ImageLayout::UNDEFINED,
+                ImageLayout::TRANSFER_DST_OPTIMAL,
+            );
+            image::copy_buffer_to_image(
+                &device.inner,
+                command_buffer,
+                staging.inner,
+                image.inner,
+                extent,
+            );
+            image::generate_mipmaps(
+                &device.inner,
+                command_buffer,
+                image.inner,
+                extent,
+                mip_levels,
+            );
+        });
+
+        Self { image }
+    }
+}
@@ -0,0 +1,53 @@
+use ash::{
+    prelude::VkResult,
+    vk::{SemaphoreCreateInfo, SemaphoreType, SemaphoreTypeCreateInfo, SemaphoreWaitInfo},
+};
+
+use super::device::Device;
+
+/// Semaphore with a monotonically increasing counter, signalled by the GPU and waited on by the CPU.
+pub struct TimelineSemaphore {
+    pub inner: ash::vk::Semaphore,
+    /// Last value a submission was told to signal.
+    pub value: u64,
+    device: ash::Device,
+}
+
+impl TimelineSemaphore {
+    pub fn new(device: &Device) -> VkResult<Self> {
+        let mut type_create_info = SemaphoreTypeCreateInfo::builder()
+            .semaphore_type(SemaphoreType::TIMELINE)
+            .initial_value(0);
+        let create_info = SemaphoreCreateInfo::builder().push_next(&mut type_create_info);
+        let inner = unsafe { device.inner.create_semaphore(&create_info, None)? };
+
+        Ok(Self {
+            inner,
+            value: 0,
+            device: device.inner.clone(),
+        })
+    }
+
+    /// Value the next submission signals. Assign it to `value` once the submission succeeded.
+    pub fn next_value(&self) -> u64 {
+        self.value + 1
+    }
+
+    /// Waits until `value` has been signalled.
+    pub fn wait(&self, timeout: u64) -> VkResult<()> {
+        let semaphores = [self.inner];
+        let values = [self.value];
+        let wait_info = SemaphoreWaitInfo::builder()
+            .semaphores(&semaphores)
+            .values(&values);
+        unsafe { self.device.wait_semaphores(&wait_info, timeout) }
+    }
+}
+
+impl Drop for TimelineSemaphore {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_semaphore(self.inner, None);
+        }
+    }
+}
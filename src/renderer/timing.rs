@@ -0,0 +1,240 @@
+use std::time::{Duration, Instant};
+
+use ash::vk::{CommandBuffer, PipelineStageFlags, QueryType};
+
+use super::{device::Device, query::QueryPool};
+
+/// Number of frames `FrameTimer` averages over.
+const FRAME_TIME_SAMPLES: usize = 60;
+
+/// Delta time reported before two frames have been drawn, instead of a zero or a startup spike.
+const FIRST_FRAME_DELTA: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Time before a frame deadline that `FrameLimiter` stops sleeping and spins instead, since sleeps
+/// can overshoot by about a scheduler tick.
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+/// Rolling average of the CPU time between successive frames.
+pub struct FrameTimer {
+    last_frame: Option<Instant>,
+    delta_time: Duration,
+    samples: [Duration; FRAME_TIME_SAMPLES],
+    next_sample: usize,
+    sample_count: usize,
+}
+
+impl FrameTimer {
+    pub fn new() -> Self {
+        Self {
+            last_frame: None,
+            delta_time: FIRST_FRAME_DELTA,
+            samples: [Duration::ZERO; FRAME_TIME_SAMPLES],
+            next_sample: 0,
+            sample_count: 0,
+        }
+    }
+
+    /// Marks the start of a new frame and records the time since the previous one.
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            self.delta_time = now - last_frame;
+            self.samples[self.next_sample] = self.delta_time;
+            self.next_sample = (self.next_sample + 1) % FRAME_TIME_SAMPLES;
+            self.sample_count = (self.sample_count + 1).min(FRAME_TIME_SAMPLES);
+        }
+        self.last_frame = Some(now);
+    }
+
+    /// Time between the two most recent frames.
+    pub fn delta_time(&self) -> Duration {
+        self.delta_time
+    }
+
+    /// Average frame time over the last `FRAME_TIME_SAMPLES` frames.
+    pub fn frame_time(&self) -> Duration {
+        if self.sample_count == 0 {
+            return Duration::ZERO;
+        }
+        self.samples[..self.sample_count].iter().sum::<Duration>() / self.sample_count as u32
+    }
+
+    pub fn fps(&self) -> f32 {
+        let frame_time = self.frame_time().as_secs_f32();
+        if frame_time > 0.0 {
+            1.0 / frame_time
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Holds frames to a minimum duration, independent of the present mode.
+pub struct FrameLimiter {
+    frame_time: Duration,
+    next_frame: Option<Instant>,
+}
+
+impl FrameLimiter {
+    pub fn new(max_fps: u32) -> Self {
+        Self {
+            frame_time: Duration::from_secs(1) / max_fps.max(1),
+            next_frame: None,
+        }
+    }
+
+    /// Blocks until a full frame time has passed since the last call. Sleeps for most of it and
+    /// spins for the rest to hit the deadline accurately.
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        let deadline = match self.next_frame {
+            // Don't try to catch up after a long frame, e.g. while the window was being resized.
+            Some(deadline) if deadline > now => deadline,
+            _ => {
+                self.next_frame = Some(now + self.frame_time);
+                return;
+            }
+        };
+        let remaining = deadline - now;
+        if remaining > SPIN_THRESHOLD {
+            std::thread::sleep(remaining - SPIN_THRESHOLD);
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+        self.next_frame = Some(deadline + self.frame_time);
+    }
+}
+
+/// Measures the GPU time between `begin` and `end` of a command buffer with timestamp queries.
+///
+/// Results are collected after the frame's fence has been waited on, so reading them never stalls
+/// and the reported time always lags one frame behind.
+pub struct GpuTimer {
+    pool: QueryPool,
+    timestamp_period: f32,
+    valid_mask: u64,
+    pending: bool,
+    last_frame_time: Duration,
+    collected_frames: u64,
+    device: ash::Device,
+}
+
+impl GpuTimer {
+    /// Returns `None` if the graphics queue doesn't support timestamps.
+    pub fn new(device: &Device) -> Option<Self> {
+        let physical_device = &device.physical_device;
+        let graphics_family = physical_device
+            .queue_family_indices
+            .graphics_family
+            .unwrap();
+        let valid_bits =
+            physical_device.queue_family_properties[graphics_family as usize].timestamp_valid_bits;
+        if valid_bits == 0 {
+            return None;
+        }
+
+        Some(Self {
+            pool: QueryPool::new(device, QueryType::TIMESTAMP, 2),
+            timestamp_period: physical_device.properties.limits.timestamp_period,
+            valid_mask: if valid_bits >= 64 {
+                u64::MAX
+            } else {
+                (1 << valid_bits) - 1
+            },
+            pending: false,
+            last_frame_time: Duration::ZERO,
+            collected_frames: 0,
+            device: device.inner.clone(),
+        })
+    }
+
+    /// Has to be recorded outside of a render pass.
+    pub fn begin(&mut self, command_buffer: CommandBuffer) {
+        self.pool.reset(command_buffer);
+        unsafe {
+            self.device.cmd_write_timestamp(
+                command_buffer,
+                PipelineStageFlags::TOP_OF_PIPE,
+                self.pool.inner,
+                0,
+            );
+        }
+    }
+
+    pub fn end(&mut self, command_buffer: CommandBuffer) {
+        unsafe {
+            self.device.cmd_write_timestamp(
+                command_buffer,
+                PipelineStageFlags::BOTTOM_OF_PIPE,
+                self.pool.inner,
+                1,
+            );
+        }
+        self.pending = true;
+    }
+
+    /// Picks up the timestamps of the last submitted frame if they are available.
+    pub fn collect(&mut self) {
+        if !self.pending {
+            return;
+        }
+        if let Some(timestamps) = self.pool.results() {
+            let ticks = (timestamps[1] & self.valid_mask)
+                .wrapping_sub(timestamps[0] & self.valid_mask)
+                & self.valid_mask;
+            self.last_frame_time =
+                Duration::from_nanos((ticks as f64 * self.timestamp_period as f64) as u64);
+            self.pending = false;
+            self.collected_frames += 1;
+        }
+    }
+
+    pub fn last_frame_time(&self) -> Duration {
+        self.last_frame_time
+    }
+
+    /// Number of frames whose time has been collected, to tell when `last_frame_time` changed.
+    pub fn collected_frames(&self) -> u64 {
+        self.collected_frames
+    }
+}
+
+/// Summary of a set of frame times.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FrameTimeStats {
+    pub min: Duration,
+    pub max: Duration,
+    pub avg: Duration,
+    /// 99th percentile, the time 99% of frames stayed within.
+    pub p99: Duration,
+}
+
+impl FrameTimeStats {
+    /// `None` if there are no samples.
+    pub fn from_samples(samples: &mut [Duration]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+        let p99_index = (samples.len() * 99).div_ceil(100) - 1;
+        Some(Self {
+            min: samples[0],
+            max: samples[samples.len() - 1],
+            avg: samples.iter().sum::<Duration>() / samples.len() as u32,
+            p99: samples[p99_index],
+        })
+    }
+}
+
+/// Result of `Renderer::benchmark`. Serializable with the `serde` feature.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BenchReport {
+    pub frame_count: u32,
+    /// CPU time of each `draw_frame`, including waiting for the previous frame.
+    pub cpu: FrameTimeStats,
+    /// `None` if timestamps are unsupported.
+    pub gpu: Option<FrameTimeStats>,
+}
@@ -0,0 +1,589 @@
+use std::{ffi::CString, mem::size_of};
+
+use ash::vk::{
+    AttachmentDescription, AttachmentLoadOp, AttachmentStoreOp, BlendFactor, BlendOp,
+    BufferUsageFlags, ColorComponentFlags, CommandBuffer, CullModeFlags, DescriptorImageInfo,
+    DescriptorPoolSize, DescriptorSet, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo,
+    DescriptorType, DeviceSize, DynamicState, Extent2D, Format, Framebuffer,
+    GraphicsPipelineCreateInfo, ImageLayout, IndexType, MemoryPropertyFlags, Offset2D,
+    PipelineBindPoint, PipelineCache, PipelineColorBlendAttachmentState,
+    PipelineColorBlendStateCreateInfo, PipelineDepthStencilStateCreateInfo,
+    PipelineDynamicStateCreateInfo, PipelineInputAssemblyStateCreateInfo, PipelineLayoutCreateInfo,
+    PipelineMultisampleStateCreateInfo, PipelineRasterizationStateCreateInfo,
+    PipelineShaderStageCreateInfo, PipelineVertexInputStateCreateInfo,
+    PipelineViewportStateCreateInfo, PolygonMode, PrimitiveTopology, PushConstantRange, Rect2D,
+    RenderPassBeginInfo, SampleCountFlags, ShaderStageFlags, SubpassContents,
+    VertexInputAttributeDescription, VertexInputBindingDescription, VertexInputRate, Viewport,
+    WriteDescriptorSet,
+};
+use egui::{epaint::Primitive, ClippedPrimitive, Color32, ImageData, TextureId, TexturesDelta};
+use log::warn;
+use winit::{event::WindowEvent, window::Window};
+
+use super::{
+    buffer::Buffer,
+    descriptor::DescriptorPool,
+    device::Device,
+    pipeline_graphics::{GraphicsPipeline, PipelineOptions},
+    render_pass::{depth_stencil_attachment, RenderPass, RenderPassBuilder, Subpass},
+    sampler::Sampler,
+    shader_module::ShaderModule,
+    texture::Texture,
+};
+
+/// Draws `egui` output on top of the finished frame.
+pub struct UiRenderer {
+    pub context: egui::Context,
+    state: egui_winit::State,
+    /// Tessellated output of the last `Renderer::run_ui`, drawn every frame until replaced.
+    primitives: Vec<ClippedPrimitive>,
+    pixels_per_point: f32,
+    /// CPU copy of the font atlas, partial updates are applied here and the whole image re-uploaded.
+    font_size: [usize; 2],
+    font_pixels: Vec<Color32>,
+    vertex_buffer: Option<Buffer>,
+    index_buffer: Option<Buffer>,
+    /// Start of each primitive's vertices and indices in the buffers, filled by `upload`.
+    offsets: Vec<(i32, u32)>,
+    descriptor_set: DescriptorSet,
+    descriptor_pool: DescriptorPool,
+    font_texture: Option<Texture>,
+    pipeline: GraphicsPipeline,
+    render_pass: RenderPass,
+}
+
+impl UiRenderer {
+    /// `format` and `layout` are those of the image the UI is drawn into, e.g. the swapchain image.
+    /// `depth_format` is that of the main pipeline, whose framebuffers the UI is drawn into.
+    pub fn new(
+        device: &Device,
+        window: &Window,
+        format: Format,
+        depth_format: Format,
+        layout: ImageLayout,
+    ) -> Self {
+        let max_texture_side = device
+            .physical_device
+            .properties
+            .limits
+            .max_image_dimension2_d;
+        Self::with_state(
+            device,
+            egui::Context::default(),
+            egui_winit::State::new(max_texture_side as usize, window),
+            format,
+            depth_format,
+            layout,
+        )
+    }
+
+    fn with_state(
+        device: &Device,
+        context: egui::Context,
+        state: egui_winit::State,
+        format: Format,
+        depth_format: Format,
+        layout: ImageLayout,
+    ) -> Self {
+        let render_pass = create_render_pass(device, format, depth_format, layout);
+        let pipeline = create_pipeline(device, &render_pass);
+        let descriptor_pool = DescriptorPool::new(
+            device,
+            &[DescriptorPoolSize::builder()
+                .ty(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .build()],
+            1,
+        );
+        let descriptor_set = descriptor_pool.allocate(pipeline.descriptor_set_layout);
+
+        Self {
+            context,
+            state,
+            primitives: Vec::new(),
+            pixels_per_point: 1.0,
+            font_size: [0, 0],
+            font_pixels: Vec::new(),
+            vertex_buffer: None,
+            index_buffer: None,
+            offsets: Vec::new(),
+            descriptor_set,
+            descriptor_pool,
+            font_texture: None,
+            pipeline,
+            render_pass,
+        }
+    }
+
+    /// Recreates the GPU resources on a new device, keeping the `egui` state and font atlas.
+    pub fn rebuild(
+        self,
+        device: &Device,
+        format: Format,
+        depth_format: Format,
+        layout: ImageLayout,
+    ) -> Self {
+        let Self {
+            context,
+            state,
+            primitives,
+            pixels_per_point,
+            font_size,
+            font_pixels,
+            ..
+        } = self;
+        Self {
+            primitives,
+            pixels_per_point,
+            font_size,
+            font_pixels,
+            ..Self::with_state(device, context, state, format, depth_format, layout)
+        }
+    }
+
+    /// Returns `true` if `egui` consumed the event.
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        self.state.on_event(&self.context, event)
+    }
+
+    /// Runs one `egui` frame and keeps its output for the following frames.
+    pub fn run(
+        &mut self,
+        instance: &ash::Instance,
+        device: &Device,
+        sampler: &Sampler,
+        window: &Window,
+        run: impl FnOnce(&egui::Context),
+    ) {
+        let raw_input = self.state.take_egui_input(window);
+        let output = self.context.run(raw_input, run);
+        self.state
+            .handle_platform_output(window, &self.context, output.platform_output);
+
+        self.update_font(&output.textures_delta);
+        if self.font_texture.is_none() && !self.font_pixels.is_empty() {
+            self.upload_font(instance, device, sampler);
+        } else if !output.textures_delta.set.is_empty() {
+            // The old font texture may still be in use by the frame in flight.
+            unsafe {
+                device.inner.device_wait_idle().unwrap();
+            }
+            self.upload_font(instance, device, sampler);
+        }
+
+        self.primitives = self.context.tessellate(output.shapes);
+        self.pixels_per_point = self.state.pixels_per_point();
+    }
+
+    fn update_font(&mut self, textures_delta: &TexturesDelta) {
+        for (id, delta) in &textures_delta.set {
+            if *id != TextureId::default() {
+                warn!("Only the egui font texture is supported, ignoring {:?}", id);
+                continue;
+            }
+            let (size, pixels): ([usize; 2], Vec<Color32>) = match &delta.image {
+                ImageData::Color(image) => (image.size, image.pixels.clone()),
+                ImageData::Font(image) => (image.size, image.srgba_pixels(1.0).collect()),
+            };
+            match delta.pos {
+                Some([x, y]) => {
+                    for row in 0..size[1] {
+                        let dst = (y + row) * self.font_size[0] + x;
+                        let src = row * size[0];
+                        self.font_pixels[dst..dst + size[0]]
+                            .copy_from_slice(&pixels[src..src + size[0]]);
+                    }
+                }
+                None => {
+                    self.font_size = size;
+                    self.font_pixels = pixels;
+                }
+            }
+        }
+    }
+
+    fn upload_font(&mut self, instance: &ash::Instance, device: &Device, sampler: &Sampler) {
+        let pixels: Vec<u8> = self
+            .font_pixels
+            .iter()
+            .flat_map(|color| color.to_array())
+            .collect();
+        let texture = Texture::from_rgba8(
+            instance,
+            device,
+            self.font_size[0] as u32,
+            self.font_size[1] as u32,
+            &pixels,
+        );
+
+        let image_infos = [DescriptorImageInfo::builder()
+            .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(texture.image.view)
+            .sampler(sampler.inner)
+            .build()];
+        let writes = [WriteDescriptorSet::builder()
+            .dst_set(self.descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&image_infos)
+            .build()];
+        unsafe {
+            device.inner.update_descriptor_sets(&writes, &[]);
+        }
+        self.font_texture = Some(texture);
+    }
+
+    /// Copies the meshes into the vertex and index buffers, growing them if needed.
+    /// Must only be called once the previous frame has finished.
+    pub fn upload(&mut self, device: &Device) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        self.offsets.clear();
+        for primitive in &self.primitives {
+            if let Primitive::Mesh(mesh) = &primitive.primitive {
+                self.offsets
+                    .push((vertices.len() as i32, indices.len() as u32));
+                vertices.extend_from_slice(&mesh.vertices);
+                indices.extend_from_slice(&mesh.indices);
+            }
+        }
+        if indices.is_empty() {
+            return;
+        }
+
+        let vertex_data = unsafe {
+            std::slice::from_raw_parts(
+                vertices.as_ptr() as *const u8,
+                vertices.len() * size_of::<egui::epaint::Vertex>(),
+            )
+        };
+        let index_data = unsafe {
+            std::slice::from_raw_parts(
+                indices.as_ptr() as *const u8,
+                indices.len() * size_of::<u32>(),
+            )
+        };
+        write_growing(
+            device,
+            &mut self.vertex_buffer,
+            BufferUsageFlags::VERTEX_BUFFER,
+            vertex_data,
+        );
+        write_growing(
+            device,
+            &mut self.index_buffer,
+            BufferUsageFlags::INDEX_BUFFER,
+            index_data,
+        );
+    }
+
+    /// Records the UI pass into `framebuffer`, drawing over its current contents.
+    pub fn record(
+        &self,
+        device: &Device,
+        command_buffer: CommandBuffer,
+        framebuffer: Framebuffer,
+        extent: Extent2D,
+    ) {
+        let (vertex_buffer, index_buffer) = match (&self.vertex_buffer, &self.index_buffer) {
+            (Some(vertex_buffer), Some(index_buffer)) if !self.offsets.is_empty() => {
+                (vertex_buffer, index_buffer)
+            }
+            _ => return,
+        };
+        if self.font_texture.is_none() {
+            return;
+        }
+
+        let render_pass_begin_info = RenderPassBeginInfo::builder()
+            .render_pass(self.render_pass.inner)
+            .framebuffer(framebuffer)
+            .render_area(Rect2D::builder().extent(extent).build());
+        let viewports = [Viewport::builder()
+            .width(extent.width as f32)
+            .height(extent.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0)
+            .build()];
+        let screen_size = [
+            extent.width as f32 / self.pixels_per_point,
+            extent.height as f32 / self.pixels_per_point,
+        ];
+        let push_data: Vec<u8> = screen_size.iter().flat_map(|x| x.to_ne_bytes()).collect();
+
+        unsafe {
+            device.inner.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                SubpassContents::INLINE,
+            );
+            device.inner.cmd_bind_pipeline(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                self.pipeline.inner,
+            );
+            device.inner.cmd_set_viewport(command_buffer, 0, &viewports);
+            device.inner.cmd_bind_descriptor_sets(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                self.pipeline.pipeline_layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+            device.inner.cmd_push_constants(
+                command_buffer,
+                self.pipeline.pipeline_layout,
+                ShaderStageFlags::VERTEX,
+                0,
+                &push_data,
+            );
+            device
+                .inner
+                .cmd_bind_vertex_buffers(command_buffer, 0, &[vertex_buffer.inner], &[0]);
+            device.inner.cmd_bind_index_buffer(
+                command_buffer,
+                index_buffer.inner,
+                0,
+                IndexType::UINT32,
+            );
+        }
+
+        let meshes = self
+            .primitives
+            .iter()
+            .filter_map(|primitive| match &primitive.primitive {
+                Primitive::Mesh(mesh) => Some((primitive.clip_rect, mesh)),
+                Primitive::Callback(_) => None,
+            });
+        for ((clip_rect, mesh), &(vertex_offset, first_index)) in meshes.zip(&self.offsets) {
+            let min_x = (clip_rect.min.x * self.pixels_per_point).clamp(0.0, extent.width as f32);
+            let min_y = (clip_rect.min.y * self.pixels_per_point).clamp(0.0, extent.height as f32);
+            let max_x = (clip_rect.max.x * self.pixels_per_point).clamp(min_x, extent.width as f32);
+            let max_y =
+                (clip_rect.max.y * self.pixels_per_point).clamp(min_y, extent.height as f32);
+            let scissors = [Rect2D::builder()
+                .offset(Offset2D {
+                    x: min_x as i32,
+                    y: min_y as i32,
+                })
+                .extent(Extent2D {
+                    width: (max_x - min_x).round() as u32,
+                    height: (max_y - min_y).round() as u32,
+                })
+                .build()];
+            unsafe {
+                device.inner.cmd_set_scissor(command_buffer, 0, &scissors);
+                device.inner.cmd_draw_indexed(
+                    command_buffer,
+                    mesh.indices.len() as u32,
+                    1,
+                    first_index,
+                    vertex_offset,
+                    0,
+                );
+            }
+        }
+
+        unsafe {
+            device.inner.cmd_end_render_pass(command_buffer);
+        }
+    }
+}
+
+fn write_growing(
+    device: &Device,
+    buffer: &mut Option<Buffer>,
+    usage: BufferUsageFlags,
+    data: &[u8],
+) {
+    let size = data.len() as DeviceSize;
+    if buffer.as_ref().is_none_or(|buffer| buffer.size < size) {
+        *buffer = Some(Buffer::new(
+            device,
+            size.next_power_of_two(),
+            usage,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        ));
+    }
+    buffer.as_mut().unwrap().write(data);
+}
+
+/// Draws over the existing contents of an image in `layout`. The depth-stencil attachment is
+/// declared but not tested, like in the fullscreen pass.
+fn create_render_pass(
+    device: &Device,
+    color_format: Format,
+    depth_format: Format,
+    layout: ImageLayout,
+) -> RenderPass {
+    let samples = SampleCountFlags::TYPE_1;
+    RenderPassBuilder::new()
+        .attachment(
+            AttachmentDescription::builder()
+                .format(color_format)
+                .samples(samples)
+                .load_op(AttachmentLoadOp::LOAD)
+                .store_op(AttachmentStoreOp::STORE)
+                .initial_layout(layout)
+                .final_layout(layout)
+                .build(),
+        )
+        .attachment(depth_stencil_attachment(
+            depth_format,
+            samples,
+            AttachmentLoadOp::DONT_CARE,
+        ))
+        .subpass(Subpass::default().color(0).depth_stencil(1))
+        .external_dependency(0)
+        .build(device)
+}
+
+/// Alpha-blended pipeline for `create_render_pass`.
+fn create_pipeline(device: &Device, render_pass: &RenderPass) -> GraphicsPipeline {
+    let vert_shader_module = ShaderModule::new(device, include_bytes!("shaders/ui_vert.spv"));
+    let frag_shader_module = ShaderModule::new(device, include_bytes!("shaders/ui_frag.spv"));
+    let p_name = CString::new("main").unwrap();
+    let shader_stage_create_infos = [
+        PipelineShaderStageCreateInfo::builder()
+            .stage(ShaderStageFlags::VERTEX)
+            .module(vert_shader_module.inner)
+            .name(&p_name)
+            .build(),
+        PipelineShaderStageCreateInfo::builder()
+            .stage(ShaderStageFlags::FRAGMENT)
+            .module(frag_shader_module.inner)
+            .name(&p_name)
+            .build(),
+    ];
+
+    // `egui::epaint::Vertex` is `#[repr(C)]`: pos [f32; 2], uv [f32; 2], color [u8; 4].
+    let binding_descriptions = [VertexInputBindingDescription::builder()
+        .binding(0)
+        .stride(size_of::<egui::epaint::Vertex>() as u32)
+        .input_rate(VertexInputRate::VERTEX)
+        .build()];
+    let attribute_descriptions = [
+        VertexInputAttributeDescription::builder()
+            .binding(0)
+            .location(0)
+            .format(Format::R32G32_SFLOAT)
+            .offset(0)
+            .build(),
+        VertexInputAttributeDescription::builder()
+            .binding(0)
+            .location(1)
+            .format(Format::R32G32_SFLOAT)
+            .offset(8)
+            .build(),
+        VertexInputAttributeDescription::builder()
+            .binding(0)
+            .location(2)
+            .format(Format::R8G8B8A8_UNORM)
+            .offset(16)
+            .build(),
+    ];
+    let vertex_input_create_info = PipelineVertexInputStateCreateInfo::builder()
+        .vertex_binding_descriptions(&binding_descriptions)
+        .vertex_attribute_descriptions(&attribute_descriptions);
+    let input_assembly_create_info = PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(PrimitiveTopology::TRIANGLE_LIST)
+        .primitive_restart_enable(false);
+    let viewport_create_info = PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissor_count(1);
+    let dynamic_states = [DynamicState::VIEWPORT, DynamicState::SCISSOR];
+    let dynamic_state_create_info =
+        PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
+    let rasterizer_create_info = PipelineRasterizationStateCreateInfo::builder()
+        .polygon_mode(PolygonMode::FILL)
+        .line_width(1.0)
+        .cull_mode(CullModeFlags::NONE);
+    let multisample_create_info =
+        PipelineMultisampleStateCreateInfo::builder().rasterization_samples(render_pass.samples);
+    // egui outputs premultiplied alpha.
+    let color_blend_attachments = [PipelineColorBlendAttachmentState::builder()
+        .color_write_mask(
+            ColorComponentFlags::R
+                | ColorComponentFlags::G
+                | ColorComponentFlags::B
+                | ColorComponentFlags::A,
+        )
+        .blend_enable(true)
+        .src_color_blend_factor(BlendFactor::ONE)
+        .dst_color_blend_factor(BlendFactor::ONE_MINUS_SRC_ALPHA)
+        .color_blend_op(BlendOp::ADD)
+        .src_alpha_blend_factor(BlendFactor::ONE_MINUS_DST_ALPHA)
+        .dst_alpha_blend_factor(BlendFactor::ONE)
+        .alpha_blend_op(BlendOp::ADD)
+        .build()];
+    let color_blend_create_info =
+        PipelineColorBlendStateCreateInfo::builder().attachments(&color_blend_attachments);
+    let depth_stencil_create_info = PipelineDepthStencilStateCreateInfo::builder();
+
+    let push_constant_ranges = [PushConstantRange::builder()
+        .stage_flags(ShaderStageFlags::VERTEX)
+        .offset(0)
+        .size(size_of::<[f32; 2]>() as u32)
+        .build()];
+    let descriptor_set_layout_bindings = [DescriptorSetLayoutBinding::builder()
+        .binding(0)
+        .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .descriptor_count(1)
+        .stage_flags(ShaderStageFlags::FRAGMENT)
+        .build()];
+    let descriptor_set_layout_create_info =
+        DescriptorSetLayoutCreateInfo::builder().bindings(&descriptor_set_layout_bindings);
+    let descriptor_set_layout = unsafe {
+        device
+            .inner
+            .create_descriptor_set_layout(&descriptor_set_layout_create_info, None)
+            .unwrap()
+    };
+    let set_layouts = [descriptor_set_layout];
+    let pipeline_layout_create_info = PipelineLayoutCreateInfo::builder()
+        .set_layouts(&set_layouts)
+        .push_constant_ranges(&push_constant_ranges);
+    let pipeline_layout = unsafe {
+        device
+            .inner
+            .create_pipeline_layout(&pipeline_layout_create_info, None)
+            .unwrap()
+    };
+
+    let create_infos = [GraphicsPipelineCreateInfo::builder()
+        .stages(&shader_stage_create_infos)
+        .vertex_input_state(&vertex_input_create_info)
+        .input_assembly_state(&input_assembly_create_info)
+        .viewport_state(&viewport_create_info)
+        .dynamic_state(&dynamic_state_create_info)
+        .rasterization_state(&rasterizer_create_info)
+        .multisample_state(&multisample_create_info)
+        .color_blend_state(&color_blend_create_info)
+        .depth_stencil_state(&depth_stencil_create_info)
+        .layout(pipeline_layout)
+        .render_pass(render_pass.inner)
+        .subpass(0)
+        .build()];
+    let inner = unsafe {
+        device
+            .inner
+            .create_graphics_pipelines(PipelineCache::null(), &create_infos, None)
+            .unwrap()[0]
+    };
+
+    GraphicsPipeline {
+        inner,
+        options: PipelineOptions::default(),
+        pipeline_layout,
+        descriptor_set_layout,
+        render_pass: render_pass.inner,
+        pending: None,
+        subpass: 0,
+        subpass_count: render_pass.subpass_count,
+        samples: render_pass.samples,
+        depth_format: render_pass.depth_format,
+        device: device.inner.clone(),
+    }
+}
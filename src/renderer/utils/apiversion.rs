@@ -1,8 +1,11 @@
+use std::fmt;
+
 use ash::vk::{
     api_version_major, api_version_minor, api_version_patch, api_version_variant, make_api_version,
 };
 
-#[derive(PartialEq, Eq, PartialOrd, Debug, Clone, Copy)]
+/// Ordered by variant, then major, minor and patch, consistent with `Eq`.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub struct ApiVersion {
     variant: u8,
     major: u8,
@@ -34,6 +37,22 @@ impl ApiVersion {
     }
 }
 
+/// Formats as `major.minor.patch`, prefixed with `variant-` when the variant is nonzero.
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.variant != 0 {
+            write!(f, "{}-", self.variant)?;
+        }
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl fmt::Debug for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ApiVersion({})", self)
+    }
+}
+
 impl From<u32> for ApiVersion {
     fn from(version: u32) -> Self {
         ApiVersion {
@@ -55,27 +74,3 @@ impl From<ApiVersion> for u32 {
         )
     }
 }
-
-impl Ord for ApiVersion {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        if self.major > other.major {
-            return std::cmp::Ordering::Greater;
-        } else if self.major < other.major {
-            return std::cmp::Ordering::Less;
-        }
-
-        if self.minor > other.minor {
-            return std::cmp::Ordering::Greater;
-        } else if self.minor < other.minor {
-            return std::cmp::Ordering::Less;
-        }
-
-        if self.patch > other.patch {
-            return std::cmp::Ordering::Greater;
-        } else if self.patch < other.patch {
-            return std::cmp::Ordering::Less;
-        }
-
-        std::cmp::Ordering::Equal
-    }
-}
@@ -1,4 +1,7 @@
-use std::ffi::CString;
+use std::{
+    ffi::{c_char, CStr, CString},
+    marker::PhantomData,
+};
 
 pub fn i8_slice_to_cstring(input: &[i8]) -> CString {
     let mut result_vec = Vec::with_capacity(input.len());
@@ -11,3 +14,25 @@ pub fn i8_slice_to_cstring(input: &[i8]) -> CString {
     }
     unsafe { CString::from_vec_unchecked(result_vec) }
 }
+
+/// Raw pointers to C strings for Vulkan create infos, borrowing the strings for `'a` so the
+/// borrow checker keeps them alive and unchanged until the create call is done.
+pub struct CStrPtrs<'a> {
+    ptrs: Vec<*const c_char>,
+    _names: PhantomData<&'a CStr>,
+}
+
+impl<'a> CStrPtrs<'a> {
+    pub fn new(names: impl IntoIterator<Item = &'a CStr>) -> Self {
+        let ptrs: Vec<*const c_char> = names.into_iter().map(CStr::as_ptr).collect();
+        debug_assert!(ptrs.iter().all(|ptr| !ptr.is_null()));
+        Self {
+            ptrs,
+            _names: PhantomData,
+        }
+    }
+
+    pub fn as_slice(&self) -> &[*const c_char] {
+        &self.ptrs
+    }
+}
@@ -1,11 +1,15 @@
-use std::ffi::{c_void, CStr};
+use std::{
+    ffi::{c_void, CStr, CString},
+    sync::{Arc, Mutex},
+};
 
 use ash::{
     extensions::ext::DebugUtils,
     vk::{
-        self, DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT,
-        DebugUtilsMessengerCallbackDataEXT, DebugUtilsMessengerCreateInfoEXT,
-        DebugUtilsMessengerCreateInfoEXTBuilder, DebugUtilsMessengerEXT,
+        self, CommandBuffer, DebugUtilsLabelEXT, DebugUtilsMessageSeverityFlagsEXT,
+        DebugUtilsMessageTypeFlagsEXT, DebugUtilsMessengerCallbackDataEXT,
+        DebugUtilsMessengerCreateInfoEXT, DebugUtilsMessengerCreateInfoEXTBuilder,
+        DebugUtilsMessengerEXT, DebugUtilsObjectNameInfoEXT, Handle,
     },
     Entry, Instance,
 };
@@ -15,7 +19,7 @@ unsafe extern "system" fn debug_callback(
     severity: DebugUtilsMessageSeverityFlagsEXT,
     msg_type: DebugUtilsMessageTypeFlagsEXT,
     callback_data: *const DebugUtilsMessengerCallbackDataEXT,
-    _user_data: *mut c_void,
+    user_data: *mut c_void,
 ) -> vk::Bool32 {
     let type_prefix = match msg_type {
         DebugUtilsMessageTypeFlagsEXT::GENERAL => "GENERAL",
@@ -43,18 +47,44 @@ unsafe extern "system" fn debug_callback(
             error!("[UNKNOWN SEVERITY] [{}] {:?}", type_prefix, message);
         }
     }
+
+    // The messenger created by `DebugMessenger::new` points the user data at its message
+    // collector. The one chained into instance creation has no collector.
+    if !user_data.is_null() {
+        let messages = &*(user_data as *const Mutex<Vec<ValidationMessage>>);
+        messages.lock().unwrap().push(ValidationMessage {
+            severity,
+            message_type: msg_type,
+            message: message.to_string_lossy().into_owned(),
+        });
+    }
+
     vk::FALSE
 }
 
+#[derive(Clone, Debug)]
+pub struct ValidationMessage {
+    pub severity: DebugUtilsMessageSeverityFlagsEXT,
+    pub message_type: DebugUtilsMessageTypeFlagsEXT,
+    pub message: String,
+}
+
 pub struct DebugMessenger {
     pub loader: DebugUtils,
     pub messenger: DebugUtilsMessengerEXT,
+    messages: Arc<Mutex<Vec<ValidationMessage>>>,
 }
 
 impl DebugMessenger {
-    pub fn new(entry: &Entry, instance: &Instance) -> Self {
+    pub fn new(
+        entry: &Entry,
+        instance: &Instance,
+        severity: DebugUtilsMessageSeverityFlagsEXT,
+    ) -> Self {
         let loader = DebugUtils::new(entry, instance);
-        let create_info = DebugMessenger::get_create_info();
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let create_info = DebugMessenger::get_create_info(severity)
+            .user_data(Arc::as_ptr(&messages) as *mut c_void);
         let messenger = unsafe {
             loader
                 .create_debug_utils_messenger(&create_info, None)
@@ -64,17 +94,65 @@ impl DebugMessenger {
         Self {
             loader: DebugUtils::new(entry, instance),
             messenger,
+            messages,
         }
     }
 
-    pub fn get_create_info() -> DebugUtilsMessengerCreateInfoEXTBuilder<'static> {
+    /// Removes and returns all messages reported since the last call.
+    pub fn take_messages(&self) -> Vec<ValidationMessage> {
+        std::mem::take(&mut *self.messages.lock().unwrap())
+    }
+
+    /// Number of collected error messages that haven't been taken yet.
+    pub fn error_count(&self) -> usize {
+        self.messages
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|message| message.severity == DebugUtilsMessageSeverityFlagsEXT::ERROR)
+            .count()
+    }
+
+    /// Attaches a name to a Vulkan object so validation messages and captures refer to it by name.
+    /// Does nothing in release builds.
+    pub fn set_object_name<T: Handle>(&self, device: &ash::Device, handle: T, name: &str) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+
+        let name = CString::new(name).unwrap();
+        let name_info = DebugUtilsObjectNameInfoEXT::builder()
+            .object_type(T::TYPE)
+            .object_handle(handle.as_raw())
+            .object_name(&name);
+        unsafe {
+            self.loader
+                .debug_utils_set_object_name(device.handle(), &name_info)
+                .unwrap();
+        }
+    }
+
+    /// Opens a labeled region in `command_buffer` that shows up in frame captures.
+    pub fn cmd_begin_label(&self, command_buffer: CommandBuffer, name: &str, color: [f32; 4]) {
+        let name = CString::new(name).unwrap();
+        let label = DebugUtilsLabelEXT::builder().label_name(&name).color(color);
+        unsafe {
+            self.loader
+                .cmd_begin_debug_utils_label(command_buffer, &label);
+        }
+    }
+
+    pub fn cmd_end_label(&self, command_buffer: CommandBuffer) {
+        unsafe {
+            self.loader.cmd_end_debug_utils_label(command_buffer);
+        }
+    }
+
+    pub fn get_create_info(
+        severity: DebugUtilsMessageSeverityFlagsEXT,
+    ) -> DebugUtilsMessengerCreateInfoEXTBuilder<'static> {
         DebugUtilsMessengerCreateInfoEXT::builder()
-            .message_severity(
-                vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
-                    | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
-                    | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
-                    | vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
-            )
+            .message_severity(severity)
             .message_type(
                 vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
                     | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
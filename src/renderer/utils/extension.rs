@@ -1,4 +1,7 @@
-use std::ffi::CString;
+use std::{
+    ffi::CString,
+    fmt::{Display, Formatter},
+};
 
 use ash::vk::ExtensionProperties;
 
@@ -28,3 +31,17 @@ impl Extension {
         result
     }
 }
+
+impl Display for Extension {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.spec_version {
+            Some(spec_version) => write!(
+                f,
+                "{} (spec version {})",
+                self.name.to_string_lossy(),
+                spec_version
+            ),
+            None => write!(f, "{}", self.name.to_string_lossy()),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use ash::vk::{self, PhysicalDeviceFeatures};
+
+macro_rules! feature_fields {
+    ($($field:ident),* $(,)?) => {
+        /// Splits `requested` into the features `supported` actually offers and the names of the ones it doesn't.
+        pub fn filter_supported(
+            requested: &PhysicalDeviceFeatures,
+            supported: &PhysicalDeviceFeatures,
+        ) -> (PhysicalDeviceFeatures, Vec<&'static str>) {
+            let mut granted = PhysicalDeviceFeatures::default();
+            let mut unsupported = Vec::new();
+            $(
+                if requested.$field == vk::TRUE {
+                    if supported.$field == vk::TRUE {
+                        granted.$field = vk::TRUE;
+                    } else {
+                        unsupported.push(stringify!($field));
+                    }
+                }
+            )*
+            (granted, unsupported)
+        }
+    };
+}
+
+feature_fields!(
+    robust_buffer_access,
+    full_draw_index_uint32,
+    image_cube_array,
+    independent_blend,
+    geometry_shader,
+    tessellation_shader,
+    sample_rate_shading,
+    dual_src_blend,
+    logic_op,
+    multi_draw_indirect,
+    draw_indirect_first_instance,
+    depth_clamp,
+    depth_bias_clamp,
+    fill_mode_non_solid,
+    depth_bounds,
+    wide_lines,
+    large_points,
+    alpha_to_one,
+    multi_viewport,
+    sampler_anisotropy,
+    texture_compression_etc2,
+    texture_compression_astc_ldr,
+    texture_compression_bc,
+    occlusion_query_precise,
+    pipeline_statistics_query,
+    vertex_pipeline_stores_and_atomics,
+    fragment_stores_and_atomics,
+    shader_tessellation_and_geometry_point_size,
+    shader_image_gather_extended,
+    shader_storage_image_extended_formats,
+    shader_storage_image_multisample,
+    shader_storage_image_read_without_format,
+    shader_storage_image_write_without_format,
+    shader_uniform_buffer_array_dynamic_indexing,
+    shader_sampled_image_array_dynamic_indexing,
+    shader_storage_buffer_array_dynamic_indexing,
+    shader_storage_image_array_dynamic_indexing,
+    shader_clip_distance,
+    shader_cull_distance,
+    shader_float64,
+    shader_int64,
+    shader_int16,
+    shader_resource_residency,
+    shader_resource_min_lod,
+    sparse_binding,
+    sparse_residency_buffer,
+    sparse_residency_image2_d,
+    sparse_residency_image3_d,
+    sparse_residency2_samples,
+    sparse_residency4_samples,
+    sparse_residency8_samples,
+    sparse_residency16_samples,
+    sparse_residency_aliased,
+    variable_multisample_rate,
+    inherited_queries,
+);
+
+/// What the device ended up with, for hiding options the GPU can't back.
+#[derive(Clone, Debug)]
+pub struct DeviceFeatureSet {
+    /// Subset of the requested core features the device supports.
+    pub enabled: PhysicalDeviceFeatures,
+    /// Requested core features that were left out.
+    pub unsupported: Vec<&'static str>,
+    pub timeline_semaphore: bool,
+    pub descriptor_indexing: bool,
+    pub dynamic_rendering: bool,
+    pub extended_dynamic_state: bool,
+}
+
+impl DeviceFeatureSet {
+    /// Samplers fall back to no anisotropy without it.
+    pub fn sampler_anisotropy(&self) -> bool {
+        self.enabled.sampler_anisotropy == vk::TRUE
+    }
+
+    /// Lines are drawn 1 pixel wide without it.
+    pub fn wide_lines(&self) -> bool {
+        self.enabled.wide_lines == vk::TRUE
+    }
+
+    /// Needed for wireframe and point polygon modes.
+    pub fn fill_mode_non_solid(&self) -> bool {
+        self.enabled.fill_mode_non_solid == vk::TRUE
+    }
+
+    pub fn geometry_shader(&self) -> bool {
+        self.enabled.geometry_shader == vk::TRUE
+    }
+
+    pub fn tessellation_shader(&self) -> bool {
+        self.enabled.tessellation_shader == vk::TRUE
+    }
+}
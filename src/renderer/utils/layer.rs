@@ -1,4 +1,7 @@
-use std::ffi::CString;
+use std::{
+    ffi::CString,
+    fmt::{Display, Formatter},
+};
 
 use ash::vk::LayerProperties;
 
@@ -34,3 +37,15 @@ impl Layer {
         result
     }
 }
+
+impl Display for Layer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (spec version {}, implementation version {})",
+            self.name.to_string_lossy(),
+            self.spec_version,
+            self.implementation_version
+        )
+    }
+}
@@ -1,6 +1,11 @@
+//! Small helpers shared across the renderer. Queue family and swapchain support queries live in
+//! `physical_device.rs` only, don't add parallel versions here.
+
 pub mod apiversion;
 pub mod cstringstuff;
 pub mod debug;
 pub mod extension;
+pub mod features;
 pub mod layer;
+pub mod pixels;
 pub mod properties;
@@ -0,0 +1,40 @@
+/// Swaps the red and blue channels of tightly packed 4-byte pixels in place, turning
+/// `B8G8R8A8` readbacks into RGBA.
+pub fn bgra_to_rgba(bytes: &mut [u8]) {
+    for pixel in bytes.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+}
+
+/// Like `bgra_to_rgba` for rows padded to `row_length` pixels, the copy's `bufferRowLength`.
+/// Only the first `width` pixels of each row are converted, the padding is left as is.
+pub fn bgra_to_rgba_rows(bytes: &mut [u8], width: usize, row_length: usize) {
+    assert!(width <= row_length, "Row length is shorter than the width");
+    for row in bytes.chunks_mut(row_length * 4) {
+        let len = row.len().min(width * 4);
+        bgra_to_rgba(&mut row[..len]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bgra_to_rgba_swaps_red_and_blue() {
+        let mut bytes = [1, 2, 3, 4, 5, 6, 7, 8];
+        bgra_to_rgba(&mut bytes);
+        assert_eq!(bytes, [3, 2, 1, 4, 7, 6, 5, 8]);
+    }
+
+    #[test]
+    fn bgra_to_rgba_rows_leaves_padding() {
+        // Two rows of one pixel, padded to two pixels each.
+        let mut bytes = [1, 2, 3, 4, 10, 20, 30, 40, 5, 6, 7, 8, 50, 60, 70, 80];
+        bgra_to_rgba_rows(&mut bytes, 1, 2);
+        assert_eq!(
+            bytes,
+            [3, 2, 1, 4, 10, 20, 30, 40, 7, 6, 5, 8, 50, 60, 70, 80]
+        );
+    }
+}
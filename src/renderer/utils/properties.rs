@@ -13,11 +13,12 @@ pub struct PhysicalDeviceProperties {
 
 impl PhysicalDeviceProperties {}
 
-impl Into<PhysicalDeviceProperties> for ash::vk::PhysicalDeviceProperties {
-    fn into(self) -> PhysicalDeviceProperties {
+impl From<ash::vk::PhysicalDeviceProperties> for PhysicalDeviceProperties {
+    fn from(properties: ash::vk::PhysicalDeviceProperties) -> Self {
         let name = unsafe {
             CString::from_vec_unchecked(
-                self.device_name
+                properties
+                    .device_name
                     .to_vec()
                     .iter()
                     .filter_map(|x| {
@@ -33,10 +34,10 @@ impl Into<PhysicalDeviceProperties> for ash::vk::PhysicalDeviceProperties {
         };
 
         PhysicalDeviceProperties {
-            api_version: ApiVersion::from(self.api_version),
-            device_type: self.device_type,
+            api_version: ApiVersion::from(properties.api_version),
+            device_type: properties.device_type,
             name,
-            limits: self.limits,
+            limits: properties.limits,
         }
     }
 }
@@ -0,0 +1,83 @@
+use std::mem::{offset_of, size_of};
+
+use ash::vk::{
+    Format, VertexInputAttributeDescription, VertexInputBindingDescription, VertexInputRate,
+};
+use bytemuck::{Pod, Zeroable};
+
+/// Vertex of a mesh, read from vertex input binding 0. The layout matches the inputs of
+/// `base_shader.vert`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+pub struct Vertex {
+    pub pos: [f32; 3],
+    /// Linear RGBA, multiplied with the sampled texture.
+    pub color: [f32; 4],
+    pub uv: [f32; 2],
+    pub normal: [f32; 3],
+}
+
+impl Vertex {
+    pub fn binding_description() -> VertexInputBindingDescription {
+        VertexInputBindingDescription::builder()
+            .binding(0)
+            .stride(size_of::<Vertex>() as u32)
+            .input_rate(VertexInputRate::VERTEX)
+            .build()
+    }
+
+    pub fn attribute_descriptions() -> [VertexInputAttributeDescription; 4] {
+        [
+            VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(0)
+                .format(Format::R32G32B32_SFLOAT)
+                .offset(offset_of!(Vertex, pos) as u32)
+                .build(),
+            VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(1)
+                .format(Format::R32G32B32A32_SFLOAT)
+                .offset(offset_of!(Vertex, color) as u32)
+                .build(),
+            VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(2)
+                .format(Format::R32G32_SFLOAT)
+                .offset(offset_of!(Vertex, uv) as u32)
+                .build(),
+            VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(3)
+                .format(Format::R32G32B32_SFLOAT)
+                .offset(offset_of!(Vertex, normal) as u32)
+                .build(),
+        ]
+    }
+}
+
+/// Per-instance data read from vertex input binding 1.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+pub struct InstanceData {
+    pub offset: [f32; 3],
+}
+
+impl InstanceData {
+    pub fn binding_description() -> VertexInputBindingDescription {
+        VertexInputBindingDescription::builder()
+            .binding(1)
+            .stride(size_of::<InstanceData>() as u32)
+            .input_rate(VertexInputRate::INSTANCE)
+            .build()
+    }
+
+    pub fn attribute_descriptions() -> [VertexInputAttributeDescription; 1] {
+        [VertexInputAttributeDescription::builder()
+            .binding(1)
+            .location(4)
+            .format(Format::R32G32B32_SFLOAT)
+            .offset(offset_of!(InstanceData, offset) as u32)
+            .build()]
+    }
+}
@@ -0,0 +1,356 @@
+use glam::{IVec3, Vec3};
+
+use super::vertex::Vertex;
+
+/// Block id of empty space, which produces no faces.
+pub const AIR: u16 = 0;
+
+/// Box of block ids, indexed `[x][y][z]`.
+pub struct Chunk {
+    pub dims: [usize; 3],
+    /// `dims[0] * dims[1] * dims[2]` ids with `z` varying fastest.
+    pub blocks: Vec<u16>,
+}
+
+impl Chunk {
+    pub fn new(dims: [usize; 3], blocks: Vec<u16>) -> Self {
+        assert_eq!(
+            blocks.len(),
+            dims[0] * dims[1] * dims[2],
+            "Block count doesn't match the chunk dimensions"
+        );
+        Self { dims, blocks }
+    }
+
+    pub fn from_array<const N: usize>(blocks: &[[[u16; N]; N]; N]) -> Self {
+        let blocks = blocks.iter().flatten().flatten().copied().collect();
+        Self::new([N; 3], blocks)
+    }
+
+    pub fn get(&self, pos: [usize; 3]) -> u16 {
+        self.blocks[(pos[0] * self.dims[1] + pos[1]) * self.dims[2] + pos[2]]
+    }
+
+    /// Like `get`, with `AIR` outside of the chunk.
+    fn get_or_air(&self, pos: [isize; 3]) -> u16 {
+        if (0..3).all(|i| pos[i] >= 0 && (pos[i] as usize) < self.dims[i]) {
+            self.get(pos.map(|i| i as usize))
+        } else {
+            AIR
+        }
+    }
+}
+
+/// Visible face of a block in a slice, facing `+axis` if `positive`.
+#[derive(Clone, Copy, PartialEq)]
+struct Face {
+    block: u16,
+    positive: bool,
+}
+
+/// Merges the visible faces of `chunk` into as few quads as possible, for `Renderer::set_mesh`.
+/// Faces between two solid blocks and faces of different blocks aren't merged. Blocks span
+/// one unit with the chunk's corner at the origin, and texture coordinates count blocks so a
+/// repeating sampler tiles the texture across merged quads.
+pub fn greedy_mesh(
+    chunk: &Chunk,
+    block_color: impl Fn(u16) -> [f32; 4],
+) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+
+    for d in 0..3 {
+        let (u, v) = ((d + 1) % 3, (d + 2) % 3);
+        let (width, height) = (chunk.dims[u], chunk.dims[v]);
+        let mut mask = vec![None; width * height];
+
+        // Slice `x` holds the faces between layers `x` and `x + 1` along `d`.
+        for x in -1..chunk.dims[d] as isize {
+            for j in 0..height {
+                for i in 0..width {
+                    let mut pos = [0; 3];
+                    pos[d] = x;
+                    pos[u] = i as isize;
+                    pos[v] = j as isize;
+                    let behind = chunk.get_or_air(pos);
+                    pos[d] += 1;
+                    let front = chunk.get_or_air(pos);
+                    mask[j * width + i] = match (behind != AIR, front != AIR) {
+                        (true, false) => Some(Face {
+                            block: behind,
+                            positive: true,
+                        }),
+                        (false, true) => Some(Face {
+                            block: front,
+                            positive: false,
+                        }),
+                        _ => None,
+                    };
+                }
+            }
+
+            for j in 0..height {
+                let mut i = 0;
+                while i < width {
+                    let Some(face) = mask[j * width + i] else {
+                        i += 1;
+                        continue;
+                    };
+                    let w = (i..width)
+                        .take_while(|&i| mask[j * width + i] == Some(face))
+                        .count();
+                    let h = (j..height)
+                        .take_while(|&j| (i..i + w).all(|i| mask[j * width + i] == Some(face)))
+                        .count();
+                    for j in j..j + h {
+                        mask[j * width + i..j * width + i + w].fill(None);
+                    }
+
+                    let mut origin = [0.0; 3];
+                    origin[d] = (x + 1) as f32;
+                    origin[u] = i as f32;
+                    origin[v] = j as f32;
+                    let corner = |su: usize, sv: usize| {
+                        let mut pos = origin;
+                        pos[u] += su as f32;
+                        pos[v] += sv as f32;
+                        (pos, [su as f32, sv as f32])
+                    };
+                    let mut normal = [0.0; 3];
+                    normal[d] = if face.positive { 1.0 } else { -1.0 };
+                    // `u x v` points along `+d`, so flip the winding for faces looking the other
+                    // way to keep them counter-clockwise seen from outside.
+                    let corners = if face.positive {
+                        [corner(0, 0), corner(w, 0), corner(w, h), corner(0, h)]
+                    } else {
+                        [corner(0, 0), corner(0, h), corner(w, h), corner(w, 0)]
+                    };
+                    let color = block_color(face.block);
+                    let base = vertices.len() as u32;
+                    vertices.extend(corners.map(|(pos, uv)| Vertex {
+                        pos,
+                        color,
+                        uv,
+                        normal,
+                    }));
+                    indices.extend([0, 1, 2, 2, 3, 0].map(|i| base + i));
+
+                    i += w;
+                }
+            }
+        }
+    }
+
+    (vertices, indices)
+}
+
+/// Side of a block, the order matches `mesh::cube_with_colors`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FaceNormal {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl FaceNormal {
+    fn new(axis: usize, positive: bool) -> Self {
+        match (axis, positive) {
+            (0, true) => Self::PosX,
+            (0, false) => Self::NegX,
+            (1, true) => Self::PosY,
+            (1, false) => Self::NegY,
+            (2, true) => Self::PosZ,
+            _ => Self::NegZ,
+        }
+    }
+
+    /// Offset to the neighboring block on this side, e.g. where a placed block goes.
+    pub fn normal(self) -> IVec3 {
+        match self {
+            Self::PosX => IVec3::X,
+            Self::NegX => IVec3::NEG_X,
+            Self::PosY => IVec3::Y,
+            Self::NegY => IVec3::NEG_Y,
+            Self::PosZ => IVec3::Z,
+            Self::NegZ => IVec3::NEG_Z,
+        }
+    }
+}
+
+/// First solid block of `chunk` hit by the ray within `max_dist`, and the face it was entered
+/// through, e.g. from `Camera::position` along `Camera::forward` for picking. Uses the
+/// Amanatides-Woo voxel traversal in the chunk's coordinates, the block containing `origin` is
+/// skipped. Stops once the ray has left the chunk, so `max_dist` may be infinite.
+pub fn raycast_voxel(
+    origin: Vec3,
+    dir: Vec3,
+    chunk: &Chunk,
+    max_dist: f32,
+) -> Option<(IVec3, FaceNormal)> {
+    let dir = dir.normalize_or_zero();
+    if dir == Vec3::ZERO {
+        return None;
+    }
+    let mut voxel = origin.floor().as_ivec3();
+    // `signum` is ±1 for ±0.0, axes the ray is parallel to don't step at all.
+    let step = IVec3::from_array(
+        dir.to_array()
+            .map(|d| if d == 0.0 { 0 } else { d.signum() as i32 }),
+    );
+    // Ray distance between two boundaries and to the next boundary on each axis, infinite for
+    // axes the ray is parallel to.
+    let t_delta = dir.recip().abs();
+    let mut t_max = Vec3::from_array([0, 1, 2].map(|i| {
+        if step[i] == 0 {
+            f32::INFINITY
+        } else {
+            (voxel[i] as f32 + step[i].max(0) as f32 - origin[i]) / dir[i]
+        }
+    }));
+
+    while !leaves_chunk(voxel, step, chunk) {
+        let axis = if t_max.x < t_max.y && t_max.x < t_max.z {
+            0
+        } else if t_max.y < t_max.z {
+            1
+        } else {
+            2
+        };
+        if t_max[axis] > max_dist {
+            return None;
+        }
+        voxel[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+        if chunk.get_or_air(voxel.to_array().map(|i| i as isize)) != AIR {
+            return Some((voxel, FaceNormal::new(axis, step[axis] < 0)));
+        }
+    }
+    None
+}
+
+/// Whether `voxel` is outside of `chunk` on an axis `step` doesn't lead back into it.
+fn leaves_chunk(voxel: IVec3, step: IVec3, chunk: &Chunk) -> bool {
+    (0..3).any(|i| {
+        (voxel[i] < 0 && step[i] <= 0) || (voxel[i] >= chunk.dims[i] as i32 && step[i] >= 0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `N`³ chunk with a single stone block at `pos`.
+    fn single_block<const N: usize>(pos: [usize; 3]) -> Chunk {
+        let mut blocks = [[[AIR; N]; N]; N];
+        blocks[pos[0]][pos[1]][pos[2]] = 1;
+        Chunk::from_array(&blocks)
+    }
+
+    #[test]
+    fn greedy_mesh_merges_solid_cube_into_six_quads() {
+        let chunk = Chunk::from_array(&[[[1; 4]; 4]; 4]);
+        let (vertices, indices) = greedy_mesh(&chunk, |_| [1.0; 4]);
+        assert_eq!(vertices.len(), 6 * 4);
+        assert_eq!(indices.len(), 6 * 6);
+        let mut normals: Vec<_> = vertices.chunks(4).map(|quad| quad[0].normal).collect();
+        normals.dedup();
+        assert_eq!(normals.len(), 6);
+        // Every quad covers a whole side of the chunk and faces away from its center.
+        for quad in vertices.chunks(4) {
+            let center = quad
+                .iter()
+                .map(|vertex| Vec3::from(vertex.pos))
+                .sum::<Vec3>()
+                / 4.0;
+            let outward = (center - Vec3::splat(2.0)).normalize();
+            assert_eq!(outward, Vec3::from(quad[0].normal));
+        }
+    }
+
+    #[test]
+    fn greedy_mesh_keeps_checkerboard_faces_separate() {
+        let mut blocks = [[[AIR; 3]; 3]; 3];
+        for (x, plane) in blocks.iter_mut().enumerate() {
+            for (y, row) in plane.iter_mut().enumerate() {
+                for (z, block) in row.iter_mut().enumerate() {
+                    if (x + y + z) % 2 == 0 {
+                        *block = 1;
+                    }
+                }
+            }
+        }
+        let (vertices, indices) = greedy_mesh(&Chunk::from_array(&blocks), |_| [1.0; 4]);
+        // 14 solid blocks, none of them touching another through a face.
+        assert_eq!(vertices.len(), 14 * 6 * 4);
+        assert_eq!(indices.len(), 14 * 6 * 6);
+        assert!(indices
+            .iter()
+            .all(|&index| (index as usize) < vertices.len()));
+    }
+
+    #[test]
+    fn raycast_hits_block_through_facing_side() {
+        let chunk = single_block::<4>([3, 1, 1]);
+        let hit = raycast_voxel(Vec3::new(0.5, 1.5, 1.5), Vec3::X, &chunk, 10.0);
+        assert_eq!(hit, Some((IVec3::new(3, 1, 1), FaceNormal::NegX)));
+        let hit = raycast_voxel(Vec3::new(3.5, 3.5, 1.5), Vec3::NEG_Y, &chunk, 10.0);
+        assert_eq!(hit, Some((IVec3::new(3, 1, 1), FaceNormal::PosY)));
+    }
+
+    #[test]
+    fn raycast_respects_max_dist() {
+        let chunk = single_block::<4>([3, 1, 1]);
+        assert_eq!(
+            raycast_voxel(Vec3::new(0.5, 1.5, 1.5), Vec3::X, &chunk, 2.0),
+            None
+        );
+    }
+
+    #[test]
+    fn raycast_with_negative_zero_components_terminates() {
+        let chunk = single_block::<4>([3, 1, 1]);
+        let hit = raycast_voxel(
+            Vec3::new(0.0, 1.0, 1.0),
+            Vec3::new(1.0, -0.0, -0.0),
+            &chunk,
+            f32::INFINITY,
+        );
+        assert_eq!(hit, Some((IVec3::new(3, 1, 1), FaceNormal::NegX)));
+        let miss = raycast_voxel(
+            Vec3::new(0.0, 2.0, 2.0),
+            Vec3::new(1.0, -0.0, -0.0),
+            &chunk,
+            f32::INFINITY,
+        );
+        assert_eq!(miss, None);
+    }
+
+    #[test]
+    fn raycast_stops_outside_of_chunk() {
+        let chunk = single_block::<4>([3, 1, 1]);
+        assert_eq!(
+            raycast_voxel(Vec3::new(1.5, 1.5, 1.5), Vec3::NEG_X, &chunk, f32::INFINITY),
+            None
+        );
+        assert_eq!(
+            raycast_voxel(Vec3::new(-5.5, 9.5, 1.5), Vec3::X, &chunk, f32::INFINITY),
+            None
+        );
+        assert_eq!(
+            raycast_voxel(Vec3::new(-5.5, 1.5, 1.5), Vec3::X, &chunk, f32::INFINITY),
+            Some((IVec3::new(3, 1, 1), FaceNormal::NegX))
+        );
+    }
+
+    #[test]
+    fn raycast_with_zero_direction_misses() {
+        let chunk = single_block::<4>([3, 1, 1]);
+        assert_eq!(
+            raycast_voxel(Vec3::new(0.5, 1.5, 1.5), Vec3::ZERO, &chunk, 10.0),
+            None
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use ash::{
+    prelude::VkResult,
+    vk::{
+        CommandBuffer, CommandBufferLevel, Extent2D, Fence, FenceCreateFlags, FenceCreateInfo,
+        Semaphore, SemaphoreCreateInfo,
+    },
+    Entry,
+};
+use winit::window::{Window, WindowId};
+
+use super::{
+    command_pool::CommandPool,
+    config::RendererConfig,
+    device::Device,
+    error::RendererError,
+    instance::Instance,
+    pipeline_graphics::{GraphicsPipeline, PipelineOptions},
+    render_pass::RenderPass,
+    surface::Surface,
+    swapchain::SwapChain,
+};
+
+/// An additional window sharing the renderer's instance and device, with its own surface,
+/// swapchain, pipeline and frame synchronization.
+pub struct WindowTarget {
+    pub window_id: WindowId,
+    pub command_buffer: CommandBuffer,
+    pub image_available_smph: Semaphore,
+    pub render_finished_smph: Semaphore,
+    pub in_flight_fence: Fence,
+    /// Created for the window's surface format, with the same options as `main`.
+    pub graphics_pipeline: GraphicsPipeline,
+    /// Only `None` while it's being recreated.
+    pub swap_chain: Option<SwapChain>,
+    /// Acquire or present reported the swapchain as suboptimal or out of date, cleared by
+    /// `recreate_swapchain`.
+    pub swapchain_suboptimal: bool,
+    /// Created for the window's surface format, shared by `graphics_pipeline` and the framebuffers.
+    pub render_pass: RenderPass,
+    pub surface: Surface,
+    command_pool: CommandPool,
+    device: ash::Device,
+}
+
+impl WindowTarget {
+    /// Fails with `RendererError::UnsupportedSurface` if the device's present queue can't present
+    /// to the window.
+    pub fn new(
+        entry: &Entry,
+        instance: &Instance,
+        device: &Device,
+        window: &Window,
+        main: &GraphicsPipeline,
+        config: &RendererConfig,
+    ) -> Result<Self, RendererError> {
+        let surface = Surface::new(entry, instance, window)?;
+        if !surface.is_supported(&device.physical_device)? {
+            return Err(RendererError::UnsupportedSurface);
+        }
+
+        let size = window.inner_size();
+        let mut swap_chain = SwapChain::new(
+            instance,
+            Extent2D::builder()
+                .width(size.width)
+                .height(size.height)
+                .build(),
+            &surface,
+            device,
+            config.present_mode,
+            config,
+        )?;
+        let mut options = PipelineOptions {
+            dynamic_rendering: false,
+            bindless_layout: None,
+            deferred: false,
+            ..main.options
+        };
+        let render_pass = RenderPass::new_main(
+            device,
+            swap_chain.surface_format.format,
+            main.depth_format,
+            main.samples,
+            ash::vk::ImageLayout::PRESENT_SRC_KHR,
+            &mut options,
+        );
+        let graphics_pipeline = GraphicsPipeline::new(device, &render_pass, 0, options);
+        swap_chain.create_framebuffers(device, &render_pass);
+        let mut command_pool = CommandPool::new(device);
+        let command_buffer = command_pool.allocate(CommandBufferLevel::PRIMARY);
+
+        let smph_info = SemaphoreCreateInfo::builder();
+        let fence_info = FenceCreateInfo::builder().flags(FenceCreateFlags::SIGNALED);
+        let (image_available_smph, render_finished_smph, in_flight_fence) = unsafe {
+            (
+                device.inner.create_semaphore(&smph_info, None)?,
+                device.inner.create_semaphore(&smph_info, None)?,
+                device.inner.create_fence(&fence_info, None)?,
+            )
+        };
+
+        Ok(Self {
+            window_id: window.id(),
+            command_buffer,
+            image_available_smph,
+            render_finished_smph,
+            in_flight_fence,
+            graphics_pipeline,
+            swap_chain: Some(swap_chain),
+            swapchain_suboptimal: false,
+            render_pass,
+            surface,
+            command_pool,
+            device: device.inner.clone(),
+        })
+    }
+
+    /// Rebuilds the swapchain after the window was resized. A minimized window is skipped like
+    /// in `Renderer::recreate_swapchain`. The device has to be idle. Leaves the window without a
+    /// swapchain if that fails.
+    pub fn recreate_swapchain(
+        &mut self,
+        instance: &Instance,
+        device: &Device,
+        window: &Window,
+        config: &RendererConfig,
+    ) -> VkResult<()> {
+        let size = window.inner_size();
+        if size.width == 0 || size.height == 0 {
+            return Ok(());
+        }
+        // The old swapchain has to be destroyed before a new one is created for the same window.
+        let present_mode = self
+            .swap_chain
+            .take()
+            .map(|swap_chain| swap_chain.present_mode);
+        let mut swap_chain = SwapChain::new(
+            instance,
+            Extent2D::builder()
+                .width(size.width)
+                .height(size.height)
+                .build(),
+            &self.surface,
+            device,
+            present_mode,
+            config,
+        )?;
+        swap_chain.create_framebuffers(device, &self.render_pass);
+        self.swap_chain = Some(swap_chain);
+        self.swapchain_suboptimal = false;
+        Ok(())
+    }
+}
+
+impl Drop for WindowTarget {
+    fn drop(&mut self) {
+        unsafe {
+            self.device
+                .destroy_semaphore(self.image_available_smph, None);
+            self.device
+                .destroy_semaphore(self.render_finished_smph, None);
+            self.device.destroy_fence(self.in_flight_fence, None);
+        }
+    }
+}